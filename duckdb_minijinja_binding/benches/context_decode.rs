@@ -0,0 +1,156 @@
+//! Compares rendering with a JSON context against the same context encoded
+//! as MessagePack.
+//!
+//! The template only prints one field, so the time is dominated by decoding
+//! the context.  Run with `cargo bench --bench context_decode`.
+
+use std::ffi::c_char;
+use std::hint::black_box;
+use std::ptr;
+use std::time::{Duration, Instant};
+
+use duckdb_minijinja_binding::{free_result_cstring, render_template, ResultCString};
+use serde::{Serialize, Serializer};
+use serde_json::{json, Value as JsonValue};
+
+const ITERATIONS: u32 = 20_000;
+const TEMPLATE: &str = "{{ order.id }}";
+
+/// A row as a wide table would produce it, with a few nested values.
+fn payload() -> JsonValue {
+    let items: Vec<JsonValue> = (0..8)
+        .map(|i| {
+            json!({
+                "sku": format!("SKU-{:05}", i * 37),
+                "name": format!("Item number {}", i),
+                "quantity": i + 1,
+                "price": 9.99 + i as f64,
+                "tags": ["clearance", "outdoor", "summer"],
+            })
+        })
+        .collect();
+    json!({
+        "order": {
+            "id": 184467,
+            "created_at": "2024-05-17T09:31:00Z",
+            "status": "shipped",
+            "total": 312.45,
+            "paid": true,
+            "notes": null,
+            "items": items,
+        },
+        "customer": {
+            "id": 99120,
+            "name": "Ada Lovelace",
+            "email": "ada@example.com",
+            "address": {
+                "street": "12 Analytical Row",
+                "city": "London",
+                "postal_code": "NW1 6XE",
+                "country": "GB",
+            },
+            "loyalty_points": 1520,
+        },
+    })
+}
+
+/// Serializes a JSON value with plain numbers.
+///
+/// With `arbitrary_precision` serde_json writes numbers as maps holding their
+/// digits, which MessagePack would encode verbatim.
+struct PlainNumbers<'a>(&'a JsonValue);
+
+impl Serialize for PlainNumbers<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.0 {
+            JsonValue::Number(n) => match (n.as_i64(), n.as_u64()) {
+                (Some(i), _) => serializer.serialize_i64(i),
+                (_, Some(u)) => serializer.serialize_u64(u),
+                _ => serializer.serialize_f64(n.as_f64().unwrap_or(f64::NAN)),
+            },
+            JsonValue::Array(items) => serializer.collect_seq(items.iter().map(PlainNumbers)),
+            JsonValue::Object(map) => {
+                serializer.collect_map(map.iter().map(|(key, value)| (key, PlainNumbers(value))))
+            }
+            value => value.serialize(serializer),
+        }
+    }
+}
+
+/// Renders `TEMPLATE` with the context in the given format.
+fn render(context: &[u8], context_format: &std::ffi::CStr) {
+    let result = unsafe {
+        render_template(
+            TEMPLATE.as_ptr() as *const c_char,
+            TEMPLATE.len(),
+            context.as_ptr() as *const c_char,
+            context.len(),
+            ptr::null(),
+            true,
+            ptr::null(),
+            ptr::null(),
+            0,
+            ptr::null(),
+            ptr::null(),
+            ptr::null(),
+            ptr::null(),
+            ptr::null(),
+            ptr::null(),
+            ptr::null(),
+            ptr::null(),
+            false,
+            false,
+            false,
+            0,
+            0,
+            false,
+            false,
+            -1,
+            true,
+            ptr::null(),
+            ptr::null(),
+            -1,
+            false,
+            context_format.as_ptr(),
+            false,
+        )
+    };
+    assert!(
+        matches!(result, ResultCString::Ok(_)),
+        "rendering with a {:?} context failed",
+        context_format
+    );
+    unsafe { free_result_cstring(black_box(result)) };
+}
+
+fn bench(name: &str, context: &[u8], context_format: &std::ffi::CStr) -> Duration {
+    // Warm up the template cache.
+    render(context, context_format);
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        render(black_box(context), context_format);
+    }
+    let per_render = start.elapsed() / ITERATIONS;
+    println!(
+        "{:<12} {:>5} bytes  {:>8.2?} per render",
+        name,
+        context.len(),
+        per_render
+    );
+    per_render
+}
+
+fn main() {
+    let payload = payload();
+    let json = serde_json::to_vec(&payload).unwrap();
+    let msgpack = rmp_serde::to_vec(&PlainNumbers(&payload)).unwrap();
+
+    let json_time = bench("json", &json, c"json");
+    let msgpack_time = bench("msgpack", &msgpack, c"msgpack");
+    // Rendering with an almost empty context, for reference.
+    bench("tiny", br#"{"order": {"id": 1}}"#, c"json");
+    println!(
+        "msgpack takes {:.0}% of the json time",
+        100.0 * msgpack_time.as_secs_f64() / json_time.as_secs_f64()
+    );
+}
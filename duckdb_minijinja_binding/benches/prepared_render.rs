@@ -0,0 +1,126 @@
+//! Compares rendering a template prepared once with `mj_prepare` against
+//! passing the template source to `render_template` for every row.
+//!
+//! Both render the same rows.  `render_template` only compiles the template
+//! once as well, through the template cache, so the difference is what it
+//! does per call: parsing the options and looking up the cache.  Run with
+//! `cargo bench --bench prepared_render`.
+
+use std::ffi::c_char;
+use std::hint::black_box;
+use std::ptr;
+use std::time::{Duration, Instant};
+
+use duckdb_minijinja_binding::{
+    free_result_cstring, mj_prepare, mj_prepared_free, mj_prepared_render, render_template,
+    ResultCString,
+};
+
+const ROWS: usize = 20_000;
+const TEMPLATE: &str = "<li>{{ name | title }} ({{ id }}){% if tags %}: \
+    {{ tags | join(', ') }}{% endif %}</li>";
+
+/// One JSON context per row.
+fn rows() -> Vec<String> {
+    (0..ROWS)
+        .map(|i| {
+            serde_json::json!({
+                "id": i,
+                "name": format!("customer number {}", i),
+                "tags": if i % 3 == 0 { vec!["new", "priority"] } else { vec![] },
+            })
+            .to_string()
+        })
+        .collect()
+}
+
+fn check(result: ResultCString, what: &str) {
+    assert!(matches!(result, ResultCString::Ok(_)), "{} render failed", what);
+    unsafe { free_result_cstring(black_box(result)) };
+}
+
+/// Renders every row with `render_template` and the default options.
+fn render_inline(rows: &[String]) {
+    for row in rows {
+        let result = unsafe {
+            render_template(
+                TEMPLATE.as_ptr() as *const c_char,
+                TEMPLATE.len(),
+                row.as_ptr() as *const c_char,
+                row.len(),
+                ptr::null(),
+                true,
+                ptr::null(),
+                ptr::null(),
+                0,
+                ptr::null(),
+                ptr::null(),
+                ptr::null(),
+                ptr::null(),
+                ptr::null(),
+                ptr::null(),
+                ptr::null(),
+                ptr::null(),
+                false,
+                false,
+                false,
+                0,
+                0,
+                false,
+                false,
+                -1,
+                true,
+                ptr::null(),
+                ptr::null(),
+                -1,
+                false,
+                c"json".as_ptr(),
+                false,
+            )
+        };
+        check(result, "inline");
+    }
+}
+
+/// Renders every row with a template prepared once.
+fn render_prepared(rows: &[String]) {
+    let mut prepared = ptr::null_mut();
+    let result = unsafe {
+        mj_prepare(
+            TEMPLATE.as_ptr() as *const c_char,
+            TEMPLATE.len(),
+            ptr::null(),
+            0,
+            ptr::null(),
+            0,
+            &mut prepared,
+        )
+    };
+    check(result, "prepare");
+    for row in rows {
+        let result =
+            unsafe { mj_prepared_render(prepared, row.as_ptr() as *const c_char, row.len()) };
+        check(result, "prepared");
+    }
+    unsafe { mj_prepared_free(prepared) };
+}
+
+fn bench(name: &str, rows: &[String], render: fn(&[String])) -> Duration {
+    // Warm up the template cache.
+    render(&rows[..1]);
+    let start = Instant::now();
+    render(black_box(rows));
+    let per_row = start.elapsed() / rows.len() as u32;
+    println!("{:<10} {:>8.2?} per row", name, per_row);
+    per_row
+}
+
+fn main() {
+    let rows = rows();
+    let inline_time = bench("inline", &rows, render_inline);
+    let prepared_time = bench("prepared", &rows, render_prepared);
+    println!(
+        "prepared is {:.1}x as fast as inline",
+        inline_time.as_secs_f64() / prepared_time.as_secs_f64()
+    );
+}
@@ -0,0 +1,30 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+/// Exposes the resolved MiniJinja version as `MINIJINJA_VERSION` for
+/// `mj_version`.
+///
+/// Cargo writes the lock file before running build scripts, so the version
+/// is read from there.  It is `unknown` if the lock file cannot be found,
+/// e.g. when the crate is built inside another workspace.
+fn main() {
+    let manifest_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let lock_file = Path::new(&manifest_dir).join("Cargo.lock");
+    println!("cargo:rerun-if-changed={}", lock_file.display());
+
+    let version = fs::read_to_string(&lock_file)
+        .ok()
+        .and_then(|lock| locked_version(&lock, "minijinja"))
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=MINIJINJA_VERSION={}", version);
+}
+
+/// Returns the version of `package` from the contents of a lock file.
+fn locked_version(lock: &str, package: &str) -> Option<String> {
+    let name_line = format!("name = \"{}\"", package);
+    let mut lines = lock.lines();
+    lines.find(|line| *line == name_line)?;
+    let version = lines.next()?.strip_prefix("version = \"")?;
+    Some(version.trim_end_matches('"').to_string())
+}
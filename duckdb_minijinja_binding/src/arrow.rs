@@ -0,0 +1,270 @@
+/// `MetadataVersion::V5`.
+const METADATA_VERSION: i16 = 4;
+/// `MessageHeader::Schema`.
+const HEADER_SCHEMA: u8 = 1;
+/// `MessageHeader::RecordBatch`.
+const HEADER_RECORD_BATCH: u8 = 3;
+/// `Type::Utf8`.
+const TYPE_UTF8: u8 = 5;
+/// Marks the start of an encapsulated message.
+const CONTINUATION: u32 = 0xFFFF_FFFF;
+
+/// A nullable UTF-8 column of a record batch.
+pub(crate) struct StringColumn<'a> {
+    pub name: &'a str,
+    pub values: Vec<Option<&'a str>>,
+}
+
+/// The value of a table field.
+enum Field {
+    Bool(bool),
+    U8(u8),
+    I16(i16),
+    I64(i64),
+    /// An offset to an object written later, patched with `Builder::patch`.
+    Offset,
+}
+
+impl Field {
+    fn size(&self) -> usize {
+        match self {
+            Field::Bool(_) | Field::U8(_) => 1,
+            Field::I16(_) => 2,
+            Field::Offset => 4,
+            Field::I64(_) => 8,
+        }
+    }
+}
+
+/// Writes a flatbuffer front to back.
+#[derive(Default)]
+struct Builder {
+    buf: Vec<u8>,
+}
+
+impl Builder {
+    fn pad_to(&mut self, alignment: usize) {
+        let len = self.buf.len().next_multiple_of(alignment);
+        self.buf.resize(len, 0);
+    }
+
+    /// Reserves the root offset, patch it with the position of the root table.
+    fn root(&mut self) -> usize {
+        self.offset_slot()
+    }
+
+    fn offset_slot(&mut self) -> usize {
+        let slot = self.buf.len();
+        self.buf.extend_from_slice(&0u32.to_le_bytes());
+        slot
+    }
+
+    /// Points the offset at `slot` to the object at `target`.
+    fn patch(&mut self, slot: usize, target: usize) {
+        let offset = (target - slot) as u32;
+        self.buf[slot..slot + 4].copy_from_slice(&offset.to_le_bytes());
+    }
+
+    /// Writes a table whose field ids are the indices of `fields`, `None`
+    /// leaves a field out.
+    ///
+    /// Returns the position of the table and the slots of its `Offset` fields
+    /// in the order of the fields.
+    fn table(&mut self, fields: &[Option<Field>]) -> (usize, Vec<usize>) {
+        // Fields are placed largest first so none needs padding.
+        let mut order: Vec<usize> = (0..fields.len()).filter(|&i| fields[i].is_some()).collect();
+        order.sort_by_key(|&i| std::cmp::Reverse(fields[i].as_ref().map_or(0, Field::size)));
+        let mut field_offsets = vec![0u16; fields.len()];
+        let mut table_len: usize = 4;
+        for &i in &order {
+            let size = fields[i].as_ref().map_or(0, Field::size);
+            table_len = table_len.next_multiple_of(size);
+            field_offsets[i] = table_len as u16;
+            table_len += size;
+        }
+
+        let vtable = self.buf.len();
+        self.buf.extend_from_slice(&(4 + 2 * fields.len() as u16).to_le_bytes());
+        self.buf.extend_from_slice(&(table_len as u16).to_le_bytes());
+        for offset in &field_offsets {
+            self.buf.extend_from_slice(&offset.to_le_bytes());
+        }
+        self.pad_to(8);
+        let table = self.buf.len();
+        self.buf.extend_from_slice(&((table - vtable) as i32).to_le_bytes());
+        self.buf.resize(table + table_len, 0);
+
+        let mut slots = Vec::new();
+        for (i, field) in fields.iter().enumerate() {
+            let at = table + field_offsets[i] as usize;
+            match field {
+                None => {}
+                Some(Field::Bool(value)) => self.buf[at] = *value as u8,
+                Some(Field::U8(value)) => self.buf[at] = *value,
+                Some(Field::I16(value)) => {
+                    self.buf[at..at + 2].copy_from_slice(&value.to_le_bytes())
+                }
+                Some(Field::I64(value)) => {
+                    self.buf[at..at + 8].copy_from_slice(&value.to_le_bytes())
+                }
+                Some(Field::Offset) => slots.push(at),
+            }
+        }
+        self.pad_to(4);
+        (table, slots)
+    }
+
+    /// Writes a vector of `len` offsets and returns its position and slots.
+    fn offset_vector(&mut self, len: usize) -> (usize, Vec<usize>) {
+        self.pad_to(4);
+        let vector = self.buf.len();
+        self.buf.extend_from_slice(&(len as u32).to_le_bytes());
+        (vector, (0..len).map(|_| self.offset_slot()).collect())
+    }
+
+    /// Writes a vector of structs made of `i64` pairs, e.g. `FieldNode` and `Buffer`.
+    fn pair_vector(&mut self, pairs: &[(i64, i64)]) -> usize {
+        // The elements are 8 byte aligned, so the length before them is not.
+        self.pad_to(4);
+        if self.buf.len().is_multiple_of(8) {
+            self.buf.extend_from_slice(&[0; 4]);
+        }
+        let vector = self.buf.len();
+        self.buf.extend_from_slice(&(pairs.len() as u32).to_le_bytes());
+        for (first, second) in pairs {
+            self.buf.extend_from_slice(&first.to_le_bytes());
+            self.buf.extend_from_slice(&second.to_le_bytes());
+        }
+        vector
+    }
+
+    fn string(&mut self, s: &str) -> usize {
+        self.pad_to(4);
+        let string = self.buf.len();
+        self.buf.extend_from_slice(&(s.len() as u32).to_le_bytes());
+        self.buf.extend_from_slice(s.as_bytes());
+        self.buf.push(0);
+        self.pad_to(4);
+        string
+    }
+
+    /// Writes a `Message` table and returns the slot of its header.
+    fn message(&mut self, header_type: u8, body_len: usize) -> usize {
+        let root = self.root();
+        let (message, slots) = self.table(&[
+            Some(Field::I16(METADATA_VERSION)),
+            Some(Field::U8(header_type)),
+            Some(Field::Offset),
+            Some(Field::I64(body_len as i64)),
+        ]);
+        self.patch(root, message);
+        slots[0]
+    }
+}
+
+/// Appends an encapsulated message with its flatbuffer and body to `out`.
+fn write_message(out: &mut Vec<u8>, metadata: Vec<u8>, body: &[u8]) {
+    let padded_len = metadata.len().next_multiple_of(8);
+    out.extend_from_slice(&CONTINUATION.to_le_bytes());
+    out.extend_from_slice(&(padded_len as u32).to_le_bytes());
+    out.extend_from_slice(&metadata);
+    out.resize(out.len() + padded_len - metadata.len(), 0);
+    out.extend_from_slice(body);
+}
+
+fn schema_message(columns: &[StringColumn]) -> Vec<u8> {
+    let mut fb = Builder::default();
+    let header = fb.message(HEADER_SCHEMA, 0);
+    // Endianness is left out, its default is little endian.
+    let (schema, slots) = fb.table(&[None, Some(Field::Offset)]);
+    fb.patch(header, schema);
+    let (fields, field_slots) = fb.offset_vector(columns.len());
+    fb.patch(slots[0], fields);
+    for (column, slot) in columns.iter().zip(field_slots) {
+        let (field, slots) = fb.table(&[
+            Some(Field::Offset),
+            Some(Field::Bool(true)),
+            Some(Field::U8(TYPE_UTF8)),
+            Some(Field::Offset),
+            None,
+            Some(Field::Offset),
+        ]);
+        fb.patch(slot, field);
+        let name = fb.string(column.name);
+        fb.patch(slots[0], name);
+        let (utf8, _) = fb.table(&[]);
+        fb.patch(slots[1], utf8);
+        let (children, _) = fb.offset_vector(0);
+        fb.patch(slots[2], children);
+    }
+    fb.buf
+}
+
+/// Appends `data` to the body as a buffer padded to 8 bytes and records it.
+fn push_buffer(body: &mut Vec<u8>, buffers: &mut Vec<(i64, i64)>, data: &[u8]) {
+    buffers.push((body.len() as i64, data.len() as i64));
+    body.extend_from_slice(data);
+    body.resize(body.len().next_multiple_of(8), 0);
+}
+
+/// Encodes string columns of equal length as an Arrow IPC stream.
+///
+/// The stream holds a schema message, a single record batch and the end of
+/// stream marker, see the [format specification].  The flatbuffers of the
+/// messages are laid out front to back, so every offset points forward to an
+/// object written after it.
+///
+/// [format specification]: https://arrow.apache.org/docs/format/Columnar.html#ipc-streaming-format
+///
+/// Returns an error if a column holds more than the 2 GiB of data the `Utf8`
+/// type can address.
+pub(crate) fn ipc_stream(columns: &[StringColumn]) -> Result<Vec<u8>, String> {
+    let rows = columns.first().map_or(0, |column| column.values.len());
+    let mut body = Vec::new();
+    let mut nodes = Vec::with_capacity(columns.len());
+    let mut buffers = Vec::with_capacity(3 * columns.len());
+    for column in columns {
+        let mut validity = vec![0u8; rows.div_ceil(8)];
+        let mut offsets = Vec::with_capacity(4 * (rows + 1));
+        let mut data = Vec::new();
+        offsets.extend_from_slice(&0i32.to_le_bytes());
+        for (row, value) in column.values.iter().enumerate() {
+            if let Some(value) = value {
+                validity[row / 8] |= 1 << (row % 8);
+                data.extend_from_slice(value.as_bytes());
+            }
+            let offset = i32::try_from(data.len()).map_err(|_| {
+                format!(
+                    "the '{}' column exceeds the 2 GiB an Arrow string array holds",
+                    column.name
+                )
+            })?;
+            offsets.extend_from_slice(&offset.to_le_bytes());
+        }
+        let null_count = column.values.iter().filter(|value| value.is_none()).count();
+        nodes.push((rows as i64, null_count as i64));
+        push_buffer(&mut body, &mut buffers, &validity);
+        push_buffer(&mut body, &mut buffers, &offsets);
+        push_buffer(&mut body, &mut buffers, &data);
+    }
+
+    let mut fb = Builder::default();
+    let header = fb.message(HEADER_RECORD_BATCH, body.len());
+    let (batch, slots) = fb.table(&[
+        Some(Field::I64(rows as i64)),
+        Some(Field::Offset),
+        Some(Field::Offset),
+    ]);
+    fb.patch(header, batch);
+    let nodes = fb.pair_vector(&nodes);
+    fb.patch(slots[0], nodes);
+    let buffers = fb.pair_vector(&buffers);
+    fb.patch(slots[1], buffers);
+
+    let mut out = Vec::new();
+    write_message(&mut out, schema_message(columns), &[]);
+    write_message(&mut out, fb.buf, &body);
+    out.extend_from_slice(&CONTINUATION.to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes());
+    Ok(out)
+}
@@ -0,0 +1,295 @@
+use std::ffi::c_char;
+use std::io;
+
+use crate::config::render_options_from_json;
+use crate::error::{
+    set_last_error_code, RenderError, MJ_ERROR_BUFFER_TOO_SMALL, MJ_ERROR_INVALID_ARGUMENT, MJ_OK,
+};
+use crate::{str_from_raw, timeout, RawStrError, RenderOptions};
+
+/// Writes into a caller provided buffer and counts what does not fit.
+struct SliceWriter<'a> {
+    buf: &'a mut [u8],
+    /// The number of bytes written so far, may exceed the buffer.
+    len: usize,
+}
+
+impl SliceWriter<'_> {
+    /// Stores `data` after the bytes written so far, as far as it fits.
+    fn append(&mut self, data: &[u8]) {
+        if let Some(free) = self.buf.get_mut(self.len..) {
+            let n = free.len().min(data.len());
+            free[..n].copy_from_slice(&data[..n]);
+        }
+        self.len += data.len();
+    }
+}
+
+impl io::Write for SliceWriter<'_> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.append(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Renders a template with `options` into `out`.
+///
+/// The render writes into the caller's buffer, so it runs on the calling
+/// thread and a timeout is only checked while rendering.
+fn render_into(
+    options: &RenderOptions,
+    template_source: &[u8],
+    context: &[u8],
+    out: &mut SliceWriter,
+) -> Result<(), RenderError> {
+    let template_str = options
+        .template_compression
+        .decode(template_source)
+        .map_err(RenderError::InvalidOptions)?;
+    let ctx = options
+        .parse_context(context)
+        .map_err(|err| RenderError::InvalidContext(err.to_string()))?;
+    let (env, name) = options.render_target(&template_str)?;
+    let tmpl = env.get_template(&name)?;
+    let ctx = timeout::bound_context(options.timeout_ms, &ctx);
+    timeout::run_inline_with_timeout(options.timeout_ms, || {
+        tmpl.render_to_write(ctx, out)
+            .map(|_| ())
+            .map_err(|err| options.map_fuel_error(err))
+    })
+}
+
+/// Renders a Minijinja template into a caller provided buffer.
+///
+/// This avoids allocating the output, so a caller can reuse one scratch
+/// buffer for many renders.  The template is rendered with the default
+/// options of `render_template` and goes through the same template cache.
+///
+/// The output, or the message of an error, is written to `out_buf` without a
+/// NUL terminator and its full length is stored in `out_len`.  If it is longer
+/// than `out_cap` only the first `out_cap` bytes are written.  Output that
+/// does not fit returns `MJ_ERROR_BUFFER_TOO_SMALL`, the caller can then grow
+/// the buffer to `out_len` bytes and render again.  A truncated error message
+/// keeps the code of the error, it is only recognizable by `out_len`
+/// exceeding `out_cap`.  Passing a null `out_buf` with an `out_cap` of 0 only
+/// measures the output.
+///
+/// # Arguments
+/// * `template_source` - The template content as a string.
+/// * `json_context` - JSON string containing context variables.
+/// * `out_buf` - The buffer receiving the output.
+/// * `out_cap` - The size of `out_buf` in bytes.
+/// * `out_len` - Receives the length of the output or error message.
+///
+/// # Returns
+/// * `MJ_OK` if the output was written, `MJ_ERROR_BUFFER_TOO_SMALL` if it did
+///   not fit, or the `MJ_ERROR_*` code of the error whose message was
+///   written, even if the message did not fit.  A null `out_len` returns `MJ_ERROR_INVALID_ARGUMENT` without
+///   writing anything.
+///
+/// # Safety
+/// `template_source` and `json_context` must be valid for the given lengths,
+/// `out_buf` must be valid for writing `out_cap` bytes and `out_len` must be
+/// valid for writing or null.
+#[no_mangle]
+pub unsafe extern "C" fn render_template_into(
+    template_source: *const c_char,
+    template_source_len: usize,
+    json_context: *const c_char,
+    json_context_len: usize,
+    out_buf: *mut u8,
+    out_cap: usize,
+    out_len: *mut usize,
+) -> i32 {
+    render_raw_into(
+        Ok(RenderOptions::default()),
+        template_source,
+        template_source_len,
+        json_context,
+        json_context_len,
+        out_buf,
+        out_cap,
+        out_len,
+    )
+}
+
+/// Renders a Minijinja template into a caller provided buffer like
+/// `render_template_into`, with the options of `render_template_with_config`.
+///
+/// An invalid config is reported like any other error, its message is written
+/// to `out_buf` and `MJ_ERROR_INVALID_ARGUMENT` is returned.  The render runs
+/// on the calling thread, so `timeout_ms` is only checked while rendering.
+///
+/// # Returns
+/// * The code of `render_template_into`.
+///
+/// # Safety
+/// `template_source`, `json_context` and `config_json` must be valid for the
+/// given lengths, `out_buf` must be valid for writing `out_cap` bytes and
+/// `out_len` must be valid for writing or null.
+#[no_mangle]
+pub unsafe extern "C" fn render_template_into_with_config(
+    template_source: *const c_char,
+    template_source_len: usize,
+    json_context: *const c_char,
+    json_context_len: usize,
+    config_json: *const c_char,
+    config_json_len: usize,
+    out_buf: *mut u8,
+    out_cap: usize,
+    out_len: *mut usize,
+) -> i32 {
+    let options = match str_from_raw(config_json, config_json_len) {
+        Ok(config_str) => render_options_from_json(config_str).map_err(RenderError::InvalidOptions),
+        Err(RawStrError::Null) => Err(RenderError::InvalidOptions("config is null".to_string())),
+        Err(RawStrError::InvalidUtf8) => Err(RenderError::InvalidOptions(
+            "config is not valid UTF-8".to_string(),
+        )),
+    };
+    render_raw_into(
+        options,
+        template_source,
+        template_source_len,
+        json_context,
+        json_context_len,
+        out_buf,
+        out_cap,
+        out_len,
+    )
+}
+
+/// Renders into `out_buf` as `render_template_into` describes, reporting an
+/// error of `options` like one of the render.
+///
+/// # Safety
+/// The pointers must be valid as `render_template_into` requires.
+#[allow(clippy::too_many_arguments)]
+unsafe fn render_raw_into(
+    options: Result<RenderOptions, RenderError>,
+    template_source: *const c_char,
+    template_source_len: usize,
+    json_context: *const c_char,
+    json_context_len: usize,
+    out_buf: *mut u8,
+    out_cap: usize,
+    out_len: *mut usize,
+) -> i32 {
+    let Some(out_len) = out_len.as_mut() else {
+        set_last_error_code(MJ_ERROR_INVALID_ARGUMENT);
+        return MJ_ERROR_INVALID_ARGUMENT;
+    };
+    let buf: &mut [u8] = if out_cap == 0 {
+        &mut []
+    } else if out_buf.is_null() {
+        *out_len = 0;
+        set_last_error_code(MJ_ERROR_INVALID_ARGUMENT);
+        return MJ_ERROR_INVALID_ARGUMENT;
+    } else {
+        std::slice::from_raw_parts_mut(out_buf, out_cap)
+    };
+    let mut out = SliceWriter { buf, len: 0 };
+
+    let result = if template_source.is_null() {
+        Err(RenderError::InvalidOptions("template source is null".to_string()))
+    } else if json_context.is_null() {
+        Err(RenderError::InvalidOptions("JSON context is null".to_string()))
+    } else {
+        let template_source =
+            std::slice::from_raw_parts(template_source as *const u8, template_source_len);
+        let context = std::slice::from_raw_parts(json_context as *const u8, json_context_len);
+        options.and_then(|options| render_into(&options, template_source, context, &mut out))
+    };
+
+    let mut code = match result {
+        Ok(()) => MJ_OK,
+        Err(err) => {
+            // Output written before the error is replaced by the message.
+            out.len = 0;
+            out.append(err.to_string().as_bytes());
+            err.code()
+        }
+    };
+    *out_len = out.len;
+    // A failed render would fail again, so its code is kept.
+    if code == MJ_OK && out.len > out_cap {
+        code = MJ_ERROR_BUFFER_TOO_SMALL;
+    }
+    set_last_error_code(code);
+    code
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::error::MJ_ERROR_SYNTAX;
+
+    /// Renders `template` into a buffer of `cap` bytes.
+    fn render(template: &str, cap: usize) -> (i32, usize, Vec<u8>) {
+        let mut buf = vec![0u8; cap];
+        let mut len = 0;
+        let context = "{}";
+        let code = unsafe {
+            render_template_into(
+                template.as_ptr().cast(),
+                template.len(),
+                context.as_ptr().cast(),
+                context.len(),
+                buf.as_mut_ptr(),
+                cap,
+                &mut len,
+            )
+        };
+        (code, len, buf)
+    }
+
+    /// Renders `template` with `{"name": "World"}` and `config` into 256 bytes.
+    fn render_with_config(template: &str, config: &str) -> (i32, String) {
+        let mut buf = vec![0u8; 256];
+        let mut len = 0;
+        let context = r#"{"name": "World"}"#;
+        let code = unsafe {
+            render_template_into_with_config(
+                template.as_ptr().cast(),
+                template.len(),
+                context.as_ptr().cast(),
+                context.len(),
+                config.as_ptr().cast(),
+                config.len(),
+                buf.as_mut_ptr(),
+                buf.len(),
+                &mut len,
+            )
+        };
+        (code, String::from_utf8_lossy(&buf[..len]).into_owned())
+    }
+
+    #[test]
+    fn renders_into_the_buffer_with_the_config() {
+        let delimiters = r#"{"variable_delimiters": ["[[", "]]"]}"#;
+        let rendered = render_with_config("Hello [[ name ]]", delimiters);
+        assert_eq!(rendered, (MJ_OK, "Hello World".to_string()));
+        let (code, msg) = render_with_config("{{ nope }}", r#"{"undefined_behavior": "strict"}"#);
+        assert_eq!(code, crate::error::MJ_ERROR_UNDEFINED, "{}", msg);
+        let (code, msg) = render_with_config("Hello", r#"{"nope": 1}"#);
+        assert_eq!((code, msg.as_str()), (MJ_ERROR_INVALID_ARGUMENT, "Unknown config key 'nope'"));
+    }
+
+    #[test]
+    fn output_that_does_not_fit_is_reported() {
+        assert_eq!(render("Hello World", 64).0, MJ_OK);
+        let (code, len, buf) = render("Hello World", 5);
+        assert_eq!((code, len, buf.as_slice()), (MJ_ERROR_BUFFER_TOO_SMALL, 11, &b"Hello"[..]));
+    }
+
+    #[test]
+    fn truncated_errors_keep_their_code() {
+        let (code, len, _) = render("{% if %}", 4);
+        assert_eq!(code, MJ_ERROR_SYNTAX);
+        assert!(len > 4);
+    }
+}
@@ -0,0 +1,284 @@
+use std::collections::BTreeMap;
+use std::ffi::c_char;
+
+use minijinja::Value;
+
+use crate::error::{set_last_error_code, MJ_ERROR_INVALID_ARGUMENT, MJ_OK};
+use crate::{slice_from_raw, str_from_raw};
+
+/// An opaque handle holding a render context built value by value.
+///
+/// A context is either a struct, created with `mj_ctx_new`, or a list,
+/// created with `mj_ctx_new_list`.  It is filled with the `mj_ctx_set_*`
+/// functions and must be released with `mj_ctx_free`.  Building the context
+/// directly skips serializing it to JSON and parsing it back.
+///
+/// A context may be rendered any number of times and from several threads at
+/// once, but must not be modified while it is rendered.
+pub struct ContextHandle {
+    node: Node,
+}
+
+/// The values of a context.
+enum Node {
+    Struct(BTreeMap<String, Value>),
+    List(Vec<Value>),
+}
+
+impl ContextHandle {
+    fn new(node: Node) -> *mut ContextHandle {
+        Box::into_raw(Box::new(ContextHandle { node }))
+    }
+
+    /// Converts the context into the value passed to a render.
+    pub(crate) fn to_value(&self) -> Value {
+        match &self.node {
+            Node::Struct(fields) => Value::from(fields.clone()),
+            Node::List(items) => Value::from(items.clone()),
+        }
+    }
+
+    fn into_value(self) -> Value {
+        match self.node {
+            Node::Struct(fields) => Value::from(fields),
+            Node::List(items) => Value::from(items),
+        }
+    }
+
+    /// Sets a field of a struct or appends to a list, ignoring `key`.
+    unsafe fn set(&mut self, key: *const c_char, key_len: usize, value: Value) -> bool {
+        match &mut self.node {
+            Node::Struct(fields) => {
+                let Ok(key) = str_from_raw(key, key_len) else {
+                    return fail();
+                };
+                fields.insert(key.to_owned(), value);
+            }
+            Node::List(items) => items.push(value),
+        }
+        set_last_error_code(MJ_OK);
+        true
+    }
+}
+
+/// Records an invalid argument for `mj_last_error_code` and returns `false`.
+fn fail() -> bool {
+    set_last_error_code(MJ_ERROR_INVALID_ARGUMENT);
+    false
+}
+
+/// Creates an empty struct context.
+///
+/// # Returns
+/// * A pointer to the context which must be freed with `mj_ctx_free`, unless
+///   it is passed to `mj_ctx_set_struct`.
+#[no_mangle]
+pub extern "C" fn mj_ctx_new() -> *mut ContextHandle {
+    ContextHandle::new(Node::Struct(BTreeMap::new()))
+}
+
+/// Creates an empty list context.
+///
+/// Values set on a list are appended in order, their keys are ignored and may
+/// be null.  A list is usually nested into a struct with `mj_ctx_set_list`,
+/// rendering it directly makes its items unreachable by name.
+///
+/// # Returns
+/// * A pointer to the context which must be freed with `mj_ctx_free`, unless
+///   it is passed to `mj_ctx_set_list`.
+#[no_mangle]
+pub extern "C" fn mj_ctx_new_list() -> *mut ContextHandle {
+    ContextHandle::new(Node::List(Vec::new()))
+}
+
+/// Frees a context created by `mj_ctx_new` or `mj_ctx_new_list`.
+///
+/// # Safety
+/// `ctx` must have been created by `mj_ctx_new` or `mj_ctx_new_list` and must
+/// not be used after this call. Passing a null pointer is a no-op.
+#[no_mangle]
+pub unsafe extern "C" fn mj_ctx_free(ctx: *mut ContextHandle) {
+    if !ctx.is_null() {
+        drop(Box::from_raw(ctx));
+    }
+}
+
+/// Sets `key` to none, like a SQL NULL.
+///
+/// All `mj_ctx_set_*` functions replace an existing field of a struct and
+/// append to a list.
+///
+/// # Returns
+/// * `true` on success, `false` if `ctx` is null or `key` is null or not valid
+///   UTF-8 for a struct.  `mj_last_error_code` then reports
+///   `MJ_ERROR_INVALID_ARGUMENT`.
+///
+/// # Safety
+/// `ctx` must be a valid context or null and `key` must be valid for
+/// `key_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn mj_ctx_set_null(
+    ctx: *mut ContextHandle,
+    key: *const c_char,
+    key_len: usize,
+) -> bool {
+    match ctx.as_mut() {
+        Some(ctx) => ctx.set(key, key_len, Value::from(())),
+        None => fail(),
+    }
+}
+
+/// Sets `key` to a boolean, see `mj_ctx_set_null`.
+///
+/// # Safety
+/// `ctx` must be a valid context or null and `key` must be valid for
+/// `key_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn mj_ctx_set_bool(
+    ctx: *mut ContextHandle,
+    key: *const c_char,
+    key_len: usize,
+    value: bool,
+) -> bool {
+    match ctx.as_mut() {
+        Some(ctx) => ctx.set(key, key_len, Value::from(value)),
+        None => fail(),
+    }
+}
+
+/// Sets `key` to an integer, see `mj_ctx_set_null`.
+///
+/// # Safety
+/// `ctx` must be a valid context or null and `key` must be valid for
+/// `key_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn mj_ctx_set_int(
+    ctx: *mut ContextHandle,
+    key: *const c_char,
+    key_len: usize,
+    value: i64,
+) -> bool {
+    match ctx.as_mut() {
+        Some(ctx) => ctx.set(key, key_len, Value::from(value)),
+        None => fail(),
+    }
+}
+
+/// Sets `key` to a float, see `mj_ctx_set_null`.
+///
+/// # Safety
+/// `ctx` must be a valid context or null and `key` must be valid for
+/// `key_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn mj_ctx_set_float(
+    ctx: *mut ContextHandle,
+    key: *const c_char,
+    key_len: usize,
+    value: f64,
+) -> bool {
+    match ctx.as_mut() {
+        Some(ctx) => ctx.set(key, key_len, Value::from(value)),
+        None => fail(),
+    }
+}
+
+/// Sets `key` to a string, see `mj_ctx_set_null`.
+///
+/// The string is copied and must be valid UTF-8, otherwise `false` is
+/// returned.
+///
+/// # Safety
+/// `ctx` must be a valid context or null, `key` must be valid for `key_len`
+/// bytes and `value` for `value_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn mj_ctx_set_string(
+    ctx: *mut ContextHandle,
+    key: *const c_char,
+    key_len: usize,
+    value: *const c_char,
+    value_len: usize,
+) -> bool {
+    let (Some(ctx), Ok(value)) = (ctx.as_mut(), str_from_raw(value, value_len)) else {
+        return fail();
+    };
+    ctx.set(key, key_len, Value::from(value))
+}
+
+/// Sets `key` to bytes, like a blob, see `mj_ctx_set_null`.
+///
+/// The bytes are copied.
+///
+/// # Safety
+/// `ctx` must be a valid context or null, `key` must be valid for `key_len`
+/// bytes and `value` for `value_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn mj_ctx_set_bytes(
+    ctx: *mut ContextHandle,
+    key: *const c_char,
+    key_len: usize,
+    value: *const u8,
+    value_len: usize,
+) -> bool {
+    let (Some(ctx), Some(value)) = (ctx.as_mut(), slice_from_raw(value, value_len)) else {
+        return fail();
+    };
+    ctx.set(key, key_len, Value::from_bytes(value.to_vec()))
+}
+
+/// Moves `child` into `ctx` under `key`, see `mj_ctx_set_null`.
+///
+/// `child` is always consumed, even if the call fails.
+unsafe fn set_child(
+    ctx: *mut ContextHandle,
+    key: *const c_char,
+    key_len: usize,
+    child: *mut ContextHandle,
+    want_list: bool,
+) -> bool {
+    if child.is_null() {
+        return fail();
+    }
+    let child = Box::from_raw(child);
+    let is_list = matches!(child.node, Node::List(_));
+    match ctx.as_mut() {
+        Some(ctx) if is_list == want_list => ctx.set(key, key_len, child.into_value()),
+        _ => fail(),
+    }
+}
+
+/// Sets `key` to a list created with `mj_ctx_new_list`, see `mj_ctx_set_null`.
+///
+/// Ownership of `child` passes to this call, it must not be used or freed
+/// afterwards, even if the call fails.
+///
+/// # Returns
+/// * `true` on success, `false` if `child` is not a list or the arguments are
+///   invalid as for `mj_ctx_set_null`.
+///
+/// # Safety
+/// `ctx` must be a valid context or null, `key` must be valid for `key_len`
+/// bytes and `child` must be a valid context other than `ctx` or null.
+#[no_mangle]
+pub unsafe extern "C" fn mj_ctx_set_list(
+    ctx: *mut ContextHandle,
+    key: *const c_char,
+    key_len: usize,
+    child: *mut ContextHandle,
+) -> bool {
+    set_child(ctx, key, key_len, child, true)
+}
+
+/// Sets `key` to a struct created with `mj_ctx_new`, see `mj_ctx_set_list`.
+///
+/// # Safety
+/// `ctx` must be a valid context or null, `key` must be valid for `key_len`
+/// bytes and `child` must be a valid context other than `ctx` or null.
+#[no_mangle]
+pub unsafe extern "C" fn mj_ctx_set_struct(
+    ctx: *mut ContextHandle,
+    key: *const c_char,
+    key_len: usize,
+    child: *mut ContextHandle,
+) -> bool {
+    set_child(ctx, key, key_len, child, false)
+}
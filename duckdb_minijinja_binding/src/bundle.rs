@@ -0,0 +1,34 @@
+/// Identifies a template source bundle.
+const MAGIC: &[u8; 4] = b"MJTC";
+
+/// The version of the bundle layout, bumped whenever it changes.
+///
+/// Version 1 stores the template source as UTF-8 after the header.
+const FORMAT_VERSION: u8 = 1;
+
+const HEADER_LEN: usize = MAGIC.len() + 1;
+
+/// Wraps a template source that is known to compile into a bundle.
+pub(crate) fn encode(source: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(HEADER_LEN + source.len());
+    bytes.extend_from_slice(MAGIC);
+    bytes.push(FORMAT_VERSION);
+    bytes.extend_from_slice(source.as_bytes());
+    bytes
+}
+
+/// Extracts the template source from a bundle created by `encode`.
+pub(crate) fn decode(bytes: &[u8]) -> Result<&str, String> {
+    if bytes.len() < HEADER_LEN || &bytes[..MAGIC.len()] != MAGIC {
+        return Err("not a template source bundle".to_string());
+    }
+    let version = bytes[MAGIC.len()];
+    if version != FORMAT_VERSION {
+        return Err(format!(
+            "unsupported template source bundle version {} (expected {})",
+            version, FORMAT_VERSION
+        ));
+    }
+    std::str::from_utf8(&bytes[HEADER_LEN..])
+        .map_err(|_| "template source bundle is not valid UTF-8".to_string())
+}
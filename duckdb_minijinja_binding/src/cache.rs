@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, Mutex, MutexGuard, PoisonError};
+
+use chrono::{DateTime, Utc};
+use minijinja::Environment;
+
+use crate::error::RenderError;
+use crate::formatter::FloatFormat;
+use crate::syntax::SyntaxOptions;
+use crate::{AutoescapeMode, RenderOptions};
+
+/// The name inline templates are registered under, matching `render_str`.
+pub(crate) const CACHED_TEMPLATE_NAME: &str = "<string>";
+
+/// The number of compiled templates kept by default.
+const DEFAULT_MAX_ENTRIES: usize = 64;
+
+static TEMPLATE_CACHE: LazyLock<Mutex<TemplateCache>> =
+    LazyLock::new(|| Mutex::new(TemplateCache::new(DEFAULT_MAX_ENTRIES)));
+
+/// Everything that influences how a template compiles and renders, apart
+/// from the context.
+#[derive(PartialEq, Eq, Hash)]
+struct CacheKey {
+    source: String,
+    autoescape: bool,
+    undefined_behavior: Option<String>,
+    autoescape_on: Vec<String>,
+    syntax: SyntaxOptions,
+    trim_blocks: bool,
+    lstrip_blocks: bool,
+    keep_trailing_newline: bool,
+    fuel: i64,
+    recursion_limit: usize,
+    pycompat: bool,
+    float_format: FloatFormat,
+    autoescape_mode: Option<AutoescapeMode>,
+    now: Option<DateTime<Utc>>,
+    random_seed: Option<u64>,
+    allow_env: bool,
+    debug: bool,
+    undefined_placeholder: Option<String>,
+}
+
+impl CacheKey {
+    fn new(options: &RenderOptions, source: &str) -> CacheKey {
+        CacheKey {
+            source: source.to_owned(),
+            autoescape: options.autoescape,
+            undefined_behavior: options.undefined_behavior.clone(),
+            autoescape_on: options.autoescape_on.clone(),
+            syntax: options.syntax.clone(),
+            trim_blocks: options.trim_blocks,
+            lstrip_blocks: options.lstrip_blocks,
+            keep_trailing_newline: options.keep_trailing_newline,
+            fuel: options.fuel,
+            recursion_limit: options.recursion_limit,
+            pycompat: options.pycompat,
+            float_format: options.float_format,
+            autoescape_mode: options.autoescape_mode,
+            now: options.now,
+            random_seed: options.random_seed,
+            allow_env: options.allow_env,
+            debug: options.debug,
+            undefined_placeholder: options.undefined_placeholder.clone(),
+        }
+    }
+}
+
+struct CacheEntry {
+    env: Arc<Environment<'static>>,
+    last_used: u64,
+}
+
+/// A size bounded cache of compiled inline templates which evicts the least
+/// recently used entry when full.
+struct TemplateCache {
+    max_entries: usize,
+    entries: HashMap<CacheKey, CacheEntry>,
+    clock: u64,
+}
+
+impl TemplateCache {
+    fn new(max_entries: usize) -> TemplateCache {
+        TemplateCache {
+            max_entries,
+            entries: HashMap::new(),
+            clock: 0,
+        }
+    }
+
+    fn get(&mut self, key: &CacheKey) -> Option<Arc<Environment<'static>>> {
+        self.clock += 1;
+        let entry = self.entries.get_mut(key)?;
+        entry.last_used = self.clock;
+        Some(entry.env.clone())
+    }
+
+    fn insert(&mut self, key: CacheKey, env: Arc<Environment<'static>>) {
+        if self.max_entries == 0 {
+            return;
+        }
+        self.clock += 1;
+        if !self.entries.contains_key(&key) {
+            self.shrink_to(self.max_entries - 1);
+        }
+        self.entries.insert(
+            key,
+            CacheEntry {
+                env,
+                last_used: self.clock,
+            },
+        );
+    }
+
+    /// Evicts the least recently used entries until at most `len` remain.
+    fn shrink_to(&mut self, len: usize) {
+        while self.entries.len() > len {
+            // Every use gets a new tick, so the oldest tick identifies one entry.
+            let Some(oldest) = self.entries.values().map(|entry| entry.last_used).min() else {
+                break;
+            };
+            self.entries.retain(|_, entry| entry.last_used != oldest);
+        }
+    }
+}
+
+fn cache() -> MutexGuard<'static, TemplateCache> {
+    TEMPLATE_CACHE.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Returns an environment with `source` compiled under `CACHED_TEMPLATE_NAME`.
+///
+/// The environment is taken from the cache if the same source was compiled
+/// with the same options before, otherwise it is built, compiled and cached.
+/// Templates that fail to compile are not cached.
+pub(crate) fn compiled_template(
+    options: &RenderOptions,
+    source: &str,
+) -> Result<Arc<Environment<'static>>, RenderError> {
+    let key = CacheKey::new(options, source);
+    if let Some(env) = cache().get(&key) {
+        return Ok(env);
+    }
+
+    let mut env = options
+        .build_environment()
+        .map_err(RenderError::InvalidOptions)?;
+    env.add_template_owned(CACHED_TEMPLATE_NAME, source.to_owned())?;
+    let env = Arc::new(env);
+    cache().insert(key, env.clone());
+    Ok(env)
+}
+
+/// Removes all compiled templates from the cache used by `render_template`.
+#[no_mangle]
+pub extern "C" fn mj_clear_template_cache() {
+    cache().entries.clear();
+}
+
+/// Sets how many compiled templates the cache used by `render_template` keeps.
+///
+/// The least recently used templates are evicted once the limit is reached,
+/// `0` disables the cache.  The default is 64.
+#[no_mangle]
+pub extern "C" fn mj_set_template_cache_size(max_entries: usize) {
+    let mut cache = cache();
+    cache.max_entries = max_entries;
+    cache.shrink_to(max_entries);
+}
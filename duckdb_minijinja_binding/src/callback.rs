@@ -0,0 +1,511 @@
+use std::ffi::{c_char, c_int, c_void};
+use std::io;
+
+use minijinja::{Error, ErrorKind, State, Value};
+
+use crate::error::{MJ_ERROR_INVALID_ARGUMENT, MJ_ERROR_SERIALIZATION, MJ_ERROR_UNDEFINED};
+use crate::formatter::auto_escape_name;
+use crate::{
+    error_result, json_to_value, ok_result, slice_from_raw, str_from_raw, Conversion, RawStrError,
+    ResultCString,
+};
+
+/// Receives the outcome of a callback invocation.
+///
+/// Callbacks report their result with `mj_callback_set_ok` or
+/// `mj_callback_set_err`.  The data is copied, so the callback keeps ownership
+/// of the strings it passes in.
+pub struct CallbackResult {
+    outcome: Option<Result<String, String>>,
+}
+
+/// A host callback taking JSON encoded arguments.
+///
+/// # Arguments
+/// * `args_json` - A JSON array with the arguments of the call, valid for
+///   `args_json_len` bytes and not null terminated.  For filters the filtered
+///   value is the first element.
+/// * `user_data` - The pointer supplied when the callback was registered.
+/// * `result` - Where the callback reports its JSON encoded result or an error.
+///
+/// The callback may be invoked from multiple threads at once.
+pub type JsonCallback = unsafe extern "C" fn(
+    args_json: *const c_char,
+    args_json_len: usize,
+    user_data: *mut c_void,
+    result: *mut CallbackResult,
+);
+
+/// The render state passed to a filter added with `mj_env_add_state_filter`.
+///
+/// It is only valid during the callback it was passed to.  Variables are
+/// looked up with `mj_filter_state_lookup`.
+pub struct FilterState<'a, 'vm, 'env> {
+    state: &'a State<'vm, 'env>,
+}
+
+/// A host callback implementing a filter that sees the render state.
+///
+/// # Arguments
+/// * `args_json` - A JSON array holding the filtered value followed by the
+///   filter arguments, valid for `args_json_len` bytes and not null terminated.
+/// * `state_json` - A JSON object describing the render state, valid for
+///   `state_json_len` bytes and not null terminated.  It holds the `name` of
+///   the current template and its `auto_escape` mode, `none`, `html`, `json`
+///   or the name of a custom mode.
+/// * `state` - The render state for looking up variables with
+///   `mj_filter_state_lookup`, valid during the call.
+/// * `user_data` - The pointer supplied when the filter was registered.
+/// * `result` - Where the callback reports its JSON encoded result or an error.
+///
+/// The callback may be invoked from multiple threads at once.
+pub type StateFilterCallback = unsafe extern "C" fn(
+    args_json: *const c_char,
+    args_json_len: usize,
+    state_json: *const c_char,
+    state_json_len: usize,
+    state: *const FilterState,
+    user_data: *mut c_void,
+    result: *mut CallbackResult,
+);
+
+/// A host callback that loads template sources by name.
+///
+/// # Arguments
+/// * `name` - The name of the requested template, valid for `name_len` bytes
+///   and not null terminated.
+/// * `user_data` - The pointer supplied when the loader was registered.
+/// * `result` - Where the callback reports the template source with
+///   `mj_callback_set_ok`.  Leaving it unset means the template does not exist.
+///
+/// The callback may be invoked from multiple threads at once.
+pub type LoaderCallback = unsafe extern "C" fn(
+    name: *const c_char,
+    name_len: usize,
+    user_data: *mut c_void,
+    result: *mut CallbackResult,
+);
+
+/// A host callback escaping a printed value for auto escaping.
+///
+/// # Arguments
+/// * `value` - The value as a string, valid for `value_len` bytes and not null
+///   terminated.
+/// * `user_data` - The pointer supplied when the callback was registered.
+/// * `result` - Where the callback reports the escaped string with
+///   `mj_callback_set_ok` or an error.  Leaving it unset fails the render.
+///
+/// The callback may be invoked from multiple threads at once.
+pub type EscapeCallback = unsafe extern "C" fn(
+    value: *const c_char,
+    value_len: usize,
+    user_data: *mut c_void,
+    result: *mut CallbackResult,
+);
+
+/// A host callback resolving the variables of a dynamic context.
+///
+/// # Arguments
+/// * `name` - The name of the requested variable, valid for `name_len` bytes
+///   and not null terminated.
+/// * `user_data` - The pointer supplied with the render call.
+/// * `result` - Where the callback reports the JSON encoded value of the
+///   variable.  Leaving it unset means the variable is undefined.
+pub type ResolverCallback = unsafe extern "C" fn(
+    name: *const c_char,
+    name_len: usize,
+    user_data: *mut c_void,
+    result: *mut CallbackResult,
+);
+
+/// A host callback answering method calls that MiniJinja does not know.
+///
+/// # Arguments
+/// * `object_json` - The value the method was called on as JSON, valid for
+///   `object_json_len` bytes and not null terminated.
+/// * `method` - The name of the method, valid for `method_len` bytes and not
+///   null terminated.
+/// * `args_json` - A JSON array with the arguments of the call, valid for
+///   `args_json_len` bytes and not null terminated.
+/// * `user_data` - The pointer supplied when the callback was registered.
+/// * `result` - Where the callback reports its JSON encoded result or an error.
+///   Leaving it unset means the object has no such method.
+///
+/// The callback may be invoked from multiple threads at once.
+pub type MethodCallback = unsafe extern "C" fn(
+    object_json: *const c_char,
+    object_json_len: usize,
+    method: *const c_char,
+    method_len: usize,
+    args_json: *const c_char,
+    args_json_len: usize,
+    user_data: *mut c_void,
+    result: *mut CallbackResult,
+);
+
+/// A host callback receiving rendered output.
+///
+/// # Arguments
+/// * `chunk` - The next piece of output, valid for `chunk_len` bytes during the
+///   call and not null terminated.
+/// * `user_data` - The pointer supplied with the render call.
+///
+/// # Returns
+/// * `0` to continue rendering, any other value aborts the render.
+pub type WriteCallback =
+    unsafe extern "C" fn(chunk: *const c_char, chunk_len: usize, user_data: *mut c_void) -> c_int;
+
+/// A host callback receiving the result of one row of a batch.
+///
+/// # Arguments
+/// * `index` - The position of the row in the batch.
+/// * `ok` - Whether the row rendered, `text` is its error message otherwise.
+/// * `text` - The output or error message of the row, valid for `text_len`
+///   bytes during the call and not null terminated.
+/// * `user_data` - The pointer supplied with the render call.
+///
+/// # Returns
+/// * `0` to continue with the next row, any other value aborts the batch.
+pub type RowCallback = unsafe extern "C" fn(
+    index: usize,
+    ok: bool,
+    text: *const c_char,
+    text_len: usize,
+    user_data: *mut c_void,
+) -> c_int;
+
+/// User data passed back to a callback.
+///
+/// The host is responsible for making the pointee safe to share between threads.
+#[derive(Clone, Copy)]
+pub(crate) struct UserData(pub *mut c_void);
+
+unsafe impl Send for UserData {}
+unsafe impl Sync for UserData {}
+
+/// Forwards written output to a `WriteCallback`.
+pub(crate) struct CallbackWriter {
+    pub callback: WriteCallback,
+    pub user_data: UserData,
+    /// Set once the callback asked to abort the render.
+    pub aborted: bool,
+}
+
+impl io::Write for CallbackWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.aborted {
+            return Err(io::Error::other("aborted by the write callback"));
+        }
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let status =
+            unsafe { (self.callback)(buf.as_ptr() as *const c_char, buf.len(), self.user_data.0) };
+        if status != 0 {
+            self.aborted = true;
+            return Err(io::Error::other("aborted by the write callback"));
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Reports a successful JSON encoded result from a callback.
+///
+/// # Safety
+/// `result` must be the pointer passed to the callback and `json` must be
+/// valid for `json_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn mj_callback_set_ok(
+    result: *mut CallbackResult,
+    json: *const c_char,
+    json_len: usize,
+) {
+    if let Some(result) = result.as_mut() {
+        result.outcome = Some(match str_from_raw(json, json_len) {
+            Ok(json) => Ok(json.to_owned()),
+            Err(RawStrError::Null) => Err("the result is null".to_string()),
+            Err(RawStrError::InvalidUtf8) => Err("the result is not valid UTF-8".to_string()),
+        });
+    }
+}
+
+/// Reports an error from a callback.
+///
+/// # Safety
+/// `result` must be the pointer passed to the callback and `message` must be
+/// valid for `message_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn mj_callback_set_err(
+    result: *mut CallbackResult,
+    message: *const c_char,
+    message_len: usize,
+) {
+    if let Some(result) = result.as_mut() {
+        let message = match slice_from_raw(message as *const u8, message_len) {
+            Some(message) => String::from_utf8_lossy(message).into_owned(),
+            None => "unknown error".to_string(),
+        };
+        result.outcome = Some(Err(message));
+    }
+}
+
+/// Looks up a variable visible at the point where a filter was called.
+///
+/// This sees the context of the render, globals and variables set in the
+/// template, e.g. by `{% set %}` or a loop.
+///
+/// # Returns
+/// * `Ok` with the JSON encoded value, or `Err` if the variable is undefined
+///   or cannot be serialized.
+///
+/// # Safety
+/// `state` must be the pointer passed to the callback and `name` must be
+/// valid for `name_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn mj_filter_state_lookup(
+    state: *const FilterState,
+    name: *const c_char,
+    name_len: usize,
+) -> ResultCString {
+    let Some(state) = state.as_ref() else {
+        return error_result(MJ_ERROR_INVALID_ARGUMENT, "Filter state is null".to_string());
+    };
+    let name_str = make_str!(name, name_len, "variable name");
+
+    match state.state.lookup(name_str) {
+        Some(value) if !value.is_undefined() => match serde_json::to_string(&value) {
+            Ok(json) => ok_result(json),
+            Err(e) => error_result(
+                MJ_ERROR_SERIALIZATION,
+                format!("Could not serialize variable '{}': {}", name_str, e),
+            ),
+        },
+        _ => error_result(
+            MJ_ERROR_UNDEFINED,
+            format!("Variable '{}' is undefined", name_str),
+        ),
+    }
+}
+
+/// Invokes a host callback with the given arguments and decodes its result.
+pub(crate) fn invoke_json_callback(
+    kind: &str,
+    name: &str,
+    callback: JsonCallback,
+    user_data: UserData,
+    args: &[Value],
+) -> Result<Value, Error> {
+    let args_json = serde_json::to_string(args).map_err(|e| {
+        Error::new(
+            ErrorKind::BadSerialization,
+            format!("could not serialize arguments for {} '{}': {}", kind, name, e),
+        )
+    })?;
+
+    let mut result = CallbackResult { outcome: None };
+    unsafe {
+        callback(
+            args_json.as_ptr() as *const c_char,
+            args_json.len(),
+            user_data.0,
+            &mut result,
+        );
+    }
+    decode_json_result(kind, name, result)
+}
+
+/// Invokes a host filter callback that also receives the render state.
+pub(crate) fn invoke_state_filter_callback(
+    name: &str,
+    callback: StateFilterCallback,
+    user_data: UserData,
+    state: &State,
+    args: &[Value],
+) -> Result<Value, Error> {
+    let args_json = serde_json::to_string(args).map_err(|e| {
+        Error::new(
+            ErrorKind::BadSerialization,
+            format!("could not serialize arguments for filter '{}': {}", name, e),
+        )
+    })?;
+    let state_json = serde_json::json!({
+        "name": state.name(),
+        "auto_escape": auto_escape_name(state.auto_escape()),
+    })
+    .to_string();
+
+    let filter_state = FilterState { state };
+    let mut result = CallbackResult { outcome: None };
+    unsafe {
+        callback(
+            args_json.as_ptr() as *const c_char,
+            args_json.len(),
+            state_json.as_ptr() as *const c_char,
+            state_json.len(),
+            &filter_state,
+            user_data.0,
+            &mut result,
+        );
+    }
+    decode_json_result("filter", name, result)
+}
+
+/// Decodes the result a `JsonCallback` or `StateFilterCallback` reported.
+fn decode_json_result(kind: &str, name: &str, result: CallbackResult) -> Result<Value, Error> {
+    match result.outcome {
+        Some(Ok(json)) => json_to_value(&json, Conversion::default()).map_err(|e| {
+            Error::new(
+                ErrorKind::CannotDeserialize,
+                format!("{} '{}' returned an invalid result: {}", kind, name, e),
+            )
+        }),
+        Some(Err(msg)) => Err(Error::new(
+            ErrorKind::InvalidOperation,
+            format!("{} '{}' failed: {}", kind, name, msg),
+        )),
+        None => Err(Error::new(
+            ErrorKind::InvalidOperation,
+            format!("{} '{}' did not return a result", kind, name),
+        )),
+    }
+}
+
+/// Invokes a host method callback for a method called on `value`.
+///
+/// Returns `None` if the callback did not report a result.
+pub(crate) fn invoke_method_callback(
+    method: &str,
+    callback: MethodCallback,
+    user_data: UserData,
+    value: &Value,
+    args: &[Value],
+) -> Result<Option<Value>, Error> {
+    let serialize_error = |e: serde_json::Error| {
+        Error::new(
+            ErrorKind::BadSerialization,
+            format!("could not serialize arguments for method '{}': {}", method, e),
+        )
+    };
+    let object_json = serde_json::to_string(value).map_err(serialize_error)?;
+    let args_json = serde_json::to_string(args).map_err(serialize_error)?;
+
+    let mut result = CallbackResult { outcome: None };
+    unsafe {
+        callback(
+            object_json.as_ptr() as *const c_char,
+            object_json.len(),
+            method.as_ptr() as *const c_char,
+            method.len(),
+            args_json.as_ptr() as *const c_char,
+            args_json.len(),
+            user_data.0,
+            &mut result,
+        );
+    }
+
+    match result.outcome {
+        Some(Ok(json)) => json_to_value(&json, Conversion::default()).map(Some).map_err(|e| {
+            Error::new(
+                ErrorKind::CannotDeserialize,
+                format!("method '{}' returned an invalid result: {}", method, e),
+            )
+        }),
+        Some(Err(msg)) => Err(Error::new(
+            ErrorKind::InvalidOperation,
+            format!("method '{}' failed: {}", method, msg),
+        )),
+        None => Ok(None),
+    }
+}
+
+/// Invokes a host loader callback for the named template.
+pub(crate) fn invoke_loader_callback(
+    name: &str,
+    callback: LoaderCallback,
+    user_data: UserData,
+) -> Result<Option<String>, Error> {
+    let mut result = CallbackResult { outcome: None };
+    unsafe {
+        callback(
+            name.as_ptr() as *const c_char,
+            name.len(),
+            user_data.0,
+            &mut result,
+        );
+    }
+
+    match result.outcome {
+        Some(Ok(source)) => Ok(Some(source)),
+        Some(Err(msg)) => Err(Error::new(
+            ErrorKind::InvalidOperation,
+            format!("loader failed for template '{}': {}", name, msg),
+        )),
+        None => Ok(None),
+    }
+}
+
+/// Invokes a host escape callback and returns the escaped string.
+pub(crate) fn invoke_escape_callback(
+    value: &str,
+    callback: EscapeCallback,
+    user_data: UserData,
+) -> Result<String, Error> {
+    let mut result = CallbackResult { outcome: None };
+    unsafe {
+        callback(
+            value.as_ptr() as *const c_char,
+            value.len(),
+            user_data.0,
+            &mut result,
+        );
+    }
+
+    match result.outcome {
+        Some(Ok(escaped)) => Ok(escaped),
+        Some(Err(msg)) => Err(Error::new(
+            ErrorKind::InvalidOperation,
+            format!("escape callback failed: {}", msg),
+        )),
+        None => Err(Error::new(
+            ErrorKind::InvalidOperation,
+            "escape callback did not report a result",
+        )),
+    }
+}
+
+/// Invokes a host resolver callback for the named variable.
+///
+/// Returns `None` if the callback did not report a value.
+pub(crate) fn invoke_resolver_callback(
+    name: &str,
+    callback: ResolverCallback,
+    user_data: UserData,
+) -> Result<Option<Value>, Error> {
+    let mut result = CallbackResult { outcome: None };
+    unsafe {
+        callback(
+            name.as_ptr() as *const c_char,
+            name.len(),
+            user_data.0,
+            &mut result,
+        );
+    }
+
+    match result.outcome {
+        Some(Ok(json)) => json_to_value(&json, Conversion::default()).map(Some).map_err(|e| {
+            Error::new(
+                ErrorKind::CannotDeserialize,
+                format!("resolver returned an invalid value for '{}': {}", name, e),
+            )
+        }),
+        Some(Err(msg)) => Err(Error::new(
+            ErrorKind::InvalidOperation,
+            format!("resolver failed for '{}': {}", name, msg),
+        )),
+        None => Ok(None),
+    }
+}
@@ -0,0 +1,174 @@
+use std::borrow::Cow;
+
+use minijinja::{filters, Error, ErrorKind};
+
+/// The languages whose casing differs from the language independent rules.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Language {
+    /// Turkish and Azeri, which pair a dotted and a dotless i.
+    Turkic,
+    /// Dutch, which capitalizes the `ij` digraph as a whole.
+    Dutch,
+    Other,
+}
+
+impl Language {
+    /// Reads the language of a locale such as `tr`, `de-DE` or `nl_BE`.
+    fn parse(filter: &str, locale: &str) -> Result<Language, Error> {
+        let language = locale.split(['-', '_']).next().unwrap_or("");
+        if !(2..=3).contains(&language.len()) || !language.chars().all(|c| c.is_ascii_alphabetic())
+        {
+            return Err(Error::new(
+                ErrorKind::InvalidOperation,
+                format!("{} got an invalid locale '{}'", filter, locale),
+            ));
+        }
+        Ok(match language.to_ascii_lowercase().as_str() {
+            "tr" | "az" => Language::Turkic,
+            "nl" => Language::Dutch,
+            _ => Language::Other,
+        })
+    }
+
+    fn upper(self, s: &str) -> String {
+        match self {
+            Language::Turkic => s.replace('i', "\u{130}").to_uppercase(),
+            _ => s.to_uppercase(),
+        }
+    }
+
+    fn lower(self, s: &str) -> String {
+        match self {
+            // A dotted capital I may also be spelled as I and a combining dot.
+            Language::Turkic => s
+                .replace("I\u{307}", "i")
+                .replace('\u{130}', "i")
+                .replace('I', "\u{131}")
+                .to_lowercase(),
+            _ => s.to_lowercase(),
+        }
+    }
+
+    /// Capitalizes a word: its first letter in title case, the rest in lowercase.
+    fn capitalize_word(self, word: &str, out: &mut String) {
+        if self == Language::Dutch {
+            if let Some(prefix) = word.get(..2).filter(|p| p.eq_ignore_ascii_case("ij")) {
+                let rest = &word[prefix.len()..];
+                out.push_str("IJ");
+                out.push_str(&self.lower(rest));
+                return;
+            }
+        }
+        let mut chars = word.chars();
+        let Some(first) = chars.next() else {
+            return;
+        };
+        match first {
+            'i' if self == Language::Turkic => out.push('\u{130}'),
+            _ => push_titlecase(first, out),
+        }
+        out.push_str(&self.lower(chars.as_str()));
+    }
+}
+
+/// Writes the title case form of `c`, which differs from the uppercase form
+/// for ligatures, digraphs and `ß`.
+fn push_titlecase(c: char, out: &mut String) {
+    let title = match c {
+        'ß' => "Ss",
+        '\u{1c4}'..='\u{1c6}' => "\u{1c5}",
+        '\u{1c7}'..='\u{1c9}' => "\u{1c8}",
+        '\u{1ca}'..='\u{1cc}' => "\u{1cb}",
+        '\u{1f1}'..='\u{1f3}' => "\u{1f2}",
+        '\u{fb00}' => "Ff",
+        '\u{fb01}' => "Fi",
+        '\u{fb02}' => "Fl",
+        '\u{fb03}' => "Ffi",
+        '\u{fb04}' => "Ffl",
+        '\u{fb05}' | '\u{fb06}' => "St",
+        _ => {
+            out.extend(c.to_uppercase());
+            return;
+        }
+    };
+    out.push_str(title);
+}
+
+/// Converts a value to uppercase, following the rules of `locale` if given.
+pub(crate) fn upper(value: Cow<'_, str>, locale: Option<&str>) -> Result<String, Error> {
+    match locale {
+        None => Ok(value.to_uppercase()),
+        Some(locale) => Ok(Language::parse("upper", locale)?.upper(&value)),
+    }
+}
+
+/// Converts a value to lowercase, following the rules of `locale` if given.
+pub(crate) fn lower(value: Cow<'_, str>, locale: Option<&str>) -> Result<String, Error> {
+    match locale {
+        None => Ok(value.to_lowercase()),
+        Some(locale) => Ok(Language::parse("lower", locale)?.lower(&value)),
+    }
+}
+
+/// Converts a value to title case, following the rules of `locale` if given.
+///
+/// Words are separated like the MiniJinja builtin, by whitespace and ASCII
+/// punctuation.
+pub(crate) fn title(value: Cow<'_, str>, locale: Option<&str>) -> Result<String, Error> {
+    let Some(locale) = locale else {
+        return Ok(filters::title(value));
+    };
+    let language = Language::parse("title", locale)?;
+    let mut out = String::with_capacity(value.len());
+    let mut rest = value.as_ref();
+    while !rest.is_empty() {
+        let word_len = rest
+            .find(|c: char| c.is_ascii_punctuation() || c.is_whitespace())
+            .unwrap_or(rest.len());
+        language.capitalize_word(&rest[..word_len], &mut out);
+        rest = &rest[word_len..];
+        let sep_len = rest
+            .find(|c: char| !(c.is_ascii_punctuation() || c.is_whitespace()))
+            .unwrap_or(rest.len());
+        out.push_str(&rest[..sep_len]);
+        rest = &rest[sep_len..];
+    }
+    Ok(out)
+}
+
+/// Uppercases the first letter of a value and lowercases the rest, following
+/// the rules of `locale` if given.
+pub(crate) fn capitalize(value: Cow<'_, str>, locale: Option<&str>) -> Result<String, Error> {
+    match locale {
+        None => {
+            let mut chars = value.chars();
+            Ok(match chars.next() {
+                None => String::new(),
+                Some(first) => {
+                    let rest = chars.as_str().to_lowercase();
+                    first.to_uppercase().chain(rest.chars()).collect()
+                }
+            })
+        }
+        Some(locale) => {
+            let mut out = String::with_capacity(value.len());
+            Language::parse("capitalize", locale)?.capitalize_word(&value, &mut out);
+            Ok(out)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use super::{capitalize, lower, upper};
+
+    #[test]
+    fn default_locale_casing() {
+        assert_eq!(upper(Cow::Borrowed("straße"), None).unwrap(), "STRASSE");
+        assert_eq!(lower(Cow::Borrowed("ÀB"), None).unwrap(), "àb");
+        assert_eq!(capitalize(Cow::Borrowed("éCOLE ab"), None).unwrap(), "École ab");
+        assert_eq!(capitalize(Cow::Borrowed(""), None).unwrap(), "");
+    }
+}
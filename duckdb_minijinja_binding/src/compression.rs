@@ -0,0 +1,92 @@
+use std::borrow::Cow;
+use std::io::Read;
+
+use flate2::read::MultiGzDecoder;
+
+/// The largest template source a compressed one may decompress to, so a
+/// small gzip bomb cannot exhaust the memory of the process.
+const MAX_DECOMPRESSED_LEN: u64 = 64 * 1024 * 1024;
+
+/// How the bytes of a template source are compressed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum TemplateCompression {
+    #[default]
+    None,
+    Gzip,
+}
+
+impl TemplateCompression {
+    pub fn parse(compression: &str) -> Result<TemplateCompression, String> {
+        match compression {
+            "none" => Ok(TemplateCompression::None),
+            "gzip" => Ok(TemplateCompression::Gzip),
+            _ => Err(format!(
+                "Invalid template compression '{}': expected none or gzip",
+                compression
+            )),
+        }
+    }
+
+    /// Turns the bytes of a template source into its text.
+    ///
+    /// Uncompressed sources are borrowed.  Gzip sources may consist of several
+    /// members, as written by concatenating gzip files, which are decompressed
+    /// one after another, up to `MAX_DECOMPRESSED_LEN` bytes in total.
+    pub fn decode(self, source: &[u8]) -> Result<Cow<'_, str>, String> {
+        match self {
+            TemplateCompression::None => std::str::from_utf8(source)
+                .map(Cow::Borrowed)
+                .map_err(|_| "template source is not valid UTF-8".to_string()),
+            TemplateCompression::Gzip => gunzip(source, MAX_DECOMPRESSED_LEN).map(Cow::Owned),
+        }
+    }
+}
+
+/// Decompresses a gzip template source of at most `limit` bytes.
+fn gunzip(source: &[u8], limit: u64) -> Result<String, String> {
+    let mut decompressed = Vec::new();
+    // One byte more than allowed tells a source at the limit from a longer one.
+    MultiGzDecoder::new(source)
+        .take(limit + 1)
+        .read_to_end(&mut decompressed)
+        .map_err(|err| format!("Invalid gzip template source: {}", err))?;
+    if decompressed.len() as u64 > limit {
+        return Err(format!(
+            "gzip template source decompresses to more than {} bytes",
+            limit
+        ));
+    }
+    String::from_utf8(decompressed)
+        .map_err(|_| "decompressed template source is not valid UTF-8".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    use super::*;
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn gzip_sources_are_capped() {
+        let source = gzip(&[b' '; 4096]);
+        assert_eq!(gunzip(&source, 4096).map(|s| s.len()), Ok(4096));
+        assert_eq!(
+            gunzip(&source, 4095),
+            Err("gzip template source decompresses to more than 4095 bytes".to_string())
+        );
+
+        // 100 MiB of spaces compress to about 100 KiB.
+        let bomb = gzip(&vec![b' '; 100 * 1024 * 1024]);
+        let err = TemplateCompression::Gzip.decode(&bomb).unwrap_err();
+        assert_eq!(err, "gzip template source decompresses to more than 67108864 bytes");
+    }
+}
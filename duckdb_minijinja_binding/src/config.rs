@@ -0,0 +1,267 @@
+use serde_json::{Map, Value as JsonValue};
+
+use crate::compression::TemplateCompression;
+use crate::context::ContextFormat;
+use crate::formatter::FloatFormat;
+use crate::syntax::SyntaxOptions;
+use crate::{datetime, AutoescapeMode, RenderOptions};
+
+/// The accepted values of `undefined_behavior`.
+const UNDEFINED_BEHAVIORS: [&str; 4] = ["strict", "semistrict", "chainable", "lenient"];
+
+/// The keys a render config may contain.
+const RENDER_KEYS: [&str; 31] = [
+    "template_path",
+    "autoescape",
+    "undefined_behavior",
+    "autoescape_extensions",
+    "block_delimiters",
+    "variable_delimiters",
+    "comment_delimiters",
+    "line_statement_prefix",
+    "line_comment_prefix",
+    "trim_blocks",
+    "lstrip_blocks",
+    "keep_trailing_newline",
+    "fuel",
+    "recursion_limit",
+    "null_as_undefined",
+    "pycompat",
+    "float_precision",
+    "float_trailing_zeros",
+    "autoescape_mode",
+    "now",
+    "random_seed",
+    "allow_env",
+    "context_format",
+    "debug",
+    "timeout_ms",
+    "undefined_placeholder",
+    "root_variable",
+    "strict_context",
+    "big_integers_as_strings",
+    "template_compression",
+    "ignore_unknown_keys",
+];
+
+/// The fields of a JSON config object.
+struct Config<'a>(&'a Map<String, JsonValue>);
+
+impl Config<'_> {
+    /// Returns the value of `key`, `None` if it is missing or null.
+    fn get(&self, key: &str) -> Option<&JsonValue> {
+        self.0.get(key).filter(|value| !value.is_null())
+    }
+
+    fn bool(&self, key: &str, default: bool) -> Result<bool, String> {
+        match self.get(key) {
+            None => Ok(default),
+            Some(value) => value.as_bool().ok_or_else(|| invalid(key, "a boolean")),
+        }
+    }
+
+    fn string(&self, key: &str) -> Result<Option<String>, String> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(JsonValue::String(value)) if value.is_empty() => Ok(None),
+            Some(JsonValue::String(value)) => Ok(Some(value.clone())),
+            Some(_) => Err(invalid(key, "a string")),
+        }
+    }
+
+    /// Reads a non-negative integer.
+    fn uint(&self, key: &str) -> Result<Option<u64>, String> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(value) => value
+                .as_u64()
+                .map(Some)
+                .ok_or_else(|| invalid(key, "a non-negative integer")),
+        }
+    }
+
+    fn strings(&self, key: &str) -> Result<Vec<String>, String> {
+        let Some(value) = self.get(key) else {
+            return Ok(Vec::new());
+        };
+        let items = value.as_array().ok_or_else(|| invalid(key, "a list of strings"))?;
+        items
+            .iter()
+            .map(|item| {
+                item.as_str()
+                    .map(str::to_owned)
+                    .ok_or_else(|| invalid(key, "a list of strings"))
+            })
+            .collect()
+    }
+
+    /// Reads the delimiters and line prefixes of the template syntax.
+    fn syntax(&self) -> Result<SyntaxOptions, String> {
+        let mut syntax = SyntaxOptions::from_delimiters(
+            self.delimiters("block_delimiters")?,
+            self.delimiters("variable_delimiters")?,
+            self.delimiters("comment_delimiters")?,
+        )?;
+        syntax.line_statement_prefix = self.string("line_statement_prefix")?;
+        syntax.line_comment_prefix = self.string("line_comment_prefix")?;
+        Ok(syntax)
+    }
+
+    /// Reads a `[start, end]` delimiter pair.
+    fn delimiters(&self, key: &str) -> Result<(Option<String>, Option<String>), String> {
+        let Some(value) = self.get(key) else {
+            return Ok((None, None));
+        };
+        match value.as_array().map(Vec::as_slice) {
+            Some([JsonValue::String(start), JsonValue::String(end)])
+                if !start.is_empty() && !end.is_empty() =>
+            {
+                Ok((Some(start.clone()), Some(end.clone())))
+            }
+            _ => Err(invalid(key, "a list of two non-empty strings")),
+        }
+    }
+}
+
+/// Parses a config object, rejecting keys that are not render options.
+///
+/// A misspelt key would otherwise silently leave a limit such as `fuel` or
+/// `timeout_ms` unset.  Configs written for newer versions can set
+/// `"ignore_unknown_keys": true` to skip the keys this version does not know.
+fn parse_object(config: &str) -> Result<Map<String, JsonValue>, String> {
+    let config = match serde_json::from_str(config)
+        .map_err(|err| format!("Invalid config JSON: {}", err))?
+    {
+        JsonValue::Object(config) => config,
+        _ => return Err("Invalid config: expected a JSON object".to_string()),
+    };
+    if Config(&config).bool("ignore_unknown_keys", false)? {
+        return Ok(config);
+    }
+    match config.keys().find(|key| !RENDER_KEYS.contains(&key.as_str())) {
+        Some(key) => Err(format!("Unknown config key '{}'", key)),
+        None => Ok(config),
+    }
+}
+
+fn invalid(key: &str, expected: &str) -> String {
+    format!("Invalid config value for '{}': expected {}", key, expected)
+}
+
+/// Reads the render options from a JSON config object.
+///
+/// The keys are named like the options of the SQL functions and missing or
+/// null keys keep their defaults.  Unknown keys are rejected unless the config
+/// sets `ignore_unknown_keys`.
+pub(crate) fn render_options_from_json(config: &str) -> Result<RenderOptions, String> {
+    let config = parse_object(config)?;
+    let config = Config(&config);
+    let defaults = RenderOptions::default();
+
+    let undefined_behavior = config.string("undefined_behavior")?;
+    if let Some(behavior) = &undefined_behavior {
+        if !UNDEFINED_BEHAVIORS.contains(&behavior.as_str()) {
+            return Err(format!(
+                "Invalid undefined behavior '{}': expected strict, semistrict, chainable or lenient",
+                behavior
+            ));
+        }
+    }
+
+    let float_precision = match config.uint("float_precision")? {
+        Some(precision) => {
+            i32::try_from(precision).map_err(|_| invalid("float_precision", "a smaller integer"))?
+        }
+        None => -1,
+    };
+
+    Ok(RenderOptions {
+        template_path: config.string("template_path")?,
+        autoescape: config.bool("autoescape", defaults.autoescape)?,
+        undefined_behavior,
+        autoescape_on: config.strings("autoescape_extensions")?,
+        syntax: config.syntax()?,
+        trim_blocks: config.bool("trim_blocks", defaults.trim_blocks)?,
+        lstrip_blocks: config.bool("lstrip_blocks", defaults.lstrip_blocks)?,
+        keep_trailing_newline: config.bool("keep_trailing_newline", defaults.keep_trailing_newline)?,
+        fuel: match config.uint("fuel")? {
+            Some(fuel) => i64::try_from(fuel).map_err(|_| invalid("fuel", "a smaller integer"))?,
+            None => defaults.fuel,
+        },
+        recursion_limit: match config.uint("recursion_limit")? {
+            Some(limit) => usize::try_from(limit)
+                .map_err(|_| invalid("recursion_limit", "a smaller integer"))?,
+            None => defaults.recursion_limit,
+        },
+        null_as_undefined: config.bool("null_as_undefined", defaults.null_as_undefined)?,
+        pycompat: config.bool("pycompat", defaults.pycompat)?,
+        float_format: FloatFormat::from_ffi(
+            float_precision,
+            config.bool("float_trailing_zeros", true)?,
+        ),
+        autoescape_mode: config
+            .string("autoescape_mode")?
+            .map(|mode| AutoescapeMode::parse(&mode))
+            .transpose()?,
+        now: config
+            .string("now")?
+            .map(|now| datetime::parse_frozen_now(&now))
+            .transpose()?,
+        random_seed: config.uint("random_seed")?,
+        allow_env: config.bool("allow_env", defaults.allow_env)?,
+        context_format: config
+            .string("context_format")?
+            .map(|format| ContextFormat::parse(&format))
+            .transpose()?
+            .unwrap_or_default(),
+        debug: config.bool("debug", defaults.debug)?,
+        timeout_ms: config.uint("timeout_ms")?.unwrap_or(defaults.timeout_ms),
+        undefined_placeholder: config.string("undefined_placeholder")?,
+        root_variable: config.string("root_variable")?,
+        strict_context: config.bool("strict_context", defaults.strict_context)?,
+        big_integers_as_strings: config
+            .bool("big_integers_as_strings", defaults.big_integers_as_strings)?,
+        template_compression: config
+            .string("template_compression")?
+            .map(|compression| TemplateCompression::parse(&compression))
+            .transpose()?
+            .unwrap_or_default(),
+    })
+}
+
+/// Reads the template syntax from a JSON config object.
+///
+/// This takes the `block_delimiters`, `variable_delimiters`,
+/// `comment_delimiters`, `line_statement_prefix` and `line_comment_prefix`
+/// keys of a render config, the other render options are ignored.
+pub(crate) fn syntax_options_from_json(config: &str) -> Result<SyntaxOptions, String> {
+    Config(&parse_object(config)?).syntax()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        let err = render_options_from_json(r#"{"fuel_limit": 100}"#).err();
+        assert_eq!(err.as_deref(), Some("Unknown config key 'fuel_limit'"));
+        let err = syntax_options_from_json(r#"{"variable_delimiter": ["[[", "]]"]}"#).err();
+        assert_eq!(err.as_deref(), Some("Unknown config key 'variable_delimiter'"));
+
+        let options = render_options_from_json(r#"{"fuel": 100, "timeout_ms": 5}"#).unwrap();
+        assert_eq!((options.fuel, options.timeout_ms), (100, 5));
+        assert!(syntax_options_from_json(r#"{"fuel": 100}"#).is_ok());
+    }
+
+    #[test]
+    fn unknown_keys_can_be_ignored() {
+        let config = r#"{"ignore_unknown_keys": true, "timeout": 100, "fuel": 7}"#;
+        assert_eq!(render_options_from_json(config).unwrap().fuel, 7);
+        let err = render_options_from_json(r#"{"ignore_unknown_keys": "yes"}"#).err();
+        assert_eq!(
+            err.as_deref(),
+            Some("Invalid config value for 'ignore_unknown_keys': expected a boolean")
+        );
+    }
+}
@@ -0,0 +1,492 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use minijinja::value::ValueKind;
+use minijinja::Value;
+use serde::de::{self, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde_json::{Number, Value as JsonValue};
+
+/// The key marking a JSON object as a base64 encoded blob.
+const BLOB_KEY: &str = "__blob__";
+
+/// The largest integer a JavaScript number holds exactly, `2^53 - 1`.
+const MAX_SAFE_INTEGER: u64 = (1 << 53) - 1;
+
+/// How the values of a document are converted.
+#[derive(Clone, Copy, Default)]
+pub(crate) struct Conversion {
+    /// Whether nulls become undefined instead of none, so they behave like
+    /// missing keys.
+    pub null_as_undefined: bool,
+    /// Whether integers beyond `MAX_SAFE_INTEGER` become strings of their
+    /// digits, so IDs of JavaScript origin print and serialize exactly.
+    pub big_integers_as_strings: bool,
+}
+
+impl Conversion {
+    /// The value a null converts to.
+    fn null(self) -> Value {
+        if self.null_as_undefined {
+            Value::UNDEFINED
+        } else {
+            Value::from(())
+        }
+    }
+
+    /// Converts an integer, `digits` renders it if it becomes a string.
+    fn integer(self, magnitude: u128, value: Value, digits: impl fmt::Display) -> Value {
+        if self.big_integers_as_strings && magnitude > MAX_SAFE_INTEGER as u128 {
+            Value::from(digits.to_string())
+        } else {
+            value
+        }
+    }
+}
+
+/// An error turning a JSON document into a MiniJinja value.
+#[derive(Debug)]
+pub(crate) enum ContextError {
+    /// The document is not valid JSON.
+    Json(serde_json::Error),
+    /// The document is not valid YAML or cannot be represented as JSON.
+    Yaml(serde_yaml::Error),
+    /// The document is not valid MessagePack or cannot be represented as JSON.
+    MsgPack(rmp_serde::decode::Error),
+    /// A JSON or YAML document is not valid UTF-8.
+    NotUtf8(ContextFormat),
+    /// A `{"__blob__": ...}` value does not hold valid base64.
+    Blob(String),
+    /// The context is not an object and no variable to bind it to is set.
+    NotAnObject(ValueKind),
+}
+
+impl fmt::Display for ContextError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContextError::Json(e) => write!(f, "Invalid JSON: {}", e),
+            ContextError::Yaml(e) => write!(f, "Invalid YAML: {}", e),
+            ContextError::MsgPack(e) => write!(f, "Invalid MessagePack: {}", e),
+            ContextError::NotUtf8(format) => {
+                write!(f, "{} context is not valid UTF-8", format.name())
+            }
+            ContextError::Blob(msg) => write!(f, "Invalid blob: {}", msg),
+            ContextError::NotAnObject(kind) => write!(
+                f,
+                "Invalid context: expected an object of variables, got a {}; set root_variable \
+                 to bind it to a variable",
+                kind
+            ),
+        }
+    }
+}
+
+/// The format a render context is written in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum ContextFormat {
+    #[default]
+    Json,
+    Yaml,
+    MsgPack,
+}
+
+impl ContextFormat {
+    pub fn parse(format: &str) -> Result<ContextFormat, String> {
+        match format {
+            "json" => Ok(ContextFormat::Json),
+            "yaml" => Ok(ContextFormat::Yaml),
+            "msgpack" => Ok(ContextFormat::MsgPack),
+            _ => Err(format!(
+                "Invalid context format '{}': expected json, yaml or msgpack",
+                format
+            )),
+        }
+    }
+
+    /// Parses a JSON or YAML document into its JSON data model.
+    ///
+    /// YAML aliases are resolved and `<<` merge keys applied.  A YAML stream
+    /// holding more than one document is rejected, as are mapping keys that
+    /// are not strings.  MessagePack is not text, `to_value` decodes it
+    /// directly instead.
+    fn parse_document(self, document: &[u8]) -> Result<JsonValue, ContextError> {
+        let document = std::str::from_utf8(document).map_err(|_| ContextError::NotUtf8(self))?;
+        match self {
+            ContextFormat::Yaml => {
+                let mut value: serde_yaml::Value =
+                    serde_yaml::from_str(document).map_err(ContextError::Yaml)?;
+                value.apply_merge().map_err(ContextError::Yaml)?;
+                serde_yaml::from_value(value).map_err(ContextError::Yaml)
+            }
+            ContextFormat::Json | ContextFormat::MsgPack => {
+                serde_json::from_str(document).map_err(ContextError::Json)
+            }
+        }
+    }
+
+    /// Parses a document of this format into a MiniJinja value like
+    /// `json_to_value`.
+    ///
+    /// MessagePack is decoded from the raw bytes without going through JSON,
+    /// which is what makes it cheaper than the text formats.
+    pub fn to_value(self, document: &[u8], conversion: Conversion) -> Result<Value, ContextError> {
+        if self == ContextFormat::MsgPack {
+            return msgpack_to_value(document, conversion);
+        }
+        json_value_to_value(self.parse_document(document)?, conversion)
+    }
+
+    /// Fails if an object of `document` repeats a key, naming the key.
+    ///
+    /// Parsing keeps the last value of a repeated key, so this runs first
+    /// when the context is strict.  YAML documents always reject repeated
+    /// keys while parsing.
+    pub fn check_duplicate_keys(self, document: &[u8]) -> Result<(), ContextError> {
+        match self {
+            ContextFormat::Json => {
+                let document =
+                    std::str::from_utf8(document).map_err(|_| ContextError::NotUtf8(self))?;
+                let mut deserializer = serde_json::Deserializer::from_str(document);
+                DuplicateKeyCheck
+                    .deserialize(&mut deserializer)
+                    .map_err(ContextError::Json)
+            }
+            ContextFormat::MsgPack => {
+                let mut deserializer = rmp_serde::Deserializer::new(document);
+                DuplicateKeyCheck
+                    .deserialize(&mut deserializer)
+                    .map_err(ContextError::MsgPack)
+            }
+            ContextFormat::Yaml => Ok(()),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            ContextFormat::Json => "JSON",
+            ContextFormat::Yaml => "YAML",
+            ContextFormat::MsgPack => "MessagePack",
+        }
+    }
+}
+
+/// Binds a context that is not an object to `root_variable`.
+///
+/// Objects are the variables of the render and are passed through, as is a
+/// null context, which renders without variables.  Any other value, such as
+/// an array or a scalar, becomes the only variable `root_variable` or is
+/// rejected if it is not set.
+pub(crate) fn bind_root(ctx: Value, root_variable: Option<&str>) -> Result<Value, ContextError> {
+    match (ctx.kind(), root_variable) {
+        (ValueKind::Map | ValueKind::None | ValueKind::Undefined, _) => Ok(ctx),
+        (_, Some(name)) => Ok(Value::from_iter([(name.to_owned(), ctx)])),
+        (kind, None) => Err(ContextError::NotAnObject(kind)),
+    }
+}
+
+/// Decodes a single MessagePack value into a MiniJinja value, rejecting
+/// trailing bytes.
+fn msgpack_to_value(document: &[u8], conversion: Conversion) -> Result<Value, ContextError> {
+    let mut rest = document;
+    let mut deserializer = rmp_serde::Deserializer::new(&mut rest);
+    let value = MsgPackVisitor { conversion }
+        .deserialize(&mut deserializer)
+        .map_err(ContextError::MsgPack)?;
+    if !rest.is_empty() {
+        return Err(ContextError::MsgPack(de::Error::custom(format!(
+            "{} trailing bytes after the value",
+            rest.len()
+        ))));
+    }
+    Ok(value)
+}
+
+/// Builds MiniJinja values from MessagePack.
+///
+/// Values convert like their JSON counterparts and binary values become
+/// bytes, like blobs.  Map keys that are not strings are rejected.
+#[derive(Clone, Copy)]
+struct MsgPackVisitor {
+    conversion: Conversion,
+}
+
+impl<'de> DeserializeSeed<'de> for MsgPackVisitor {
+    type Value = Value;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for MsgPackVisitor {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a MessagePack value")
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Value, E> {
+        Ok(Value::from(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Value, E> {
+        Ok(self.conversion.integer(v.unsigned_abs().into(), Value::from(v), v))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Value, E> {
+        Ok(self.conversion.integer(v.into(), Value::from(v), v))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Value, E> {
+        Ok(Value::from(v))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Value, E> {
+        Ok(Value::from(v))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Value, E> {
+        Ok(Value::from(v))
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Value, E> {
+        Ok(Value::from_bytes(v.to_vec()))
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Value, E> {
+        Ok(Value::from_bytes(v))
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Value, E> {
+        Ok(self.conversion.null())
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Value, E> {
+        Ok(self.conversion.null())
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut items = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+        while let Some(item) = seq.next_element_seed(self)? {
+            items.push(item);
+        }
+        Ok(Value::from(items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let mut entries = Vec::with_capacity(map.size_hint().unwrap_or(0).min(4096));
+        while let Some(key) = map.next_key::<String>()? {
+            entries.push((key, map.next_value_seed(self)?));
+        }
+        Ok(entries.into_iter().collect())
+    }
+}
+
+/// Walks a document and fails at the first object that repeats a key.
+///
+/// Nothing is built, so a strict context costs one extra pass over the
+/// document.  Numbers of the arbitrary precision representation arrive as
+/// single key objects and pass like any other object.
+#[derive(Clone, Copy)]
+struct DuplicateKeyCheck;
+
+impl<'de> DeserializeSeed<'de> for DuplicateKeyCheck {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for DuplicateKeyCheck {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("any value")
+    }
+
+    fn visit_bool<E: de::Error>(self, _: bool) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_i64<E: de::Error>(self, _: i64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_u64<E: de::Error>(self, _: u64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_f64<E: de::Error>(self, _: f64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_str<E: de::Error>(self, _: &str) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_bytes<E: de::Error>(self, _: &[u8]) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        while seq.next_element_seed(self)?.is_some() {}
+        Ok(())
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        let mut keys = HashSet::new();
+        while let Some(key) = map.next_key::<String>()? {
+            map.next_value_seed(self)?;
+            if let Some(key) = keys.replace(key) {
+                return Err(de::Error::custom(format_args!("duplicate key '{}'", key)));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parses a JSON document into a MiniJinja value.
+///
+/// Numbers keep the exact digits of the document, see `number_to_value`, and
+/// blobs are decoded, see `json_value_to_value`.  `conversion` decides what
+/// nulls and integers beyond the precision of JavaScript numbers become.
+pub(crate) fn json_to_value(json_str: &str, conversion: Conversion) -> Result<Value, ContextError> {
+    let value = serde_json::from_str(json_str).map_err(ContextError::Json)?;
+    json_value_to_value(value, conversion)
+}
+
+/// Converts a parsed JSON value into a MiniJinja value.
+///
+/// An object whose only key is `__blob__` is a blob: a base64 string in it
+/// becomes a bytes value and `null` becomes none, which is how NULL blobs are
+/// passed.
+pub(crate) fn json_value_to_value(
+    value: JsonValue,
+    conversion: Conversion,
+) -> Result<Value, ContextError> {
+    Ok(match value {
+        JsonValue::Null => conversion.null(),
+        JsonValue::Bool(b) => Value::from(b),
+        JsonValue::Number(n) => number_to_value(&n, conversion),
+        JsonValue::String(s) => Value::from(s),
+        JsonValue::Array(items) => items
+            .into_iter()
+            .map(|item| json_value_to_value(item, conversion))
+            .collect::<Result<Value, _>>()?,
+        JsonValue::Object(mut map) if map.len() == 1 && map.contains_key(BLOB_KEY) => {
+            match map.remove(BLOB_KEY) {
+                Some(JsonValue::String(encoded)) => {
+                    Value::from_bytes(BASE64.decode(encoded).map_err(|e| {
+                        ContextError::Blob(format!("{} is not valid base64: {}", BLOB_KEY, e))
+                    })?)
+                }
+                Some(JsonValue::Null) => conversion.null(),
+                _ => {
+                    return Err(ContextError::Blob(format!(
+                        "{} must be a base64 string or null",
+                        BLOB_KEY
+                    )))
+                }
+            }
+        }
+        JsonValue::Object(map) => map
+            .into_iter()
+            .map(|(key, value)| Ok((key, json_value_to_value(value, conversion)?)))
+            .collect::<Result<Value, ContextError>>()?,
+    })
+}
+
+/// Converts a JSON number without losing precision.
+///
+/// Integers become integer values as long as they fit into 128 bits, or
+/// strings of their digits with `big_integers_as_strings` if they are beyond
+/// the safe range of JavaScript numbers.  Other numbers become floats if a
+/// float renders to the same digits as the input, otherwise the original text
+/// is kept as a string so the exact value reaches the output.
+fn number_to_value(n: &Number, conversion: Conversion) -> Value {
+    let repr = n.as_str();
+    if let Some(i) = n.as_i64() {
+        return conversion.integer(i.unsigned_abs().into(), Value::from(i), repr);
+    }
+    if let Some(u) = n.as_u64() {
+        return conversion.integer(u.into(), Value::from(u), repr);
+    }
+    if let Ok(i) = repr.parse::<i128>() {
+        return conversion.integer(i.unsigned_abs(), Value::from(i), repr);
+    }
+    if let Ok(u) = repr.parse::<u128>() {
+        return conversion.integer(u, Value::from(u), repr);
+    }
+    match repr.parse::<f64>() {
+        Ok(f) if Value::from(f).to_string() == repr => Value::from(f),
+        _ => Value::from(repr),
+    }
+}
+
+/// Deep-merges `overlay` into `base`.
+///
+/// Objects are merged key by key, any other value in `overlay` replaces the
+/// one in `base`.
+fn merge_json(base: &mut JsonValue, overlay: JsonValue) {
+    match (base, overlay) {
+        (JsonValue::Object(base), JsonValue::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_json(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Parses the documents and deep-merges them from left to right.
+///
+/// Later documents win, an empty list yields an empty object.  The merged
+/// document is converted like `json_to_value`.
+pub(crate) fn merge_json_documents(
+    documents: &[&[u8]],
+    format: ContextFormat,
+    conversion: Conversion,
+) -> Result<Value, String> {
+    if format == ContextFormat::MsgPack {
+        return Err("MessagePack contexts cannot be merged, use json or yaml".to_string());
+    }
+    let mut merged = JsonValue::Object(Default::default());
+    for (index, document) in documents.iter().enumerate() {
+        let value = format.parse_document(document).map_err(|e| {
+            let detail = match e {
+                ContextError::Json(e) => e.to_string(),
+                ContextError::Yaml(e) => e.to_string(),
+                ContextError::MsgPack(e) => e.to_string(),
+                ContextError::NotUtf8(_) => "not valid UTF-8".to_string(),
+                ContextError::Blob(msg) => msg,
+                e @ ContextError::NotAnObject(_) => e.to_string(),
+            };
+            format!("Invalid {} in context {}: {}", format.name(), index, detail)
+        })?;
+        merge_json(&mut merged, value);
+    }
+    json_value_to_value(merged, conversion).map_err(|e| e.to_string())
+}
@@ -0,0 +1,182 @@
+use std::fmt::Write;
+use std::time::SystemTime;
+
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, SecondsFormat, Utc};
+use minijinja::value::{Kwargs, ValueKind};
+use minijinja::{Environment, Error, ErrorKind, Value};
+
+const DEFAULT_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// A timestamp parsed from a template value.
+enum Timestamp {
+    Zoned(DateTime<FixedOffset>),
+    Naive(NaiveDateTime),
+}
+
+fn invalid_timestamp(value: &Value) -> Error {
+    Error::new(
+        ErrorKind::InvalidOperation,
+        format!(
+            "datetimeformat expects an ISO 8601 string or a Unix timestamp, got {}",
+            value
+        ),
+    )
+}
+
+/// Parses an RFC 3339 / ISO 8601 string or Unix epoch seconds.
+fn parse_timestamp(value: &Value) -> Result<Timestamp, Error> {
+    match value.kind() {
+        ValueKind::Number => {
+            let dt = if let Ok(secs) = i64::try_from(value.clone()) {
+                DateTime::from_timestamp(secs, 0)
+            } else {
+                let secs = f64::try_from(value.clone()).map_err(|_| invalid_timestamp(value))?;
+                DateTime::from_timestamp_millis((secs * 1000.0).round() as i64)
+            };
+            dt.map(|dt| Timestamp::Zoned(dt.fixed_offset()))
+                .ok_or_else(|| invalid_timestamp(value))
+        }
+        ValueKind::String => {
+            let s = value.as_str().unwrap_or_default().trim();
+            if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+                return Ok(Timestamp::Zoned(dt));
+            }
+            if let Ok(dt) = DateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S%.f%:z") {
+                return Ok(Timestamp::Zoned(dt));
+            }
+            for format in ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"] {
+                if let Ok(dt) = NaiveDateTime::parse_from_str(s, format) {
+                    return Ok(Timestamp::Naive(dt));
+                }
+            }
+            if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+                return Ok(Timestamp::Naive(date.and_time(Default::default())));
+            }
+            Err(invalid_timestamp(value))
+        }
+        _ => Err(invalid_timestamp(value)),
+    }
+}
+
+/// Parses the fixed time `now()` returns instead of the system clock.
+///
+/// Accepts the same values as `datetimeformat`, timestamps without an offset
+/// are taken as UTC.
+pub(crate) fn parse_frozen_now(s: &str) -> Result<DateTime<Utc>, String> {
+    let value = match s.trim().parse::<i64>() {
+        Ok(secs) => Value::from(secs),
+        Err(_) => Value::from(s),
+    };
+    match parse_timestamp(&value) {
+        Ok(Timestamp::Zoned(dt)) => Ok(dt.with_timezone(&Utc)),
+        Ok(Timestamp::Naive(dt)) => Ok(dt.and_utc()),
+        Err(_) => Err(format!(
+            "Invalid now value '{}': expected an ISO 8601 timestamp or Unix epoch seconds",
+            s
+        )),
+    }
+}
+
+/// Parses `UTC`, `Z` or an offset such as `+02:00`, `-0530` or `+02`.
+fn parse_offset(tz: &str) -> Option<FixedOffset> {
+    if tz.eq_ignore_ascii_case("utc") || tz.eq_ignore_ascii_case("z") {
+        return FixedOffset::east_opt(0);
+    }
+    let (sign, rest) = match tz.as_bytes().first()? {
+        b'+' => (1, &tz[1..]),
+        b'-' => (-1, &tz[1..]),
+        _ => return None,
+    };
+    let (hours, minutes) = match rest.split_once(':') {
+        Some((hours, minutes)) => (hours, minutes),
+        None if rest.len() == 4 => rest.split_at(2),
+        None => (rest, "0"),
+    };
+    if hours.is_empty() || hours.len() > 2 || minutes.len() > 2 {
+        return None;
+    }
+    let hours: i32 = hours.parse().ok()?;
+    let minutes: i32 = minutes.parse().ok()?;
+    if minutes >= 60 {
+        return None;
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+/// Registers the `now()` global function.
+///
+/// With `frozen` set `now()` always returns that time, which keeps renders
+/// reproducible, otherwise it reads the system clock.
+pub(crate) fn register_now(env: &mut Environment<'static>, frozen: Option<DateTime<Utc>>) {
+    env.add_function(
+        "now",
+        move |format: Option<String>, tz: Option<String>, kwargs: Kwargs| {
+            now(frozen, format, tz, kwargs)
+        },
+    );
+}
+
+/// Returns the current time, e.g. `{{ now() }}` or `{{ now('%Y-%m-%d', '+02:00') }}`.
+///
+/// The format and the offset may also be passed as `format` and `tz` keyword
+/// arguments.  Without a format the time is returned as RFC 3339 with second
+/// precision, without an offset it is in UTC.  Named time zones other than
+/// UTC are not supported.
+fn now(
+    frozen: Option<DateTime<Utc>>,
+    format: Option<String>,
+    tz: Option<String>,
+    kwargs: Kwargs,
+) -> Result<String, Error> {
+    let format = kwargs.get::<Option<String>>("format")?.or(format);
+    let tz = kwargs.get::<Option<String>>("tz")?.or(tz);
+    kwargs.assert_all_used()?;
+
+    let utc = frozen.unwrap_or_else(|| DateTime::<Utc>::from(SystemTime::now()));
+    let offset = match tz.as_deref() {
+        Some(tz) => parse_offset(tz.trim()).ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidOperation,
+                format!(
+                    "now got an unsupported time zone '{}', use UTC or an offset such as +02:00",
+                    tz
+                ),
+            )
+        })?,
+        None => FixedOffset::east_opt(0).unwrap(),
+    };
+    let dt = utc.with_timezone(&offset);
+
+    let Some(format) = format else {
+        return Ok(dt.to_rfc3339_opts(SecondsFormat::Secs, true));
+    };
+    let mut rv = String::new();
+    write!(rv, "{}", dt.format(&format)).map_err(|_| {
+        Error::new(
+            ErrorKind::InvalidOperation,
+            format!("now got an invalid format string '{}'", format),
+        )
+    })?;
+    Ok(rv)
+}
+
+/// Formats a timestamp with a strftime-style pattern.
+///
+/// Accepts RFC 3339 / ISO 8601 strings and Unix epoch seconds, e.g.
+/// `{{ ts | datetimeformat('%Y-%m-%d') }}`.  Timestamps with an offset are
+/// formatted in that offset, epoch values in UTC.
+pub(crate) fn datetimeformat(value: Value, format: Option<String>) -> Result<String, Error> {
+    let format = format.as_deref().unwrap_or(DEFAULT_FORMAT);
+    let mut rv = String::new();
+    let written = match parse_timestamp(&value)? {
+        Timestamp::Zoned(dt) => write!(rv, "{}", dt.format(format)),
+        Timestamp::Naive(dt) => write!(rv, "{}", dt.format(format)),
+    };
+    written.map_err(|_| {
+        Error::new(
+            ErrorKind::InvalidOperation,
+            format!("datetimeformat got an invalid format string '{}'", format),
+        )
+    })?;
+    Ok(rv)
+}
@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+use std::ffi::{c_char, c_void};
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use minijinja::value::{Object, ObjectRepr};
+use minijinja::{Error, Value};
+
+use crate::callback::{invoke_resolver_callback, ResolverCallback, UserData};
+use crate::error::MJ_ERROR_INVALID_ARGUMENT;
+use crate::{error_result, ok_result, render_result, RenderOptions, ResultCString};
+
+/// A root context that asks a host callback for each variable on first use.
+///
+/// Resolved values, including undefined ones, are remembered for the rest of
+/// the render so the callback sees every name at most once.  Lookups cannot
+/// fail, so the first error of the callback is kept and reported once the
+/// render is done.
+struct DynamicContext {
+    callback: ResolverCallback,
+    user_data: UserData,
+    resolved: Mutex<HashMap<String, Option<Value>>>,
+    error: Mutex<Option<Error>>,
+}
+
+impl fmt::Debug for DynamicContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DynamicContext").finish_non_exhaustive()
+    }
+}
+
+impl Object for DynamicContext {
+    fn repr(self: &Arc<Self>) -> ObjectRepr {
+        ObjectRepr::Map
+    }
+
+    fn get_value(self: &Arc<Self>, key: &Value) -> Option<Value> {
+        let name = key.as_str()?;
+        if let Some(value) = self.lock_resolved().get(name) {
+            return value.clone();
+        }
+
+        let value = match invoke_resolver_callback(name, self.callback, self.user_data) {
+            Ok(value) => value,
+            Err(err) => {
+                let mut error = self.error.lock().unwrap_or_else(PoisonError::into_inner);
+                error.get_or_insert(err);
+                None
+            }
+        };
+        self.lock_resolved().insert(name.to_owned(), value.clone());
+        value
+    }
+}
+
+impl DynamicContext {
+    fn lock_resolved(&self) -> MutexGuard<'_, HashMap<String, Option<Value>>> {
+        self.resolved.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Renders a Minijinja template, resolving variables through a callback.
+///
+/// Instead of a context document the template looks its variables up on
+/// demand, so only the variables it actually uses are produced by the host.
+/// This suits wide rows of which a template only uses a few columns.  The
+/// template is rendered with the default options of `render_template`.
+///
+/// # Arguments
+/// * `template_source` - The template content as a string.
+/// * `resolver_callback` - Called with the name of every variable the template
+///   reads that is not set by the template itself, including globals such as
+///   `range`.  Leaving the result unset makes the variable undefined, and
+///   globals then resolve as usual.  The callback is invoked on the calling
+///   thread before this function returns, at most once per name.
+/// * `user_data` - Passed to every call of `resolver_callback`.
+///
+/// # Returns
+/// * The rendered output, or the error message if rendering fails or the
+///   callback reported an error or an invalid value for any variable.
+///
+/// # Safety
+/// `template_source` must be valid for `template_source_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn render_template_dynamic(
+    template_source: *const c_char,
+    template_source_len: usize,
+    resolver_callback: Option<ResolverCallback>,
+    user_data: *mut c_void,
+) -> ResultCString {
+    let Some(resolver_callback) = resolver_callback else {
+        return error_result(
+            MJ_ERROR_INVALID_ARGUMENT,
+            "Resolver callback is null".to_string(),
+        );
+    };
+    let template_str = make_str!(template_source, template_source_len, "template source");
+
+    let dynamic = Arc::new(DynamicContext {
+        callback: resolver_callback,
+        user_data: UserData(user_data),
+        resolved: Mutex::new(HashMap::new()),
+        error: Mutex::new(None),
+    });
+    let ctx = Value::from_dyn_object(dynamic.clone());
+
+    let rendered = RenderOptions::default().render(template_str, &ctx);
+    if let Some(err) = dynamic.error.lock().unwrap_or_else(PoisonError::into_inner).take() {
+        return render_result(Err(err));
+    }
+    match rendered {
+        Ok(output) => ok_result(output),
+        Err(err) => error_result(err.code(), err.to_string()),
+    }
+}
@@ -0,0 +1,1507 @@
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::{c_char, c_void};
+use std::io;
+use std::sync::{Arc, Mutex, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use minijinja::value::{Rest, ValueKind};
+use minijinja::{
+    default_auto_escape_callback, machinery, Environment, Error, ErrorKind, State, Value,
+};
+
+use crate::builder::ContextHandle;
+use crate::callback::{
+    invoke_escape_callback, invoke_json_callback, invoke_loader_callback, invoke_method_callback,
+    invoke_state_filter_callback, EscapeCallback, JsonCallback, LoaderCallback, MethodCallback,
+    StateFilterCallback, UserData,
+};
+use crate::error::{
+    error_code, set_last_error_code, MJ_ERROR_INVALID_ARGUMENT, MJ_ERROR_INVALID_CONTEXT,
+    MJ_ERROR_SERIALIZATION, MJ_ERROR_TEMPLATE_NOT_FOUND, MJ_ERROR_UNKNOWN_CALLABLE, MJ_OK,
+};
+use crate::config::syntax_options_from_json;
+use crate::datetime::{parse_frozen_now, register_now};
+use crate::formatter::{auto_escape_name, install_formatter, AutoEscapeCallback, FloatFormat};
+use crate::os_env::register_env;
+use crate::random::register_random;
+use crate::syntax::SyntaxOptions;
+use crate::{
+    bundle, error_result, json_to_value, ok_result, render_error_message, render_result,
+    slice_from_raw, str_from_raw, AutoescapeMode, Conversion, FfiResult, ResultBytes,
+    ResultCString,
+};
+
+/// The recursion limit MiniJinja uses by default.
+const DEFAULT_RECURSION_LIMIT: usize = 500;
+
+/// An opaque handle owning a MiniJinja `Environment` across FFI calls.
+///
+/// Handles are created with `mj_env_create` and must be released with
+/// `mj_env_destroy`.
+///
+/// A handle may be shared between threads.  Functions taking a `const`
+/// handle only read the environment and run concurrently with each other,
+/// functions taking a mutable handle wait for running renders to finish and
+/// have exclusive access while they modify it.  Callbacks invoked during a
+/// render must therefore not modify the handle they were invoked from.
+pub struct EnvHandle {
+    env: RwLock<Environment<'static>>,
+    unknown_methods: Mutex<UnknownMethods>,
+    /// The syntax set with `mj_env_set_syntax`, which MiniJinja cannot report.
+    syntax: Mutex<SyntaxOptions>,
+    /// The auto escape callback installed on the environment, which MiniJinja
+    /// cannot report.
+    auto_escape: Mutex<AutoEscapeCallback>,
+}
+
+/// The sources consulted for methods MiniJinja does not know.
+#[derive(Clone, Default)]
+struct UnknownMethods {
+    pycompat: bool,
+    callback: Option<(MethodCallback, UserData)>,
+}
+
+impl EnvHandle {
+    /// Wraps `env` into a newly allocated handle.
+    fn new(mut env: Environment<'static>) -> *mut EnvHandle {
+        install_formatter(&mut env, FloatFormat::default(), None, None);
+        env.set_debug(false);
+        Box::into_raw(Box::new(EnvHandle {
+            env: RwLock::new(env),
+            unknown_methods: Mutex::new(UnknownMethods::default()),
+            syntax: Mutex::new(SyntaxOptions::default()),
+            auto_escape: Mutex::new(Arc::new(default_auto_escape_callback)),
+        }))
+    }
+
+    /// Locks the environment for rendering.
+    fn read(&self) -> RwLockReadGuard<'_, Environment<'static>> {
+        // Panics do not unwind across the FFI boundary, so there is no
+        // partially modified environment to guard against.
+        self.env.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Locks the environment for modification.
+    fn write(&self) -> RwLockWriteGuard<'_, Environment<'static>> {
+        self.env.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Returns the auto escape callback of the environment.
+    fn auto_escape(&self) -> AutoEscapeCallback {
+        self.auto_escape.lock().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    /// Installs `callback` as the auto escape callback of the environment.
+    fn set_auto_escape(&self, callback: AutoEscapeCallback) {
+        let mut env = self.write();
+        let installed = callback.clone();
+        env.set_auto_escape_callback(move |name| installed(name));
+        *self.auto_escape.lock().unwrap_or_else(PoisonError::into_inner) = callback;
+    }
+
+    /// Updates how unknown methods are resolved and installs the result.
+    ///
+    /// The host callback is asked first; methods it does not answer fall back
+    /// to the Python compatible methods if those are enabled.
+    fn update_unknown_methods(&self, update: impl FnOnce(&mut UnknownMethods)) {
+        let mut env = self.write();
+        let mut methods = self
+            .unknown_methods
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        update(&mut methods);
+
+        let pycompat = methods.pycompat;
+        let callback = methods.callback;
+        env.set_unknown_method_callback(move |state, value, method, args| {
+            if let Some((callback, user_data)) = callback {
+                if let Some(rv) = invoke_method_callback(method, callback, user_data, value, args)? {
+                    return Ok(rv);
+                }
+            }
+            if pycompat {
+                return minijinja_contrib::pycompat::unknown_method_callback(
+                    state, value, method, args,
+                );
+            }
+            Err(Error::from(ErrorKind::UnknownMethod))
+        });
+    }
+}
+
+/// Creates a new environment handle.
+///
+/// # Returns
+/// * A pointer to a newly allocated handle which must be freed with `mj_env_destroy`.
+#[no_mangle]
+pub extern "C" fn mj_env_create() -> *mut EnvHandle {
+    let mut env = Environment::new();
+    crate::filters::register_filters(&mut env);
+    register_now(&mut env, None);
+    register_random(&mut env, None);
+    register_env(&mut env, false);
+    EnvHandle::new(env)
+}
+
+/// Creates an independent copy of an environment handle.
+///
+/// The copy starts out with the templates, filters, tests, globals and
+/// settings of `handle` at the time of the call.  Changes made to either
+/// handle afterwards do not affect the other, so a configured base handle can
+/// be specialized per request, e.g. with request scoped globals.  Compiled
+/// templates are shared rather than copied, which keeps cloning cheap.
+/// Callbacks registered on `handle` are registered on the copy as well and
+/// must stay callable for its lifetime too.
+///
+/// # Returns
+/// * A pointer to a newly allocated handle which must be freed with
+///   `mj_env_destroy`, or null if `handle` is null.
+///
+/// # Safety
+/// `handle` must be a valid handle or null.
+#[no_mangle]
+pub unsafe extern "C" fn mj_env_clone(handle: *const EnvHandle) -> *mut EnvHandle {
+    let Some(handle) = handle.as_ref() else {
+        return std::ptr::null_mut();
+    };
+    let env = handle.read().clone();
+    let unknown_methods = handle
+        .unknown_methods
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone();
+    let syntax = handle.syntax.lock().unwrap_or_else(PoisonError::into_inner).clone();
+    Box::into_raw(Box::new(EnvHandle {
+        env: RwLock::new(env),
+        unknown_methods: Mutex::new(unknown_methods),
+        syntax: Mutex::new(syntax),
+        auto_escape: Mutex::new(handle.auto_escape()),
+    }))
+}
+
+/// Creates a new environment handle without any filters, tests or functions.
+///
+/// This is the starting point of a restricted dialect: only the filters,
+/// tests and functions added with `mj_env_add_filter`, `mj_env_add_test` and
+/// `mj_env_add_function` are available, using any other fails with an unknown
+/// filter, test or function error.  Templates, control structures and
+/// operators work as usual.
+///
+/// # Returns
+/// * A pointer to a newly allocated handle which must be freed with `mj_env_destroy`.
+#[no_mangle]
+pub extern "C" fn mj_env_create_empty() -> *mut EnvHandle {
+    let mut env = Environment::empty();
+    env.set_auto_escape_callback(default_auto_escape_callback);
+    EnvHandle::new(env)
+}
+
+/// Destroys an environment handle created by `mj_env_create`.
+///
+/// # Safety
+/// The handle must have been created by `mj_env_create` and must not be used
+/// after this call. Passing a null pointer is a no-op.
+#[no_mangle]
+pub unsafe extern "C" fn mj_env_destroy(handle: *mut EnvHandle) {
+    if !handle.is_null() {
+        drop(Box::from_raw(handle));
+    }
+}
+
+/// Renders a template string with the environment of the handle.
+///
+/// # Arguments
+/// * `handle` - The environment handle.
+/// * `template_source` - The template content as a string.
+/// * `json_context` - JSON string containing context variables.
+///
+/// # Safety
+/// `handle` must be a valid handle and all string pointers must be valid for
+/// the given lengths.
+#[no_mangle]
+pub unsafe extern "C" fn mj_env_render(
+    handle: *const EnvHandle,
+    template_source: *const c_char,
+    template_source_len: usize,
+    json_context: *const c_char,
+    json_context_len: usize,
+) -> ResultCString {
+    let Some(handle) = handle.as_ref() else {
+        return error_result(MJ_ERROR_INVALID_ARGUMENT, "Environment handle is null".to_string());
+    };
+    let template_str = make_str!(template_source, template_source_len, "template source");
+    let json_str = make_str!(json_context, json_context_len, "JSON context", MJ_ERROR_INVALID_CONTEXT);
+
+    let ctx = match json_to_value(json_str, Conversion::default()) {
+        Ok(c) => c,
+        Err(e) => return error_result(MJ_ERROR_INVALID_CONTEXT, e.to_string()),
+    };
+
+    render_result(handle.read().render_str(template_str, &ctx))
+}
+
+/// Renders a template string with a context built with `mj_ctx_new`.
+///
+/// Like `mj_env_render`, but the context is only read, so it may be rendered
+/// again afterwards.
+///
+/// # Safety
+/// `handle` must be a valid handle, `template_source` must be valid for
+/// `template_source_len` bytes and `ctx` must be a valid context.
+#[no_mangle]
+pub unsafe extern "C" fn mj_env_render_ctx(
+    handle: *const EnvHandle,
+    template_source: *const c_char,
+    template_source_len: usize,
+    ctx: *const ContextHandle,
+) -> ResultCString {
+    let Some(handle) = handle.as_ref() else {
+        return error_result(MJ_ERROR_INVALID_ARGUMENT, "Environment handle is null".to_string());
+    };
+    let template_str = make_str!(template_source, template_source_len, "template source");
+    let Some(ctx) = ctx.as_ref() else {
+        return error_result(MJ_ERROR_INVALID_ARGUMENT, "Context is null".to_string());
+    };
+
+    render_result(handle.read().render_str(template_str, ctx.to_value()))
+}
+
+/// Compiles a template and registers it with the handle under `name`.
+///
+/// Registered templates are found by name from `{% include %}`, `{% import %}`,
+/// `{% from %}` and `{% extends %}` in any template of the handle, without a
+/// loader or files on disk.  They may be registered in any order since names are
+/// only resolved when rendering, and take precedence over templates of a loader
+/// set with `mj_env_set_loader`.  Registering a template under an existing name
+/// replaces the prior template.
+///
+/// # Returns
+/// * `Ok` with an empty string, or `Err` with the syntax error if the template
+///   fails to compile.
+///
+/// # Safety
+/// `handle` must be a valid handle and all string pointers must be valid for
+/// the given lengths.
+#[no_mangle]
+pub unsafe extern "C" fn mj_env_add_template(
+    handle: *mut EnvHandle,
+    name: *const c_char,
+    name_len: usize,
+    template_source: *const c_char,
+    template_source_len: usize,
+) -> ResultCString {
+    let Some(handle) = handle.as_ref() else {
+        return error_result(MJ_ERROR_INVALID_ARGUMENT, "Environment handle is null".to_string());
+    };
+    let name_str = make_str!(name, name_len, "template name");
+    let template_str = make_str!(template_source, template_source_len, "template source");
+
+    let result = handle
+        .write()
+        .add_template_owned(name_str.to_owned(), template_str.to_owned())
+        .map(|_| String::new());
+    render_result(result)
+}
+
+/// Checks that a template compiles in an environment and wraps its source
+/// into a bundle that can be registered with `mj_env_add_bundle`.
+///
+/// The template is checked with the syntax and whitespace options of the
+/// handle, so a build step can reject invalid templates ahead of time.  A
+/// bundle is not a compiled form of the template: MiniJinja does not expose
+/// its compiled instructions, so the bundle holds the template source in a
+/// versioned layout and registering it compiles the template again.
+/// Registering bundles is therefore not faster than registering the sources
+/// with `mj_env_add_template`.
+///
+/// # Returns
+/// * `ResultBytes` holding the bundle, or the compile error if the template is
+///   invalid.  The result must be freed with `free_result_bytes`.
+///
+/// # Safety
+/// `handle` must be a valid handle and `template_source` must be valid for
+/// `template_source_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn mj_env_bundle_template(
+    handle: *const EnvHandle,
+    template_source: *const c_char,
+    template_source_len: usize,
+) -> ResultBytes {
+    let Some(handle) = handle.as_ref() else {
+        return ResultBytes::from_error(
+            MJ_ERROR_INVALID_ARGUMENT,
+            "Environment handle is null".to_string(),
+        );
+    };
+    let template_str = make_str!(template_source, template_source_len, "template source");
+
+    if let Err(err) = handle.read().template_from_str(template_str) {
+        return ResultBytes::from_error(error_code(&err), render_error_message(&err));
+    }
+
+    set_last_error_code(MJ_OK);
+    ResultBytes::new(true, bundle::encode(template_str))
+}
+
+/// Registers a template from a bundle created by `mj_env_bundle_template`.
+///
+/// This behaves like `mj_env_add_template` with the source the bundle was
+/// created from, which is compiled again.
+///
+/// # Returns
+/// * `Ok` with an empty string, or `Err` if the bytes are not a template
+///   source bundle or the template fails to compile.
+///
+/// # Safety
+/// `handle` must be a valid handle, `name` must be valid for `name_len` bytes
+/// and `bytes` must be valid for `bytes_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn mj_env_add_bundle(
+    handle: *mut EnvHandle,
+    name: *const c_char,
+    name_len: usize,
+    bytes: *const u8,
+    bytes_len: usize,
+) -> ResultCString {
+    let Some(handle) = handle.as_ref() else {
+        return error_result(MJ_ERROR_INVALID_ARGUMENT, "Environment handle is null".to_string());
+    };
+    let name_str = make_str!(name, name_len, "template name");
+    let Some(bytes) = slice_from_raw(bytes, bytes_len) else {
+        return error_result(
+            MJ_ERROR_INVALID_ARGUMENT,
+            "Template source bundle is null".to_string(),
+        );
+    };
+    let template_str = match bundle::decode(bytes) {
+        Ok(source) => source,
+        Err(msg) => return error_result(MJ_ERROR_INVALID_ARGUMENT, msg),
+    };
+
+    let result = handle
+        .write()
+        .add_template_owned(name_str.to_owned(), template_str.to_owned())
+        .map(|_| String::new());
+    render_result(result)
+}
+
+/// Registers a batch of templates given as parallel name and source arrays.
+///
+/// The batch is all or nothing: every template is compiled before any is
+/// registered, and if one fails the handle is left unchanged.  Templates of
+/// the batch may refer to each other, e.g. with `{% extends %}`, as references
+/// are resolved when rendering.  A name that appears more than once keeps
+/// the last source.
+///
+/// # Returns
+/// * `Ok` with an empty string, or `Err` for the first template that is not
+///   valid UTF-8 or fails to compile, naming it in the message.
+///
+/// # Safety
+/// `handle` must be a valid handle, `names`, `name_lens`, `sources` and
+/// `source_lens` must each point to `count` elements and every name and source
+/// must be valid for its length.
+#[no_mangle]
+pub unsafe extern "C" fn mj_env_add_templates(
+    handle: *mut EnvHandle,
+    names: *const *const c_char,
+    name_lens: *const usize,
+    sources: *const *const c_char,
+    source_lens: *const usize,
+    count: usize,
+) -> ResultCString {
+    let Some(handle) = handle.as_ref() else {
+        return error_result(MJ_ERROR_INVALID_ARGUMENT, "Environment handle is null".to_string());
+    };
+    let (Some(names), Some(name_lens), Some(sources), Some(source_lens)) = (
+        slice_from_raw(names, count),
+        slice_from_raw(name_lens, count),
+        slice_from_raw(sources, count),
+        slice_from_raw(source_lens, count),
+    ) else {
+        return error_result(MJ_ERROR_INVALID_ARGUMENT, "Template arrays are null".to_string());
+    };
+
+    let mut env = handle.write();
+    // Compile into a copy so a failure leaves the handle untouched.
+    let mut batch = env.clone();
+    for idx in 0..count {
+        let Ok(name_str) = str_from_raw(names[idx], name_lens[idx]) else {
+            return error_result(
+                MJ_ERROR_INVALID_ARGUMENT,
+                format!("Name of template {} is null or not valid UTF-8", idx),
+            );
+        };
+        let Ok(template_str) = str_from_raw(sources[idx], source_lens[idx]) else {
+            return error_result(
+                MJ_ERROR_INVALID_ARGUMENT,
+                format!("Source of template '{}' is null or not valid UTF-8", name_str),
+            );
+        };
+        if let Err(err) = batch.add_template_owned(name_str.to_owned(), template_str.to_owned()) {
+            return error_result(
+                error_code(&err),
+                format!("Template '{}' failed to compile: {}", name_str, render_error_message(&err)),
+            );
+        }
+    }
+    *env = batch;
+    ok_result(String::new())
+}
+
+/// Renders a template previously registered with `mj_env_add_template`.
+///
+/// # Arguments
+/// * `handle` - The environment handle.
+/// * `name` - The name the template was registered under.
+/// * `json_context` - JSON string containing context variables.
+///
+/// # Safety
+/// `handle` must be a valid handle and all string pointers must be valid for
+/// the given lengths.
+#[no_mangle]
+pub unsafe extern "C" fn mj_env_render_named(
+    handle: *const EnvHandle,
+    name: *const c_char,
+    name_len: usize,
+    json_context: *const c_char,
+    json_context_len: usize,
+) -> ResultCString {
+    let Some(handle) = handle.as_ref() else {
+        return error_result(MJ_ERROR_INVALID_ARGUMENT, "Environment handle is null".to_string());
+    };
+    let name_str = make_str!(name, name_len, "template name");
+    let json_str = make_str!(json_context, json_context_len, "JSON context", MJ_ERROR_INVALID_CONTEXT);
+
+    let env = handle.read();
+    let tmpl = match env.get_template(name_str) {
+        Ok(tmpl) => tmpl,
+        Err(err) if err.kind() == ErrorKind::TemplateNotFound => {
+            return error_result(
+                MJ_ERROR_TEMPLATE_NOT_FOUND,
+                format!("template '{}' not found", name_str),
+            );
+        }
+        Err(err) => return render_result(Err(err)),
+    };
+
+    let ctx = match json_to_value(json_str, Conversion::default()) {
+        Ok(c) => c,
+        Err(e) => return error_result(MJ_ERROR_INVALID_CONTEXT, e.to_string()),
+    };
+
+    render_result(tmpl.render(&ctx))
+}
+
+/// Renders a single block of a template registered with `mj_env_add_template`.
+///
+/// The template is evaluated with the context first, so top-level `{% set %}`
+/// and imports are visible to the block, and with `{% extends %}` the block
+/// as overridden by the template is rendered.  This renders e.g. only the
+/// `subject` block of an email template whose body is the rest.
+///
+/// # Arguments
+/// * `handle` - The environment handle.
+/// * `name` - The name the template was registered under.
+/// * `block` - The name of the block to render.
+/// * `json_context` - JSON string containing context variables.
+///
+/// # Returns
+/// * `Ok` containing the rendered block, or `Err` with `MJ_ERROR_UNKNOWN_CALLABLE`
+///   if the template has no such block.
+///
+/// # Safety
+/// `handle` must be a valid handle and all string pointers must be valid for
+/// the given lengths.
+#[no_mangle]
+pub unsafe extern "C" fn mj_env_render_block(
+    handle: *const EnvHandle,
+    name: *const c_char,
+    name_len: usize,
+    block: *const c_char,
+    block_len: usize,
+    json_context: *const c_char,
+    json_context_len: usize,
+) -> ResultCString {
+    let Some(handle) = handle.as_ref() else {
+        return error_result(MJ_ERROR_INVALID_ARGUMENT, "Environment handle is null".to_string());
+    };
+    let name_str = make_str!(name, name_len, "template name");
+    let block_str = make_str!(block, block_len, "block name");
+    let json_str = make_str!(json_context, json_context_len, "JSON context", MJ_ERROR_INVALID_CONTEXT);
+
+    let env = handle.read();
+    let tmpl = match env.get_template(name_str) {
+        Ok(tmpl) => tmpl,
+        Err(err) if err.kind() == ErrorKind::TemplateNotFound => {
+            return error_result(
+                MJ_ERROR_TEMPLATE_NOT_FOUND,
+                format!("template '{}' not found", name_str),
+            );
+        }
+        Err(err) => return render_result(Err(err)),
+    };
+
+    let ctx = match json_to_value(json_str, Conversion::default()) {
+        Ok(c) => c,
+        Err(e) => return error_result(MJ_ERROR_INVALID_CONTEXT, e.to_string()),
+    };
+
+    let mut state = match tmpl.render_to_write(&ctx, io::sink()) {
+        Ok(state) => state,
+        Err(err) => return render_result(Err(err)),
+    };
+    match state.render_block(block_str) {
+        Err(err) if err.kind() == ErrorKind::UnknownBlock => error_result(
+            MJ_ERROR_UNKNOWN_CALLABLE,
+            format!("block '{}' not found in template '{}'", block_str, name_str),
+        ),
+        result => render_result(result),
+    }
+}
+
+/// Renders every block of a template registered with `mj_env_add_template`.
+///
+/// The template is evaluated once and each block is rendered from the same
+/// state, so an email template with a `subject` and a `body` block yields
+/// `{"subject": "...", "body": "..."}` in one call.  Nothing outside the
+/// blocks is rendered.
+///
+/// Only the blocks the template itself defines are returned, nested blocks
+/// included.  With `{% extends %}` these are its overrides, rendered as the
+/// override with `{{ super() }}` reaching the parent; blocks only the parent
+/// defines are not returned but can be rendered with `mj_env_render_block`.
+///
+/// # Arguments
+/// * `handle` - The environment handle.
+/// * `name` - The name the template was registered under.
+/// * `json_context` - JSON string containing context variables.
+///
+/// # Returns
+/// * `Ok` containing a JSON object mapping block names to their output, or
+///   `Err` if evaluating the template or rendering a block fails.
+///
+/// # Safety
+/// `handle` must be a valid handle and all string pointers must be valid for
+/// the given lengths.
+#[no_mangle]
+pub unsafe extern "C" fn mj_env_render_blocks(
+    handle: *const EnvHandle,
+    name: *const c_char,
+    name_len: usize,
+    json_context: *const c_char,
+    json_context_len: usize,
+) -> ResultCString {
+    let Some(handle) = handle.as_ref() else {
+        return error_result(MJ_ERROR_INVALID_ARGUMENT, "Environment handle is null".to_string());
+    };
+    let name_str = make_str!(name, name_len, "template name");
+    let json_str = make_str!(json_context, json_context_len, "JSON context", MJ_ERROR_INVALID_CONTEXT);
+
+    let env = handle.read();
+    let tmpl = match env.get_template(name_str) {
+        Ok(tmpl) => tmpl,
+        Err(err) if err.kind() == ErrorKind::TemplateNotFound => {
+            return error_result(
+                MJ_ERROR_TEMPLATE_NOT_FOUND,
+                format!("template '{}' not found", name_str),
+            );
+        }
+        Err(err) => return render_result(Err(err)),
+    };
+
+    let ctx = match json_to_value(json_str, Conversion::default()) {
+        Ok(c) => c,
+        Err(e) => return error_result(MJ_ERROR_INVALID_CONTEXT, e.to_string()),
+    };
+
+    let mut state = match tmpl.render_to_write(&ctx, io::sink()) {
+        Ok(state) => state,
+        Err(err) => return render_result(Err(err)),
+    };
+    let mut blocks = BTreeMap::new();
+    for &block in machinery::get_compiled_template(&tmpl).blocks.keys() {
+        match state.render_block(block) {
+            Ok(output) => blocks.insert(block, output),
+            Err(err) => return render_result(Err(err)),
+        };
+    }
+
+    match serde_json::to_string(&blocks) {
+        Ok(json) => ok_result(json),
+        Err(e) => error_result(
+            MJ_ERROR_SERIALIZATION,
+            format!("Failed to serialize the rendered blocks: {}", e),
+        ),
+    }
+}
+
+/// Removes a registered template from the handle.
+///
+/// # Returns
+/// * `true` if a template with that name was registered, `false` otherwise.
+///
+/// # Safety
+/// `handle` must be a valid handle or null and `name` must be valid for
+/// `name_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn mj_env_remove_template(
+    handle: *mut EnvHandle,
+    name: *const c_char,
+    name_len: usize,
+) -> bool {
+    let Some(handle) = handle.as_ref() else {
+        return false;
+    };
+    let Ok(name_str) = str_from_raw(name, name_len) else {
+        return false;
+    };
+
+    let mut env = handle.write();
+    let present = env.templates().any(|(n, _)| n == name_str);
+    if present {
+        env.remove_template(name_str);
+    }
+    present
+}
+
+/// Removes all registered templates from the handle.
+///
+/// # Safety
+/// `handle` must be a valid handle or null.
+#[no_mangle]
+pub unsafe extern "C" fn mj_env_clear_templates(handle: *mut EnvHandle) {
+    if let Some(handle) = handle.as_ref() {
+        handle.write().clear_templates();
+    }
+}
+
+/// Checks whether a template is registered with the handle.
+///
+/// # Safety
+/// `handle` must be a valid handle or null and `name` must be valid for
+/// `name_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn mj_env_template_exists(
+    handle: *const EnvHandle,
+    name: *const c_char,
+    name_len: usize,
+) -> bool {
+    let Some(handle) = handle.as_ref() else {
+        return false;
+    };
+    let Ok(name_str) = str_from_raw(name, name_len) else {
+        return false;
+    };
+    handle.read().templates().any(|(n, _)| n == name_str)
+}
+
+/// Lists the names of all templates registered with the handle.
+///
+/// # Returns
+/// * `Ok` containing a JSON array of template names (`[]` if none are registered).
+///
+/// # Safety
+/// `handle` must be a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn mj_env_list_templates(handle: *const EnvHandle) -> ResultCString {
+    let Some(handle) = handle.as_ref() else {
+        return error_result(MJ_ERROR_INVALID_ARGUMENT, "Environment handle is null".to_string());
+    };
+    let env = handle.read();
+    let names: Vec<&str> = env.templates().map(|(name, _)| name).collect();
+    match serde_json::to_string(&names) {
+        Ok(json) => ok_result(json),
+        Err(e) => error_result(
+            MJ_ERROR_SERIALIZATION,
+            format!("Failed to serialize template names: {}", e),
+        ),
+    }
+}
+
+/// Sets the maximum recursion depth for renders with the handle.
+///
+/// `0` restores the MiniJinja default of 500, values above that are capped.
+///
+/// # Safety
+/// `handle` must be a valid handle or null.
+#[no_mangle]
+pub unsafe extern "C" fn mj_env_set_recursion_limit(handle: *mut EnvHandle, recursion_limit: usize) {
+    if let Some(handle) = handle.as_ref() {
+        handle.write().set_recursion_limit(if recursion_limit == 0 {
+            DEFAULT_RECURSION_LIMIT
+        } else {
+            recursion_limit
+        });
+    }
+}
+
+/// Enables or disables Python compatible methods for renders with the handle.
+///
+/// When enabled, templates can call the Python methods Jinja2 templates
+/// commonly use, such as `dict.items()`, `str.upper()` or `list.count()`.
+///
+/// # Safety
+/// `handle` must be a valid handle or null.
+#[no_mangle]
+pub unsafe extern "C" fn mj_env_set_pycompat(handle: *mut EnvHandle, enabled: bool) {
+    if let Some(handle) = handle.as_ref() {
+        handle.update_unknown_methods(|methods| methods.pycompat = enabled);
+    }
+}
+
+/// Sets how values are escaped in renders with the handle.
+///
+/// `mode` is one of `html`, `json` (for use inside JSON string literals),
+/// `none` or `auto`, which picks the escaping from the template name.  Handles
+/// start out in a mode like `auto` that does not escape `.json` templates.
+///
+/// The escaping of a template is fixed when it is compiled, so the mode
+/// applies to templates added or loaded afterwards and to `mj_env_render`.
+///
+/// # Returns
+/// * `Ok` with an empty string, or `Err` if the mode is not known.
+///
+/// # Safety
+/// `handle` must be a valid handle and `mode` must be valid for `mode_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn mj_env_set_autoescape_mode(
+    handle: *mut EnvHandle,
+    mode: *const c_char,
+    mode_len: usize,
+) -> ResultCString {
+    let Some(handle) = handle.as_ref() else {
+        return error_result(MJ_ERROR_INVALID_ARGUMENT, "Environment handle is null".to_string());
+    };
+    let mode_str = make_str!(mode, mode_len, "autoescape mode");
+    match AutoescapeMode::parse(mode_str) {
+        Ok(mode) => {
+            handle.set_auto_escape(mode.callback(&[]));
+            ok_result(String::new())
+        }
+        Err(msg) => error_result(MJ_ERROR_INVALID_ARGUMENT, msg),
+    }
+}
+
+/// Sets a host callback that escapes values in renders with the handle.
+///
+/// Whenever auto escaping is active, whatever the mode, the callback receives
+/// the string form of each printed value and its result is written instead,
+/// replacing the built-in escaping.  Values marked safe, e.g. with the `safe`
+/// filter, are printed unchanged without calling it.  Which templates escape
+/// is still decided by `mj_env_set_autoescape_mode` and `{% autoescape %}`.
+/// Passing a null callback restores the built-in escaping.
+///
+/// # Safety
+/// `handle` must be a valid handle or null and `callback` must stay callable
+/// with `user_data` for the lifetime of the handle.
+#[no_mangle]
+pub unsafe extern "C" fn mj_env_set_escape_callback(
+    handle: *mut EnvHandle,
+    callback: Option<EscapeCallback>,
+    user_data: *mut c_void,
+) {
+    let Some(handle) = handle.as_ref() else {
+        return;
+    };
+    let user_data = UserData(user_data);
+    let escaper = callback.map(|callback| {
+        Arc::new(move |value: &str| invoke_escape_callback(value, callback, user_data)) as _
+    });
+    install_formatter(&mut handle.write(), FloatFormat::default(), None, escaper);
+}
+
+/// Returns the auto escape mode the handle picks for a template name.
+///
+/// This asks the mode set with `mj_env_set_autoescape_mode` the way compiling
+/// a template of that name would, e.g. to check which templates an `auto`
+/// mode escapes.  A `{% autoescape %}` block in the template still overrides
+/// it and an escape callback only changes how values are escaped, not
+/// whether.
+///
+/// # Returns
+/// * `Ok` with `html`, `json`, `none` or the name of a custom mode.
+///
+/// # Safety
+/// `handle` must be a valid handle and `name` must be valid for `name_len`
+/// bytes.
+#[no_mangle]
+pub unsafe extern "C" fn mj_env_autoescape_for(
+    handle: *const EnvHandle,
+    name: *const c_char,
+    name_len: usize,
+) -> ResultCString {
+    let Some(handle) = handle.as_ref() else {
+        return error_result(MJ_ERROR_INVALID_ARGUMENT, "Environment handle is null".to_string());
+    };
+    let name_str = make_str!(name, name_len, "template name");
+    ok_result(auto_escape_name(handle.auto_escape()(name_str)).to_string())
+}
+
+/// Sets the template syntax of the handle.
+///
+/// `config_json` is a JSON object with the `block_delimiters`,
+/// `variable_delimiters` and `comment_delimiters` as `[start, end]` pairs and
+/// the `line_statement_prefix` and `line_comment_prefix`, named like the
+/// options of the SQL functions.  Missing keys keep the MiniJinja default, so
+/// `{}` restores the default syntax.  A render config is accepted as well, its
+/// other options are ignored, but unknown keys are rejected.  The syntax is
+/// validated once here instead of on every render.
+///
+/// Like the autoescape mode, the syntax applies to templates added or loaded
+/// afterwards and to `mj_env_render`.
+///
+/// # Returns
+/// * `Ok` with an empty string, or `Err` describing why the config is
+///   invalid, e.g. empty delimiters or two that start alike.  The syntax is
+///   only changed on success.
+///
+/// # Safety
+/// `handle` must be a valid handle and `config_json` must be valid for
+/// `config_json_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn mj_env_set_syntax(
+    handle: *mut EnvHandle,
+    config_json: *const c_char,
+    config_json_len: usize,
+) -> ResultCString {
+    let Some(handle) = handle.as_ref() else {
+        return error_result(MJ_ERROR_INVALID_ARGUMENT, "Environment handle is null".to_string());
+    };
+    let config_str = make_str!(config_json, config_json_len, "syntax config");
+    let syntax = match syntax_options_from_json(config_str) {
+        Ok(syntax) => syntax,
+        Err(msg) => return error_result(MJ_ERROR_INVALID_ARGUMENT, msg),
+    };
+    let config = match syntax.build() {
+        Ok(config) => config,
+        Err(msg) => return error_result(MJ_ERROR_INVALID_ARGUMENT, msg),
+    };
+    let mut env = handle.write();
+    env.set_syntax(config);
+    *handle.syntax.lock().unwrap_or_else(PoisonError::into_inner) = syntax;
+    ok_result(String::new())
+}
+
+/// Returns the template syntax of the handle.
+///
+/// # Returns
+/// * `Ok` with a JSON object in the format taken by `mj_env_set_syntax`,
+///   holding the effective delimiters and the line prefixes, which are `null`
+///   when disabled.
+///
+/// # Safety
+/// `handle` must be a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn mj_env_get_syntax(handle: *const EnvHandle) -> ResultCString {
+    let Some(handle) = handle.as_ref() else {
+        return error_result(MJ_ERROR_INVALID_ARGUMENT, "Environment handle is null".to_string());
+    };
+    let syntax = handle.syntax.lock().unwrap_or_else(PoisonError::into_inner);
+    ok_result(syntax.to_json().to_string())
+}
+
+/// Freezes the time `now()` returns in renders with the handle.
+///
+/// `now` is an ISO 8601 timestamp or Unix epoch seconds, timestamps without
+/// an offset are taken as UTC.  Passing null or an empty string makes `now()`
+/// read the system clock again.
+///
+/// # Returns
+/// * `Ok` with an empty string, or `Err` if the timestamp is invalid.
+///
+/// # Safety
+/// `handle` must be a valid handle and `now` must be null or valid for
+/// `now_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn mj_env_set_now(
+    handle: *mut EnvHandle,
+    now: *const c_char,
+    now_len: usize,
+) -> ResultCString {
+    let Some(handle) = handle.as_ref() else {
+        return error_result(MJ_ERROR_INVALID_ARGUMENT, "Environment handle is null".to_string());
+    };
+    let now_str = if now.is_null() {
+        ""
+    } else {
+        make_str!(now, now_len, "now")
+    };
+    let frozen = if now_str.is_empty() {
+        None
+    } else {
+        match parse_frozen_now(now_str) {
+            Ok(frozen) => Some(frozen),
+            Err(msg) => return error_result(MJ_ERROR_INVALID_ARGUMENT, msg),
+        }
+    };
+    register_now(&mut handle.write(), frozen);
+    ok_result(String::new())
+}
+
+/// Seeds `uuid()`, `random()` and `randrange()` in renders with the handle.
+///
+/// Every render starts its sequence from the seed, so rendering a template
+/// with the same context always produces the same values.  A negative seed
+/// makes every render pick a random seed again.  The generator is not
+/// suitable for secrets.
+///
+/// # Safety
+/// `handle` must be a valid handle or null.
+#[no_mangle]
+pub unsafe extern "C" fn mj_env_set_random_seed(handle: *mut EnvHandle, seed: i64) {
+    if let Some(handle) = handle.as_ref() {
+        register_random(&mut handle.write(), u64::try_from(seed).ok());
+    }
+}
+
+/// Enables or disables debug information in errors of renders with the handle.
+///
+/// With debug information error messages show the failing line of the
+/// template and the values of the variables in scope.  It is disabled by
+/// default, as error messages may be shown where the template source must
+/// not be.
+///
+/// # Safety
+/// `handle` must be a valid handle or null.
+#[no_mangle]
+pub unsafe extern "C" fn mj_env_set_debug(handle: *mut EnvHandle, enabled: bool) {
+    if let Some(handle) = handle.as_ref() {
+        handle.write().set_debug(enabled);
+    }
+}
+
+/// Allows or forbids `env()` to read environment variables in renders with
+/// the handle.
+///
+/// Access is disabled by default, `env()` then fails with an error.
+///
+/// # Safety
+/// `handle` must be a valid handle or null.
+#[no_mangle]
+pub unsafe extern "C" fn mj_env_set_allow_env(handle: *mut EnvHandle, allowed: bool) {
+    if let Some(handle) = handle.as_ref() {
+        register_env(&mut handle.write(), allowed);
+    }
+}
+
+/// Sets a host callback answering method calls MiniJinja does not know.
+///
+/// This lets values passed in through globals, functions or the context
+/// respond to methods such as `{{ account.balance_in('EUR') }}`.  The callback
+/// receives the value as JSON together with the method name and arguments.
+/// Leaving the result unset reports an unknown method.
+///
+/// Methods MiniJinja implements itself never reach the callback.  If Python
+/// compatible methods are enabled with `mj_env_set_pycompat`, the callback is
+/// asked first and only the methods it does not answer fall back to them.
+/// Passing a null callback removes it.
+///
+/// # Safety
+/// `handle` must be a valid handle or null and `callback` must stay callable
+/// with `user_data` for the lifetime of the handle.
+#[no_mangle]
+pub unsafe extern "C" fn mj_env_set_unknown_method_callback(
+    handle: *mut EnvHandle,
+    callback: Option<MethodCallback>,
+    user_data: *mut c_void,
+) {
+    if let Some(handle) = handle.as_ref() {
+        let callback = callback.map(|callback| (callback, UserData(user_data)));
+        handle.update_unknown_methods(|methods| methods.callback = callback);
+    }
+}
+
+/// Registers a filter implemented by a host callback.
+///
+/// The callback receives a JSON array holding the filtered value followed by
+/// the filter arguments and reports a JSON encoded result.  Errors reported by
+/// the callback fail the render.
+///
+/// # Returns
+/// * `Ok` with an empty string, or `Err` if the callback is null.
+///
+/// # Safety
+/// `handle` must be a valid handle, `name` must be valid for `name_len` bytes
+/// and `callback` must stay callable with `user_data` for the lifetime of the handle.
+#[no_mangle]
+pub unsafe extern "C" fn mj_env_add_filter(
+    handle: *mut EnvHandle,
+    name: *const c_char,
+    name_len: usize,
+    callback: Option<JsonCallback>,
+    user_data: *mut c_void,
+) -> ResultCString {
+    let Some(handle) = handle.as_ref() else {
+        return error_result(MJ_ERROR_INVALID_ARGUMENT, "Environment handle is null".to_string());
+    };
+    let Some(callback) = callback else {
+        return error_result(MJ_ERROR_INVALID_ARGUMENT, "Filter callback is null".to_string());
+    };
+    let name_str = make_str!(name, name_len, "filter name").to_owned();
+    let user_data = UserData(user_data);
+
+    let filter_name = name_str.clone();
+    handle.write().add_filter(name_str, move |value: Value, args: Rest<Value>| {
+        let mut all_args = Vec::with_capacity(args.len() + 1);
+        all_args.push(value);
+        all_args.extend(args.iter().cloned());
+        invoke_json_callback("filter", &filter_name, callback, user_data, &all_args)
+    });
+    ok_result(String::new())
+}
+
+/// Registers a filter implemented by a host callback that sees the render state.
+///
+/// This works like `mj_env_add_filter`, but the callback also receives the
+/// name of the current template, the auto escape mode and a state for looking
+/// up variables with `mj_filter_state_lookup`.  Describing the state costs an
+/// extra JSON document per call and every lookup serializes the variable, so
+/// prefer `mj_env_add_filter` for filters that only need their arguments.
+///
+/// # Returns
+/// * `Ok` with an empty string, or `Err` if the callback is null.
+///
+/// # Safety
+/// `handle` must be a valid handle, `name` must be valid for `name_len` bytes
+/// and `callback` must stay callable with `user_data` for the lifetime of the handle.
+#[no_mangle]
+pub unsafe extern "C" fn mj_env_add_state_filter(
+    handle: *mut EnvHandle,
+    name: *const c_char,
+    name_len: usize,
+    callback: Option<StateFilterCallback>,
+    user_data: *mut c_void,
+) -> ResultCString {
+    let Some(handle) = handle.as_ref() else {
+        return error_result(MJ_ERROR_INVALID_ARGUMENT, "Environment handle is null".to_string());
+    };
+    let Some(callback) = callback else {
+        return error_result(MJ_ERROR_INVALID_ARGUMENT, "Filter callback is null".to_string());
+    };
+    let name_str = make_str!(name, name_len, "filter name").to_owned();
+    let user_data = UserData(user_data);
+
+    let filter_name = name_str.clone();
+    handle.write().add_filter(
+        name_str,
+        move |state: &State, value: Value, args: Rest<Value>| {
+            let mut all_args = Vec::with_capacity(args.len() + 1);
+            all_args.push(value);
+            all_args.extend(args.iter().cloned());
+            invoke_state_filter_callback(&filter_name, callback, user_data, state, &all_args)
+        },
+    );
+    ok_result(String::new())
+}
+
+/// Registers a global function implemented by a host callback.
+///
+/// The callback receives the function arguments as a JSON array and reports a
+/// JSON encoded result.  Errors reported by the callback fail the render.
+///
+/// # Returns
+/// * `Ok` with an empty string, or `Err` if the callback is null.
+///
+/// # Safety
+/// `handle` must be a valid handle, `name` must be valid for `name_len` bytes
+/// and `callback` must stay callable with `user_data` for the lifetime of the handle.
+#[no_mangle]
+pub unsafe extern "C" fn mj_env_add_function(
+    handle: *mut EnvHandle,
+    name: *const c_char,
+    name_len: usize,
+    callback: Option<JsonCallback>,
+    user_data: *mut c_void,
+) -> ResultCString {
+    let Some(handle) = handle.as_ref() else {
+        return error_result(MJ_ERROR_INVALID_ARGUMENT, "Environment handle is null".to_string());
+    };
+    let Some(callback) = callback else {
+        return error_result(MJ_ERROR_INVALID_ARGUMENT, "Function callback is null".to_string());
+    };
+    let name_str = make_str!(name, name_len, "function name").to_owned();
+    let user_data = UserData(user_data);
+
+    let function_name = name_str.clone();
+    handle.write().add_function(name_str, move |args: Rest<Value>| {
+        invoke_json_callback("function", &function_name, callback, user_data, &args)
+    });
+    ok_result(String::new())
+}
+
+/// Registers a test implemented by a host callback.
+///
+/// The callback receives a JSON array holding the tested value followed by
+/// the test arguments and must report `true` or `false`.  Any other result
+/// fails the render.
+///
+/// # Returns
+/// * `Ok` with an empty string, or `Err` if the callback is null.
+///
+/// # Safety
+/// `handle` must be a valid handle, `name` must be valid for `name_len` bytes
+/// and `callback` must stay callable with `user_data` for the lifetime of the handle.
+#[no_mangle]
+pub unsafe extern "C" fn mj_env_add_test(
+    handle: *mut EnvHandle,
+    name: *const c_char,
+    name_len: usize,
+    callback: Option<JsonCallback>,
+    user_data: *mut c_void,
+) -> ResultCString {
+    let Some(handle) = handle.as_ref() else {
+        return error_result(MJ_ERROR_INVALID_ARGUMENT, "Environment handle is null".to_string());
+    };
+    let Some(callback) = callback else {
+        return error_result(MJ_ERROR_INVALID_ARGUMENT, "Test callback is null".to_string());
+    };
+    let name_str = make_str!(name, name_len, "test name").to_owned();
+    let user_data = UserData(user_data);
+
+    let test_name = name_str.clone();
+    handle.write().add_test(name_str, move |value: Value, args: Rest<Value>| {
+        let mut all_args = Vec::with_capacity(args.len() + 1);
+        all_args.push(value);
+        all_args.extend(args.iter().cloned());
+        let rv = invoke_json_callback("test", &test_name, callback, user_data, &all_args)?;
+        if rv.kind() != ValueKind::Bool {
+            return Err(Error::new(
+                ErrorKind::InvalidOperation,
+                format!("test '{}' must return a boolean, got {}", test_name, rv.kind()),
+            ));
+        }
+        Ok(rv.is_true())
+    });
+    ok_result(String::new())
+}
+
+/// Removes a filter, built-in or added with `mj_env_add_filter`.
+///
+/// Templates using the filter afterwards fail with an unknown filter error.
+/// Removing a filter that does not exist does nothing.
+///
+/// # Safety
+/// `handle` must be a valid handle or null and `name` must be valid for
+/// `name_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn mj_env_remove_filter(
+    handle: *mut EnvHandle,
+    name: *const c_char,
+    name_len: usize,
+) {
+    let (Some(handle), Ok(name_str)) = (handle.as_ref(), str_from_raw(name, name_len)) else {
+        return;
+    };
+    handle.write().remove_filter(name_str);
+}
+
+/// Removes a global function such as `range`, see `mj_env_remove_filter`.
+///
+/// Functions share their namespace with globals, so this also removes a
+/// global added with `mj_env_add_global`.
+///
+/// # Safety
+/// `handle` must be a valid handle or null and `name` must be valid for
+/// `name_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn mj_env_remove_function(
+    handle: *mut EnvHandle,
+    name: *const c_char,
+    name_len: usize,
+) {
+    let (Some(handle), Ok(name_str)) = (handle.as_ref(), str_from_raw(name, name_len)) else {
+        return;
+    };
+    handle.write().remove_global(name_str);
+}
+
+/// Adds a global variable available to every render with the handle.
+///
+/// Variables of the same name in the render context shadow the global.
+/// Adding a global under an existing name replaces it.
+///
+/// # Returns
+/// * `Ok` with an empty string, or `Err` if the value is not valid JSON.
+///
+/// # Safety
+/// `handle` must be a valid handle and all string pointers must be valid for
+/// the given lengths.
+#[no_mangle]
+pub unsafe extern "C" fn mj_env_add_global(
+    handle: *mut EnvHandle,
+    name: *const c_char,
+    name_len: usize,
+    json_value: *const c_char,
+    json_value_len: usize,
+) -> ResultCString {
+    let Some(handle) = handle.as_ref() else {
+        return error_result(MJ_ERROR_INVALID_ARGUMENT, "Environment handle is null".to_string());
+    };
+    let name_str = make_str!(name, name_len, "global name");
+    let json_str = make_str!(json_value, json_value_len, "JSON value", MJ_ERROR_INVALID_CONTEXT);
+
+    let value = match json_to_value(json_str, Conversion::default()) {
+        Ok(v) => v,
+        Err(e) => return error_result(MJ_ERROR_INVALID_CONTEXT, e.to_string()),
+    };
+    handle.write().add_global(name_str.to_owned(), value);
+    ok_result(String::new())
+}
+
+/// Sets a host callback used to load templates that are not registered.
+///
+/// The loader is consulted by `mj_env_render_named` as well as by
+/// `{% include %}`, `{% import %}` and `{% extends %}`.  Loaded templates are
+/// cached in the handle until they are removed or cleared.  Passing a null
+/// callback removes the loader.
+///
+/// # Safety
+/// `handle` must be a valid handle or null and `callback` must stay callable
+/// with `user_data` for the lifetime of the handle.
+#[no_mangle]
+pub unsafe extern "C" fn mj_env_set_loader(
+    handle: *mut EnvHandle,
+    callback: Option<LoaderCallback>,
+    user_data: *mut c_void,
+) {
+    let Some(handle) = handle.as_ref() else {
+        return;
+    };
+    let user_data = UserData(user_data);
+    match callback {
+        Some(callback) => handle
+            .write()
+            .set_loader(move |name| invoke_loader_callback(name, callback, user_data)),
+        None => handle.write().set_loader(|_| Ok(None)),
+    }
+}
+
+/// Sets a loader that layers in-memory overrides over a template directory.
+///
+/// A template is looked up in the overrides first and only loaded from
+/// `base_path` if no override has its name, so `{% extends "base.html" %}`
+/// picks an override of `base.html` when there is one and the file otherwise.
+/// This replaces a loader set with `mj_env_set_loader`, and templates
+/// registered with `mj_env_add_template` still take precedence over both.
+/// Loaded templates are cached in the handle until they are removed or
+/// cleared.  A name that appears more than once keeps the last source.
+///
+/// # Returns
+/// * `Ok` with an empty string, or `Err` if an argument is null or not valid
+///   UTF-8.  The loader is only changed on success.
+///
+/// # Safety
+/// `handle` must be a valid handle, `names`, `name_lens`, `sources` and
+/// `source_lens` must each point to `count` elements and every name, source
+/// and `base_path` must be valid for its length.
+#[no_mangle]
+pub unsafe extern "C" fn mj_env_set_overlay_loader(
+    handle: *mut EnvHandle,
+    names: *const *const c_char,
+    name_lens: *const usize,
+    sources: *const *const c_char,
+    source_lens: *const usize,
+    count: usize,
+    base_path: *const c_char,
+    base_path_len: usize,
+) -> ResultCString {
+    let Some(handle) = handle.as_ref() else {
+        return error_result(MJ_ERROR_INVALID_ARGUMENT, "Environment handle is null".to_string());
+    };
+    let base_path = make_str!(base_path, base_path_len, "base path");
+    let (Some(names), Some(name_lens), Some(sources), Some(source_lens)) = (
+        slice_from_raw(names, count),
+        slice_from_raw(name_lens, count),
+        slice_from_raw(sources, count),
+        slice_from_raw(source_lens, count),
+    ) else {
+        return error_result(MJ_ERROR_INVALID_ARGUMENT, "Template arrays are null".to_string());
+    };
+
+    let mut overrides = HashMap::with_capacity(count);
+    for idx in 0..count {
+        let Ok(name_str) = str_from_raw(names[idx], name_lens[idx]) else {
+            return error_result(
+                MJ_ERROR_INVALID_ARGUMENT,
+                format!("Name of template {} is null or not valid UTF-8", idx),
+            );
+        };
+        let Ok(template_str) = str_from_raw(sources[idx], source_lens[idx]) else {
+            return error_result(
+                MJ_ERROR_INVALID_ARGUMENT,
+                format!("Source of template '{}' is null or not valid UTF-8", name_str),
+            );
+        };
+        overrides.insert(name_str.to_owned(), template_str.to_owned());
+    }
+
+    let files = minijinja::path_loader(base_path);
+    handle.write().set_loader(move |name| match overrides.get(name) {
+        Some(source) => Ok(Some(source.clone())),
+        None => files(name),
+    });
+    ok_result(String::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::error::{mj_last_error_code, MJ_ERROR_SYNTAX};
+
+    unsafe fn autoescape_for(handle: *const EnvHandle, name: &str) -> Result<String, String> {
+        mj_env_autoescape_for(handle, name.as_ptr().cast(), name.len()).into_result()
+    }
+
+    unsafe fn set_autoescape_mode(handle: *mut EnvHandle, mode: &str) {
+        mj_env_set_autoescape_mode(handle, mode.as_ptr().cast(), mode.len())
+            .into_result()
+            .unwrap();
+    }
+
+    unsafe fn add_template(handle: *mut EnvHandle, name: &str, source: &str) {
+        let (name_ptr, source_ptr) = (name.as_ptr().cast(), source.as_ptr().cast());
+        mj_env_add_template(handle, name_ptr, name.len(), source_ptr, source.len())
+            .into_result()
+            .unwrap();
+    }
+
+    unsafe fn render_named(handle: *const EnvHandle, name: &str) -> Result<String, String> {
+        let ctx = r#"{"name": "World"}"#;
+        let (name_ptr, ctx_ptr) = (name.as_ptr().cast(), ctx.as_ptr().cast());
+        mj_env_render_named(handle, name_ptr, name.len(), ctx_ptr, ctx.len()).into_result()
+    }
+
+    #[test]
+    fn bundles_are_checked_with_the_syntax_of_the_handle() {
+        let syntax = r#"{"variable_delimiters": ["[[", "]]"]}"#;
+        let source = "Hello [[ name ]]!";
+        unsafe {
+            let handle = mj_env_create();
+            mj_env_set_syntax(handle, syntax.as_ptr().cast(), syntax.len())
+                .into_result()
+                .unwrap();
+            let bundle = mj_env_bundle_template(handle, source.as_ptr().cast(), source.len());
+            assert!(bundle.is_ok);
+            let (name, name_len) = ("greeting".as_ptr().cast(), "greeting".len());
+            let added = mj_env_add_bundle(handle, name, name_len, bundle.ptr, bundle.len);
+            assert_eq!(added.into_result(), Ok(String::new()));
+            crate::free_result_bytes(bundle);
+            assert_eq!(render_named(handle, "greeting"), Ok("Hello World!".to_string()));
+
+            let unclosed = "Hello [[ name";
+            let bundle = mj_env_bundle_template(handle, unclosed.as_ptr().cast(), unclosed.len());
+            assert!(!bundle.is_ok);
+            assert_eq!(mj_last_error_code(), MJ_ERROR_SYNTAX);
+            crate::free_result_bytes(bundle);
+
+            let added = mj_env_add_bundle(handle, name, name_len, source.as_ptr(), 5);
+            assert_eq!(added.into_result(), Err("not a template source bundle".to_string()));
+            mj_env_destroy(handle);
+        }
+    }
+
+    #[test]
+    fn renders_from_several_threads_while_templates_are_added() {
+        let handle = mj_env_create();
+        unsafe { add_template(handle, "greeting", "Hello {{ name }}!") };
+        // Raw pointers are not `Send`, the handle itself is shared.
+        let shared = handle as usize;
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(move || {
+                    for _ in 0..200 {
+                        let handle = shared as *const EnvHandle;
+                        let rendered = unsafe { render_named(handle, "greeting") };
+                        assert_eq!(rendered, Ok("Hello World!".to_string()));
+                    }
+                });
+            }
+            scope.spawn(move || {
+                for idx in 0..200 {
+                    let name = format!("page{}", idx);
+                    unsafe { add_template(shared as *mut EnvHandle, &name, "{{ name }}") };
+                }
+            });
+        });
+        unsafe {
+            assert_eq!(render_named(handle, "page199"), Ok("World".to_string()));
+            mj_env_destroy(handle);
+        }
+    }
+
+    unsafe fn list_templates(handle: *const EnvHandle) -> String {
+        mj_env_list_templates(handle).into_result().unwrap()
+    }
+
+    #[test]
+    fn add_templates_registers_nothing_if_one_fails() {
+        let names = ["first", "broken", "third"];
+        let sources = ["1", "{% if %}", "3"];
+        let ptrs = |strs: &[&str]| strs.iter().map(|s| s.as_ptr().cast()).collect::<Vec<_>>();
+        let lens = |strs: &[&str]| strs.iter().map(|s| s.len()).collect::<Vec<_>>();
+        let (name_ptrs, name_lens) = (ptrs(&names), lens(&names));
+        let (source_ptrs, source_lens) = (ptrs(&sources), lens(&sources));
+        unsafe {
+            let handle = mj_env_create();
+            add_template(handle, "first", "kept");
+            let result = mj_env_add_templates(
+                handle,
+                name_ptrs.as_ptr(),
+                name_lens.as_ptr(),
+                source_ptrs.as_ptr(),
+                source_lens.as_ptr(),
+                names.len(),
+            )
+            .into_result();
+            assert_eq!(mj_last_error_code(), MJ_ERROR_SYNTAX);
+            let msg = result.unwrap_err();
+            assert!(msg.starts_with("Template 'broken' failed to compile: "), "{}", msg);
+
+            assert_eq!(list_templates(handle), r#"["first"]"#);
+            assert_eq!(render_named(handle, "first"), Ok("kept".to_string()));
+            mj_env_destroy(handle);
+        }
+    }
+
+    #[test]
+    fn include_and_import_resolve_registered_templates() {
+        unsafe {
+            let handle = mj_env_create_empty();
+            add_template(handle, "base.html", "<h1>{{ name }}</h1>");
+            add_template(handle, "macros.html", "{% macro hi(s) %}Hi {{ s }}{% endmacro %}");
+            add_template(
+                handle,
+                "child.html",
+                "{% include 'base.html' %}{% from 'macros.html' import hi %}{{ hi(name) }}",
+            );
+            let rendered = render_named(handle, "child.html");
+            assert_eq!(rendered, Ok("<h1>World</h1>Hi World".to_string()));
+
+            add_template(handle, "missing.html", "{% include 'nowhere.html' %}");
+            let msg = render_named(handle, "missing.html").unwrap_err();
+            assert!(msg.contains("nowhere.html"), "{}", msg);
+            mj_env_destroy(handle);
+        }
+    }
+
+    #[test]
+    fn autoescape_for_asks_the_configured_mode() {
+        unsafe {
+            let handle = mj_env_create();
+            assert_eq!(autoescape_for(handle, "page.html"), Ok("html".to_string()));
+            assert_eq!(autoescape_for(handle, "data.json"), Ok("none".to_string()));
+
+            set_autoescape_mode(handle, "auto");
+            assert_eq!(autoescape_for(handle, "data.json.j2"), Ok("json".to_string()));
+            let clone = mj_env_clone(handle);
+            assert_eq!(autoescape_for(clone, "data.json"), Ok("json".to_string()));
+
+            set_autoescape_mode(handle, "none");
+            assert_eq!(autoescape_for(handle, "page.html"), Ok("none".to_string()));
+            assert_eq!(autoescape_for(clone, "page.html"), Ok("html".to_string()));
+            mj_env_destroy(clone);
+            mj_env_destroy(handle);
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use std::cell::Cell;
+use std::fmt;
+
+use minijinja::ErrorKind;
+use serde_json::json;
+
+/// The call succeeded.
+pub const MJ_OK: i32 = 0;
+/// An argument is invalid, e.g. a null handle or conflicting delimiters.
+pub const MJ_ERROR_INVALID_ARGUMENT: i32 = 1;
+/// The context is not valid JSON or contains an invalid blob.
+pub const MJ_ERROR_INVALID_CONTEXT: i32 = 2;
+/// The template has a syntax error.
+pub const MJ_ERROR_SYNTAX: i32 = 3;
+/// An undefined value was used, e.g. under strict undefined behavior.
+pub const MJ_ERROR_UNDEFINED: i32 = 4;
+/// A template could not be found.
+pub const MJ_ERROR_TEMPLATE_NOT_FOUND: i32 = 5;
+/// A filter, test or function was called with bad arguments.
+pub const MJ_ERROR_BAD_ARGUMENTS: i32 = 6;
+/// An unknown filter, test, function, method or block was used.
+pub const MJ_ERROR_UNKNOWN_CALLABLE: i32 = 7;
+/// An operation failed at runtime, e.g. adding a number and a string.
+pub const MJ_ERROR_INVALID_OPERATION: i32 = 8;
+/// A value could not be serialized or deserialized.
+pub const MJ_ERROR_SERIALIZATION: i32 = 9;
+/// Writing the output or loading a template failed.
+pub const MJ_ERROR_IO: i32 = 10;
+/// The render exceeded its fuel limit.
+pub const MJ_ERROR_OUT_OF_FUEL: i32 = 11;
+/// The render did not finish within its timeout.
+pub const MJ_ERROR_TIMEOUT: i32 = 12;
+/// The output did not fit into the buffer passed to `render_template_into`.
+pub const MJ_ERROR_BUFFER_TOO_SMALL: i32 = 13;
+/// Any other error.
+pub const MJ_ERROR_OTHER: i32 = 99;
+
+thread_local! {
+    static LAST_ERROR_CODE: Cell<i32> = const { Cell::new(MJ_OK) };
+}
+
+/// Records the outcome of the current call for `mj_last_error_code`.
+pub(crate) fn set_last_error_code(code: i32) {
+    LAST_ERROR_CODE.with(|last| last.set(code));
+}
+
+/// Returns the error code of the last call on the current thread.
+///
+/// Every call returning a `ResultCString` records its outcome, so this is
+/// `MJ_OK` after a successful call and one of the `MJ_ERROR_*` codes after a
+/// failed one.
+#[no_mangle]
+pub extern "C" fn mj_last_error_code() -> i32 {
+    LAST_ERROR_CODE.with(|last| last.get())
+}
+
+/// Describes where in the template an error occurred, e.g. `line 2, column 5`.
+pub(crate) fn error_location(err: &minijinja::Error) -> Option<String> {
+    let line = err.line()?;
+    Some(match error_column(err) {
+        Some(column) => format!("line {}, column {}", line, column),
+        None => format!("line {}", line),
+    })
+}
+
+/// Maps a MiniJinja error to one of the `MJ_ERROR_*` codes.
+pub(crate) fn error_code(err: &minijinja::Error) -> i32 {
+    match err.kind() {
+        ErrorKind::SyntaxError | ErrorKind::BadEscape | ErrorKind::InvalidDelimiter => {
+            MJ_ERROR_SYNTAX
+        }
+        ErrorKind::UndefinedError => MJ_ERROR_UNDEFINED,
+        ErrorKind::TemplateNotFound => MJ_ERROR_TEMPLATE_NOT_FOUND,
+        ErrorKind::TooManyArguments | ErrorKind::MissingArgument | ErrorKind::CannotUnpack => {
+            MJ_ERROR_BAD_ARGUMENTS
+        }
+        ErrorKind::UnknownFilter
+        | ErrorKind::UnknownTest
+        | ErrorKind::UnknownFunction
+        | ErrorKind::UnknownMethod
+        | ErrorKind::UnknownBlock => MJ_ERROR_UNKNOWN_CALLABLE,
+        ErrorKind::InvalidOperation
+        | ErrorKind::NonPrimitive
+        | ErrorKind::NonKey
+        | ErrorKind::BadInclude
+        | ErrorKind::EvalBlock => MJ_ERROR_INVALID_OPERATION,
+        ErrorKind::BadSerialization | ErrorKind::CannotDeserialize => MJ_ERROR_SERIALIZATION,
+        ErrorKind::WriteFailure => MJ_ERROR_IO,
+        ErrorKind::OutOfFuel => MJ_ERROR_OUT_OF_FUEL,
+        _ => MJ_ERROR_OTHER,
+    }
+}
+
+/// An error from one of the render entry points.
+#[derive(Debug)]
+pub(crate) enum RenderError {
+    /// The render options are invalid, e.g. conflicting delimiters.
+    InvalidOptions(String),
+    /// The context could not be converted, e.g. it is not valid JSON.
+    InvalidContext(String),
+    /// MiniJinja failed to compile or render the template.
+    Template(minijinja::Error),
+    /// The render did not finish within the timeout, in milliseconds.
+    Timeout(u64),
+}
+
+impl From<minijinja::Error> for RenderError {
+    fn from(err: minijinja::Error) -> Self {
+        RenderError::Template(err)
+    }
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenderError::InvalidOptions(msg) | RenderError::InvalidContext(msg) => f.write_str(msg),
+            RenderError::Template(err) => f.write_str(&crate::render_error_message(err)),
+            RenderError::Timeout(timeout_ms) => {
+                write!(f, "render exceeded the timeout of {} ms", timeout_ms)
+            }
+        }
+    }
+}
+
+impl RenderError {
+    /// Returns the `MJ_ERROR_*` code of the error.
+    pub fn code(&self) -> i32 {
+        match self {
+            RenderError::InvalidOptions(_) => MJ_ERROR_INVALID_ARGUMENT,
+            RenderError::InvalidContext(_) => MJ_ERROR_INVALID_CONTEXT,
+            RenderError::Template(err) => error_code(err),
+            RenderError::Timeout(_) => MJ_ERROR_TIMEOUT,
+        }
+    }
+
+    /// Returns the name of the error kind, the `ErrorKind` variant for template errors.
+    fn kind_name(&self) -> String {
+        match self {
+            RenderError::InvalidOptions(_) => "InvalidOptions".to_string(),
+            RenderError::InvalidContext(_) => "InvalidContext".to_string(),
+            RenderError::Template(err) => format!("{:?}", err.kind()),
+            RenderError::Timeout(_) => "Timeout".to_string(),
+        }
+    }
+
+    /// Describes the error as a JSON object.
+    ///
+    /// The object has the fields `code`, `kind`, `message`, `detail`,
+    /// `template_name`, `line`, `column` and `debug_info`.  Fields that are not known are `null`,
+    /// lines and columns start at 1.  `debug_info` is the source snippet around the failing
+    /// line, which render errors only carry with the `debug` option.
+    pub fn to_json(&self) -> serde_json::Value {
+        match self {
+            RenderError::InvalidOptions(_)
+            | RenderError::InvalidContext(_)
+            | RenderError::Timeout(_) => json!({
+                "code": self.code(),
+                "kind": self.kind_name(),
+                "message": self.to_string(),
+                "detail": null,
+                "template_name": null,
+                "line": null,
+                "column": null,
+                "debug_info": null,
+            }),
+            RenderError::Template(err) => json!({
+                "code": self.code(),
+                "kind": self.kind_name(),
+                "message": err.to_string(),
+                "detail": err.detail(),
+                "template_name": err.name(),
+                "line": err.line(),
+                "column": error_column(err),
+                "debug_info": debug_info(err),
+            }),
+        }
+    }
+}
+
+/// Renders the source snippet of the error, if it carries the template source.
+fn debug_info(err: &minijinja::Error) -> Option<String> {
+    err.template_source()?;
+    Some(err.display_debug_info().to_string())
+}
+
+/// Returns the column at which the error starts, if known.
+pub(crate) fn error_column(err: &minijinja::Error) -> Option<usize> {
+    source_column(err.template_source()?, err.range()?.start)
+}
+
+/// Returns the column of the byte `offset` of `source`, counted in characters
+/// from 1.
+pub(crate) fn source_column(source: &str, offset: usize) -> Option<usize> {
+    let before = source.get(..offset)?;
+    let line_start = before.rfind('\n').map_or(0, |pos| pos + 1);
+    Some(before[line_start..].chars().count() + 1)
+}
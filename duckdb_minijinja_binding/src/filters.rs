@@ -0,0 +1,963 @@
+use std::fmt::Write;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use minijinja::value::{Kwargs, ValueKind};
+use minijinja::{AutoEscape, Environment, Error, ErrorKind, HtmlEscape, State, Value};
+use serde::Serialize;
+use serde_json::ser::PrettyFormatter;
+
+use crate::context::{json_to_value, Conversion};
+
+/// Registers the filters this binding adds on top of the MiniJinja builtins.
+pub(crate) fn register_filters(env: &mut Environment) {
+    env.add_filter("sql_quote", sql_quote);
+    env.add_filter("sql_identifier", sql_identifier);
+    env.add_filter("sql_in", sql_in);
+    env.add_filter("datetimeformat", crate::datetime::datetimeformat);
+    env.add_filter("b64encode", b64encode);
+    env.add_filter("b64decode", b64decode);
+    env.add_filter("csvescape", csvescape);
+    env.add_filter("urlencode", urlencode);
+    env.add_filter("urlencode_component", urlencode_component);
+    env.add_filter("slugify", slugify);
+    env.add_filter("upper", crate::casing::upper);
+    env.add_filter("lower", crate::casing::lower);
+    env.add_filter("title", crate::casing::title);
+    env.add_filter("capitalize", crate::casing::capitalize);
+    env.add_filter("from_json", from_json);
+    env.add_filter("to_json", to_json);
+    env.add_filter("tojson", tojson);
+    env.add_filter("reformat_json", reformat_json);
+    env.add_filter("indent", indent);
+    env.add_filter("dedent", dedent);
+    env.add_filter("default", default);
+    env.add_filter("d", default);
+    env.add_filter("join", join);
+    env.add_filter("number_format", number_format);
+    env.add_filter("filesizeformat", filesizeformat);
+    env.add_filter("durationformat", durationformat);
+    env.add_filter("truncate", truncate);
+    env.add_filter("wordwrap", wordwrap);
+}
+
+/// Quotes a value as a SQL string literal.
+///
+/// Embedded single quotes are doubled and none/undefined become `NULL`.
+fn quote_sql_literal(value: &Value) -> String {
+    if value.is_undefined() || value.is_none() {
+        return "NULL".to_string();
+    }
+    format!("'{}'", value.to_string().replace('\'', "''"))
+}
+
+/// Renders a value as a SQL string literal, e.g. `O'Brien` becomes `'O''Brien'`.
+///
+/// The result is marked safe as it is already escaped for SQL.
+fn sql_quote(value: Value) -> Value {
+    Value::from_safe_string(quote_sql_literal(&value))
+}
+
+/// Renders a value as a quoted SQL identifier, e.g. `weird"col` becomes `"weird""col"`.
+///
+/// The result is marked safe as it is already escaped for SQL.
+fn sql_identifier(value: Value) -> Result<Value, Error> {
+    if value.is_undefined() || value.is_none() {
+        return Err(Error::new(
+            ErrorKind::InvalidOperation,
+            "sql_identifier requires a value, got none",
+        ));
+    }
+    let ident = value.to_string();
+    if ident.contains('\0') {
+        return Err(Error::new(
+            ErrorKind::InvalidOperation,
+            "sql_identifier does not accept identifiers containing null bytes",
+        ));
+    }
+    Ok(Value::from_safe_string(format!(
+        "\"{}\"",
+        ident.replace('"', "\"\"")
+    )))
+}
+
+/// Expands a list into a parenthesized SQL list, e.g. `['a', 'b']` becomes `('a', 'b')`.
+///
+/// Numbers and booleans are emitted as-is, everything else is quoted like
+/// `sql_quote`.  An empty list becomes `(NULL)` so the resulting `IN` clause
+/// stays valid.  The result is marked safe as it is already escaped for SQL.
+fn sql_in(value: Value) -> Result<Value, Error> {
+    let iter = match value.kind() {
+        ValueKind::Seq | ValueKind::Iterable => value.try_iter()?,
+        kind => {
+            return Err(Error::new(
+                ErrorKind::InvalidOperation,
+                format!("sql_in expects a list, got {}", kind),
+            ))
+        }
+    };
+
+    let items: Vec<String> = iter
+        .map(|item| match item.kind() {
+            ValueKind::Number => item.to_string(),
+            ValueKind::Bool if item.is_true() => "TRUE".to_string(),
+            ValueKind::Bool => "FALSE".to_string(),
+            _ => quote_sql_literal(&item),
+        })
+        .collect();
+
+    if items.is_empty() {
+        return Ok(Value::from_safe_string("(NULL)".to_string()));
+    }
+    Ok(Value::from_safe_string(format!("({})", items.join(", "))))
+}
+
+/// Encodes bytes, or the UTF-8 bytes of a string, as standard base64.
+fn b64encode(value: Value) -> Result<String, Error> {
+    match value.as_bytes() {
+        Some(bytes) => Ok(BASE64.encode(bytes)),
+        None => Err(Error::new(
+            ErrorKind::InvalidOperation,
+            format!("b64encode expects bytes or a string, got {}", value.kind()),
+        )),
+    }
+}
+
+/// Decodes a standard base64 string into bytes.
+fn b64decode(value: &str) -> Result<Value, Error> {
+    BASE64.decode(value).map(Value::from_bytes).map_err(|e| {
+        Error::new(
+            ErrorKind::InvalidOperation,
+            format!("b64decode got invalid base64: {}", e),
+        )
+    })
+}
+
+/// Escapes a value as a CSV field following RFC 4180, e.g. `a,b` becomes `"a,b"`.
+///
+/// Fields containing the delimiter, a double quote or a line break are quoted
+/// with embedded double quotes doubled, other fields are left untouched.  The
+/// delimiter defaults to a comma and none/undefined become an empty field.
+/// The result is marked safe as it is already escaped for CSV.
+fn csvescape(value: Value, delimiter: Option<&str>) -> Result<Value, Error> {
+    let delimiter = delimiter.unwrap_or(",");
+    if delimiter.is_empty() {
+        return Err(Error::new(
+            ErrorKind::InvalidOperation,
+            "csvescape requires a non-empty delimiter",
+        ));
+    }
+    if value.is_undefined() || value.is_none() {
+        return Ok(Value::from_safe_string(String::new()));
+    }
+    let field = value.to_string();
+    if field.contains(delimiter) || field.contains(['"', '\n', '\r']) {
+        Ok(Value::from_safe_string(format!(
+            "\"{}\"",
+            field.replace('"', "\"\"")
+        )))
+    } else {
+        Ok(Value::from_safe_string(field))
+    }
+}
+
+/// Percent-encodes the UTF-8 bytes of `s` per RFC 3986.
+///
+/// Unreserved characters (`A-Z`, `a-z`, `0-9`, `-`, `.`, `_` and `~`) are kept,
+/// as is `/` if `keep_slash` is set.
+fn percent_encode(s: &str, keep_slash: bool) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for &b in s.as_bytes() {
+        if b.is_ascii_alphanumeric()
+            || matches!(b, b'-' | b'.' | b'_' | b'~')
+            || (keep_slash && b == b'/')
+        {
+            encoded.push(b as char);
+        } else {
+            let _ = write!(encoded, "%{:02X}", b);
+        }
+    }
+    encoded
+}
+
+/// Percent-encodes a value for use in a URL path, e.g. `a b/c` becomes `a%20b/c`.
+///
+/// Slashes are kept, use `urlencode_component` to encode them as well.
+/// None/undefined become an empty string.  The result is marked safe as it
+/// only contains characters that need no HTML escaping.
+fn urlencode(value: Value) -> Value {
+    if value.is_undefined() || value.is_none() {
+        return Value::from_safe_string(String::new());
+    }
+    Value::from_safe_string(percent_encode(&value.to_string(), true))
+}
+
+/// Percent-encodes a value for use as a single URL component, e.g. a query
+/// parameter, so `a b/c` becomes `a%20b%2Fc`.
+///
+/// None/undefined become an empty string.  The result is marked safe like
+/// `urlencode`.
+fn urlencode_component(value: Value) -> Value {
+    if value.is_undefined() || value.is_none() {
+        return Value::from_safe_string(String::new());
+    }
+    Value::from_safe_string(percent_encode(&value.to_string(), false))
+}
+
+/// Returns the ASCII spelling of a lowercase Latin letter with a diacritic.
+fn fold_diacritic(c: char) -> Option<&'static str> {
+    Some(match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => "a",
+        'æ' => "ae",
+        'ç' | 'ć' | 'ĉ' | 'ċ' | 'č' => "c",
+        'ď' | 'đ' | 'ð' => "d",
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ĕ' | 'ė' | 'ę' | 'ě' => "e",
+        'ĝ' | 'ğ' | 'ġ' | 'ģ' => "g",
+        'ĥ' | 'ħ' => "h",
+        'ì' | 'í' | 'î' | 'ï' | 'ĩ' | 'ī' | 'ĭ' | 'į' | 'ı' => "i",
+        'ĳ' => "ij",
+        'ĵ' => "j",
+        'ķ' => "k",
+        'ĺ' | 'ļ' | 'ľ' | 'ŀ' | 'ł' => "l",
+        'ñ' | 'ń' | 'ņ' | 'ň' => "n",
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ŏ' | 'ő' => "o",
+        'œ' => "oe",
+        'ŕ' | 'ŗ' | 'ř' => "r",
+        'ś' | 'ŝ' | 'ş' | 'š' => "s",
+        'ß' => "ss",
+        'ţ' | 'ť' | 'ŧ' => "t",
+        'þ' => "th",
+        'ù' | 'ú' | 'û' | 'ü' | 'ũ' | 'ū' | 'ŭ' | 'ů' | 'ű' | 'ų' => "u",
+        'ŵ' => "w",
+        'ý' | 'ÿ' | 'ŷ' => "y",
+        'ź' | 'ż' | 'ž' => "z",
+        _ => return None,
+    })
+}
+
+/// Turns a string into a lowercase ASCII slug, e.g. `Crème Brûlée!` becomes
+/// `creme-brulee`.
+///
+/// Latin letters lose their diacritics, whether precomposed or followed by
+/// combining marks.  Any run of other characters that are not ASCII letters or
+/// digits becomes a single hyphen and leading and trailing hyphens are removed.
+fn slugify(value: &str) -> String {
+    let mut slug = String::with_capacity(value.len());
+    let mut separator = false;
+    for c in value.chars().flat_map(char::to_lowercase) {
+        // Combining diacritical marks, as left by decomposed input.
+        if ('\u{300}'..='\u{36f}').contains(&c) {
+            continue;
+        }
+        let folded = if c.is_ascii_alphanumeric() {
+            None
+        } else if let Some(folded) = fold_diacritic(c) {
+            Some(folded)
+        } else {
+            separator = !slug.is_empty();
+            continue;
+        };
+        if separator {
+            slug.push('-');
+            separator = false;
+        }
+        match folded {
+            Some(folded) => slug.push_str(folded),
+            None => slug.push(c),
+        }
+    }
+    slug
+}
+
+/// Parses a JSON string into a value, e.g. `{{ (row | from_json).name }}`.
+///
+/// The document is converted like the render context, so numbers keep their
+/// exact digits and `{"__blob__": ...}` objects become bytes.
+fn from_json(value: &str) -> Result<Value, Error> {
+    json_to_value(value, Conversion::default()).map_err(|e| {
+        Error::new(
+            ErrorKind::InvalidOperation,
+            format!("from_json could not parse the value: {}", e),
+        )
+    })
+}
+
+/// Serializes a value as JSON, pretty printed with `indent` spaces if given.
+///
+/// The indent may also be passed as `indent` keyword argument.  The result is
+/// marked safe so the JSON is not HTML escaped.
+fn to_json(value: Value, indent: Option<usize>, kwargs: Kwargs) -> Result<Value, Error> {
+    let json = serialize_json("to_json", &value, indent, kwargs)?;
+    Ok(Value::from_safe_string(json))
+}
+
+/// Serializes a value as JSON that is safe to embed in HTML, like Jinja2's
+/// `tojson`, e.g. `<script>const data = {{ row | tojson }};</script>`.
+///
+/// Unlike `to_json`, `<`, `>`, `&` and `'` as well as the line and paragraph
+/// separators are written as `\uXXXX` escapes, so a string holding
+/// `</script>` cannot end the script block.  The result is marked safe.
+fn tojson(value: Value, indent: Option<usize>, kwargs: Kwargs) -> Result<Value, Error> {
+    let json = serialize_json("tojson", &value, indent, kwargs)?;
+    let mut escaped = String::with_capacity(json.len());
+    for c in json.chars() {
+        match c {
+            '<' => escaped.push_str("\\u003c"),
+            '>' => escaped.push_str("\\u003e"),
+            '&' => escaped.push_str("\\u0026"),
+            '\'' => escaped.push_str("\\u0027"),
+            '\u{2028}' => escaped.push_str("\\u2028"),
+            '\u{2029}' => escaped.push_str("\\u2029"),
+            c => escaped.push(c),
+        }
+    }
+    Ok(Value::from_safe_string(escaped))
+}
+
+/// Parses a string as JSON and serializes it again with `indent` spaces, 2 if
+/// not given, e.g. `{% filter reformat_json %}{"a": [1, {{ b }}]}{% endfilter %}`.
+///
+/// This gives JSON assembled from fragments a consistent layout and fails if
+/// it is not valid JSON.  Numbers keep their exact digits and object keys are
+/// sorted.  The indent may also be passed as `indent` keyword argument and the
+/// result is marked safe.
+fn reformat_json(value: &str, indent: Option<usize>, kwargs: Kwargs) -> Result<Value, Error> {
+    let document: serde_json::Value = serde_json::from_str(value).map_err(|e| {
+        Error::new(
+            ErrorKind::InvalidOperation,
+            format!("reformat_json could not parse the value: {}", e),
+        )
+    })?;
+    let json = serialize_json("reformat_json", &document, indent.or(Some(2)), kwargs)?;
+    Ok(Value::from_safe_string(json))
+}
+
+/// Serializes a value for `filter`, pretty printed with `indent` spaces if
+/// given as argument or `indent` keyword argument.
+fn serialize_json(
+    filter: &str,
+    value: &impl Serialize,
+    indent: Option<usize>,
+    kwargs: Kwargs,
+) -> Result<String, Error> {
+    let indent = kwargs.get::<Option<usize>>("indent")?.or(indent);
+    kwargs.assert_all_used()?;
+
+    let serialized = match indent {
+        None => serde_json::to_vec(value),
+        Some(width) => {
+            let indent = vec![b' '; width];
+            let mut out = Vec::new();
+            let mut serializer =
+                serde_json::Serializer::with_formatter(&mut out, PrettyFormatter::with_indent(&indent));
+            value.serialize(&mut serializer).map(|()| out)
+        }
+    };
+    let json = serialized.map_err(|e| {
+        Error::new(
+            ErrorKind::BadSerialization,
+            format!("{} could not serialize the value: {}", filter, e),
+        )
+    })?;
+    // serde_json only writes valid UTF-8.
+    Ok(String::from_utf8_lossy(&json).into_owned())
+}
+
+/// Indents all lines but the first, e.g. `{{ config | indent(2) }}` to nest YAML.
+///
+/// This replaces the MiniJinja builtin to also accept Jinja2's keyword
+/// arguments, `indent(width=4, first=false, blank=false)`.  `width` is a number
+/// of spaces or the string to indent with, `first` also indents the first line
+/// and `blank` also indents blank lines.  Like the builtin, a trailing newline
+/// is removed.
+fn indent(
+    value: String,
+    width: Option<Value>,
+    first: Option<bool>,
+    blank: Option<bool>,
+    kwargs: Kwargs,
+) -> Result<String, Error> {
+    let width = kwargs.get::<Option<Value>>("width")?.or(width);
+    let first = kwargs.get::<Option<bool>>("first")?.or(first).unwrap_or(false);
+    let blank = kwargs.get::<Option<bool>>("blank")?.or(blank).unwrap_or(false);
+    kwargs.assert_all_used()?;
+
+    let indent_with = match width {
+        None => " ".repeat(4),
+        Some(width) if width.kind() == ValueKind::String => width.to_string(),
+        Some(width) => match usize::try_from(width.clone()) {
+            Ok(width) => " ".repeat(width),
+            Err(_) => {
+                return Err(Error::new(
+                    ErrorKind::InvalidOperation,
+                    format!("indent width must be a number or a string, got {}", width.kind()),
+                ))
+            }
+        },
+    };
+
+    let value = value.strip_suffix('\n').unwrap_or(&value);
+    let value = value.strip_suffix('\r').unwrap_or(value);
+    let mut output = String::with_capacity(value.len());
+    for (idx, line) in value.split('\n').enumerate() {
+        if idx > 0 {
+            output.push('\n');
+        }
+        if (idx > 0 || first) && (!line.is_empty() || blank) {
+            output.push_str(&indent_with);
+        }
+        output.push_str(line);
+    }
+    Ok(output)
+}
+
+/// Removes the leading whitespace all lines have in common, e.g. to write a SQL
+/// fragment at the nesting of the template and re-indent it with `indent`.
+///
+/// Like Python's `textwrap.dedent`, tabs and spaces are different characters,
+/// so a line indented with a tab and one indented with spaces have no margin
+/// in common.  Lines of only whitespace do not count towards the margin and
+/// become empty.
+fn dedent(value: &str) -> String {
+    let is_blank = |line: &str| {
+        line.trim_end_matches('\r').trim_start_matches([' ', '\t']).is_empty()
+    };
+    let margin = value
+        .split('\n')
+        .filter(|line| !is_blank(line))
+        .map(|line| &line[..line.len() - line.trim_start_matches([' ', '\t']).len()])
+        .reduce(|margin, prefix| {
+            let common = margin.bytes().zip(prefix.bytes()).take_while(|(a, b)| a == b).count();
+            &margin[..common]
+        })
+        .unwrap_or("");
+
+    let mut output = String::with_capacity(value.len());
+    for (idx, line) in value.split('\n').enumerate() {
+        if idx > 0 {
+            output.push('\n');
+        }
+        if is_blank(line) {
+            if line.ends_with('\r') {
+                output.push('\r');
+            }
+        } else {
+            output.push_str(&line[margin.len()..]);
+        }
+    }
+    output
+}
+
+/// Returns `other` (default: an empty string) in place of an undefined value.
+///
+/// Like Jinja2, `boolean=true` also replaces values that are false, such as
+/// none, `0` or empty strings.  `null=true` only replaces none in addition, for
+/// JSON nulls without `null_as_undefined`.
+fn default(
+    value: &Value,
+    other: Option<Value>,
+    boolean: Option<bool>,
+    kwargs: Kwargs,
+) -> Result<Value, Error> {
+    let other = kwargs.get::<Option<Value>>("default_value")?.or(other);
+    let boolean = kwargs.get::<Option<bool>>("boolean")?.or(boolean).unwrap_or(false);
+    let null = kwargs.get::<Option<bool>>("null")?.unwrap_or(false);
+    kwargs.assert_all_used()?;
+
+    let replace = value.is_undefined()
+        || (boolean && !value.is_true())
+        || (null && value.is_none());
+    Ok(if replace {
+        other.unwrap_or_else(|| Value::from(""))
+    } else {
+        value.clone()
+    })
+}
+
+/// Joins the items of a sequence with `joiner`, e.g. `{{ tags | join(", ") }}`.
+///
+/// This replaces the MiniJinja builtin, which always returns a plain string,
+/// so under HTML autoescape safe items would be escaped again.  Like Jinja2,
+/// if the joiner or any item is safe, the other items are escaped and the
+/// result is marked safe.
+fn join(state: &State, value: &Value, joiner: Option<Value>) -> Result<Value, Error> {
+    let joiner = joiner.filter(|joiner| !joiner.is_none());
+    let markup = match value.try_iter() {
+        Ok(items) if matches!(state.auto_escape(), AutoEscape::Html) => {
+            let items = items.collect::<Vec<_>>();
+            let any_safe = items.iter().chain(joiner.iter()).any(Value::is_safe);
+            any_safe.then_some(items)
+        }
+        _ => None,
+    };
+    let Some(items) = markup else {
+        return join_plain(value, joiner.as_ref()).map(Value::from);
+    };
+
+    let mut rv = String::new();
+    for (idx, item) in items.iter().enumerate() {
+        if idx > 0 {
+            if let Some(joiner) = &joiner {
+                push_html(&mut rv, joiner);
+            }
+        }
+        push_html(&mut rv, item);
+    }
+    Ok(Value::from_safe_string(rv))
+}
+
+/// Joins the items of `value` as plain text, treating `none` and undefined
+/// as an empty sequence.
+fn join_plain(value: &Value, joiner: Option<&Value>) -> Result<String, Error> {
+    if value.is_undefined() || value.is_none() {
+        return Ok(String::new());
+    }
+    let items = value.try_iter().map_err(|err| {
+        Error::new(
+            ErrorKind::InvalidOperation,
+            format!("cannot join value of type {}", value.kind()),
+        )
+        .with_source(err)
+    })?;
+    let mut rv = String::new();
+    for (idx, item) in items.enumerate() {
+        if idx > 0 {
+            if let Some(joiner) = joiner {
+                write!(rv, "{joiner}").ok();
+            }
+        }
+        if let Some(item) = item.as_str() {
+            rv.push_str(item);
+        } else {
+            write!(rv, "{item}").ok();
+        }
+    }
+    Ok(rv)
+}
+
+/// Appends `value` to `out`, HTML escaping it unless it is safe.
+fn push_html(out: &mut String, value: &Value) {
+    let text = value.to_string();
+    if value.is_safe() {
+        out.push_str(&text);
+    } else {
+        write!(out, "{}", HtmlEscape(&text)).ok();
+    }
+}
+
+/// Formats a number with grouped thousands like Twig, e.g.
+/// `{{ revenue | number_format(2, '.', ',') }}` renders `1,234,567.89`.
+///
+/// `decimals` defaults to 0, the decimal point to `.` and the thousands
+/// separator to `,`.  Rounding is done on the decimal digits, half away from
+/// zero, so `1.005` rounds to `1.01` and `-2.5` to `-3`.  Strings holding a
+/// number, such as the exact digits of a large JSON number, are accepted.
+fn number_format(
+    value: &Value,
+    decimals: Option<usize>,
+    decimal_point: Option<&str>,
+    thousands_sep: Option<&str>,
+) -> Result<String, Error> {
+    let digits = decimal_digits(value).ok_or_else(|| {
+        Error::new(
+            ErrorKind::InvalidOperation,
+            format!("number_format expects a number, got {}", value.kind()),
+        )
+    })?;
+    let decimals = decimals.unwrap_or(0);
+    let (negative, digits) = match digits.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, digits.as_str()),
+    };
+    let (int_part, frac_part) = digits.split_once('.').unwrap_or((digits, ""));
+
+    // All digits up to the requested precision, rounded on the next digit.
+    let mut kept: Vec<u8> = int_part.bytes().collect();
+    kept.extend(frac_part.bytes().chain(std::iter::repeat(b'0')).take(decimals));
+    if frac_part.as_bytes().get(decimals).is_some_and(|&next| next >= b'5') {
+        let mut idx = kept.len();
+        loop {
+            if idx == 0 {
+                kept.insert(0, b'1');
+                break;
+            }
+            idx -= 1;
+            if kept[idx] == b'9' {
+                kept[idx] = b'0';
+            } else {
+                kept[idx] += 1;
+                break;
+            }
+        }
+    }
+    let int_len = kept.len() - decimals;
+    let (int_digits, frac_digits) = kept.split_at(int_len);
+
+    let mut rv = String::new();
+    // Rounding to zero does not keep the sign, like PHP 8.
+    if negative && kept.iter().any(|&digit| digit != b'0') {
+        rv.push('-');
+    }
+    for (idx, &digit) in int_digits.iter().enumerate() {
+        if idx > 0 && (int_len - idx).is_multiple_of(3) {
+            rv.push_str(thousands_sep.unwrap_or(","));
+        }
+        rv.push(digit as char);
+    }
+    if decimals > 0 {
+        rv.push_str(decimal_point.unwrap_or("."));
+        rv.extend(frac_digits.iter().map(|&digit| digit as char));
+    }
+    Ok(rv)
+}
+
+/// Returns a finite number as plain decimal digits, e.g. `-1234.5`.
+fn decimal_digits(value: &Value) -> Option<String> {
+    let repr = match value.kind() {
+        ValueKind::Number => value.to_string(),
+        ValueKind::String => value.as_str()?.trim().to_string(),
+        _ => return None,
+    };
+    let number = repr.parse::<f64>().ok().filter(|number| number.is_finite())?;
+    let unsigned = repr.strip_prefix('-').unwrap_or(&repr);
+    let (int_part, frac_part) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+    let is_plain =
+        !int_part.is_empty() && int_part.bytes().chain(frac_part.bytes()).all(|b| b.is_ascii_digit());
+    if is_plain {
+        Some(repr)
+    } else {
+        // Exponents and other spellings Rust accepts are written out in full.
+        Some(number.to_string())
+    }
+}
+
+/// Returns a number or numeric string as a float for `filter`.
+fn float_arg(filter: &str, value: &Value) -> Result<f64, Error> {
+    decimal_digits(value)
+        .and_then(|digits| digits.parse().ok())
+        .ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidOperation,
+                format!("{} expects a number, got {}", filter, value.kind()),
+            )
+        })
+}
+
+/// Formats a number of bytes as a human readable size like Jinja2's
+/// `filesizeformat(binary=false)`, e.g. `1.5 MB` or with `binary` `1.4 MiB`.
+///
+/// Decimal units are powers of 1000 and binary units powers of 1024.  Sizes
+/// below one unit are whole `Bytes`, larger ones have one decimal rounded half
+/// to even like Python.
+fn filesizeformat(value: &Value, binary: Option<bool>, kwargs: Kwargs) -> Result<String, Error> {
+    const SI_PREFIXES: [&str; 8] = ["kB", "MB", "GB", "TB", "PB", "EB", "ZB", "YB"];
+    const BINARY_PREFIXES: [&str; 8] = ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB", "ZiB", "YiB"];
+    let binary = kwargs.get::<Option<bool>>("binary")?.or(binary).unwrap_or(false);
+    kwargs.assert_all_used()?;
+    let bytes = float_arg("filesizeformat", value)?;
+
+    let (prefixes, base) = if binary {
+        (BINARY_PREFIXES, 1024f64)
+    } else {
+        (SI_PREFIXES, 1000f64)
+    };
+    if bytes == 1.0 {
+        return Ok("1 Byte".to_string());
+    }
+    if bytes < base {
+        return Ok(format!("{} Bytes", bytes as i64));
+    }
+    // The largest prefix is used for everything beyond it.
+    let (mut prefix, mut unit) = (prefixes[0], base * base);
+    for next in &prefixes[1..] {
+        if bytes < unit {
+            break;
+        }
+        prefix = next;
+        unit *= base;
+    }
+    Ok(format!("{:.1} {}", base * bytes / unit, prefix))
+}
+
+/// Formats a number of seconds as a duration, e.g. `{{ 7380 | durationformat }}`
+/// renders `2h 3m`.
+///
+/// The parts are days `d`, hours `h`, minutes `m` and seconds `s`, and parts
+/// that are zero are left out.  With `units` only that many parts are shown,
+/// counted from the largest, and the last one is rounded half up, so
+/// `durationformat(units=1)` turns `5400` into `2h`.  Fractions of a second
+/// are rounded the same way.
+fn durationformat(value: &Value, units: Option<usize>, kwargs: Kwargs) -> Result<String, Error> {
+    const UNITS: [(&str, f64); 4] = [("d", 86400.0), ("h", 3600.0), ("m", 60.0), ("s", 1.0)];
+    let units = kwargs.get::<Option<usize>>("units")?.or(units);
+    kwargs.assert_all_used()?;
+    if units == Some(0) {
+        return Err(Error::new(
+            ErrorKind::InvalidOperation,
+            "durationformat needs at least one unit",
+        ));
+    }
+    let seconds = float_arg("durationformat", value)?;
+
+    // Rounding can carry into a larger part, so the first part is found on
+    // the rounded value.
+    let first_unit = |total: f64| UNITS.iter().position(|&(_, size)| total >= size).unwrap_or(3);
+    let last_unit = |total: f64| match units {
+        Some(units) => first_unit(total).saturating_add(units.saturating_sub(1)).min(3),
+        None => 3,
+    };
+    let precision = UNITS[last_unit(seconds.abs())].1;
+    let mut rest = (seconds.abs() / precision).round() * precision;
+    let (first, last) = (first_unit(rest), last_unit(rest));
+
+    let mut parts = Vec::new();
+    for &(name, size) in &UNITS[first..=last] {
+        let count = (rest / size).floor();
+        rest -= count * size;
+        if count > 0.0 {
+            parts.push(format!("{}{}", count as u64, name));
+        }
+    }
+    if parts.is_empty() {
+        return Ok("0s".to_string());
+    }
+    let sign = if seconds < 0.0 { "-" } else { "" };
+    Ok(format!("{}{}", sign, parts.join(" ")))
+}
+
+/// Shortens a string to `length` characters ending in `end`, like Jinja2's
+/// `truncate(length=255, killwords=false, end='...', leeway=5)`.
+///
+/// Strings at most `leeway` characters longer than `length` are kept as they
+/// are.  Otherwise the string is cut at the last space that leaves room for
+/// `end`, or mid-word if `killwords` is set.  Lengths count characters, so
+/// multibyte characters are never split.  `leeway` is only accepted as
+/// keyword argument.
+fn truncate(
+    value: String,
+    length: Option<usize>,
+    killwords: Option<bool>,
+    end: Option<String>,
+    kwargs: Kwargs,
+) -> Result<String, Error> {
+    let length = kwargs.get::<Option<usize>>("length")?.or(length).unwrap_or(255);
+    let killwords = kwargs.get::<Option<bool>>("killwords")?.or(killwords).unwrap_or(false);
+    let end = kwargs.get::<Option<String>>("end")?.or(end);
+    let end = end.as_deref().unwrap_or("...");
+    let leeway = kwargs.get::<Option<usize>>("leeway")?.unwrap_or(5);
+    kwargs.assert_all_used()?;
+
+    let end_len = end.chars().count();
+    if length < end_len {
+        return Err(Error::new(
+            ErrorKind::InvalidOperation,
+            format!(
+                "truncate length must be at least {}, the length of the end, got {}",
+                end_len, length
+            ),
+        ));
+    }
+    if value.chars().count() <= length.saturating_add(leeway) {
+        return Ok(value);
+    }
+
+    let cut = value
+        .char_indices()
+        .nth(length - end_len)
+        .map_or(value.len(), |(idx, _)| idx);
+    let mut kept = &value[..cut];
+    if !killwords {
+        if let Some(space) = kept.rfind(' ') {
+            kept = &kept[..space];
+        }
+    }
+    Ok(format!("{}{}", kept, end))
+}
+
+/// Wraps every line of a string to `width` characters, like Jinja2's
+/// `wordwrap(width=79, break_long_words=true, wrapstring='\n', break_on_hyphens=true)`.
+///
+/// Lines break at whitespace, which is dropped at the breaks, and after
+/// hyphens inside words unless `break_on_hyphens` is false.  Words longer than
+/// `width` are split unless `break_long_words` is false.  The wrapped lines
+/// are joined with `wrapstring`.  Widths count characters, so multibyte
+/// characters are never split.  `break_on_hyphens` is only accepted as
+/// keyword argument.
+fn wordwrap(
+    value: String,
+    width: Option<usize>,
+    break_long_words: Option<bool>,
+    wrapstring: Option<String>,
+    kwargs: Kwargs,
+) -> Result<String, Error> {
+    let width = kwargs.get::<Option<usize>>("width")?.or(width).unwrap_or(79);
+    let break_long_words = kwargs
+        .get::<Option<bool>>("break_long_words")?
+        .or(break_long_words)
+        .unwrap_or(true);
+    let wrapstring = kwargs.get::<Option<String>>("wrapstring")?.or(wrapstring);
+    let wrapstring = wrapstring.as_deref().unwrap_or("\n");
+    let break_on_hyphens = kwargs.get::<Option<bool>>("break_on_hyphens")?.unwrap_or(true);
+    kwargs.assert_all_used()?;
+
+    if width == 0 {
+        return Err(Error::new(
+            ErrorKind::InvalidOperation,
+            "wordwrap width must be at least 1",
+        ));
+    }
+
+    let lines: Vec<String> = value
+        .lines()
+        .map(|line| wrap_line(line, width, break_long_words, break_on_hyphens).join(wrapstring))
+        .collect();
+    Ok(lines.join(wrapstring))
+}
+
+/// Splits a line into words and the whitespace between them.
+///
+/// With `break_on_hyphens` words are also split after hyphens that join two
+/// alphanumeric characters, e.g. `well-known` into `well-` and `known`.
+fn wrap_chunks(line: &str, break_on_hyphens: bool) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    let mut prev: Option<char> = None;
+    let mut chars = line.char_indices().peekable();
+    while let Some((idx, c)) = chars.next() {
+        let next = chars.peek().map(|&(_, next)| next);
+        if let Some(prev) = prev {
+            if prev.is_ascii_whitespace() != c.is_ascii_whitespace() {
+                chunks.push(&line[start..idx]);
+                start = idx;
+            }
+        }
+        let at_hyphen = break_on_hyphens
+            && c == '-'
+            && prev.is_some_and(char::is_alphanumeric)
+            && next.is_some_and(char::is_alphanumeric);
+        if at_hyphen {
+            let after = idx + c.len_utf8();
+            chunks.push(&line[start..after]);
+            start = after;
+        }
+        prev = Some(c);
+    }
+    if start < line.len() {
+        chunks.push(&line[start..]);
+    }
+    chunks
+}
+
+/// Wraps one line greedily like Python's `textwrap.wrap`.
+fn wrap_line(line: &str, width: usize, break_long_words: bool, break_on_hyphens: bool) -> Vec<String> {
+    let is_space = |chunk: &str| chunk.chars().all(|c| c.is_ascii_whitespace());
+    let mut chunks: Vec<String> = wrap_chunks(line, break_on_hyphens)
+        .into_iter()
+        .rev()
+        .map(str::to_owned)
+        .collect();
+    let mut lines = Vec::new();
+
+    while !chunks.is_empty() {
+        // Whitespace is dropped at the start of every line but the first.
+        if !lines.is_empty() && chunks.last().is_some_and(|chunk| is_space(chunk)) {
+            chunks.pop();
+        }
+
+        let mut current: Vec<String> = Vec::new();
+        let mut current_len = 0;
+        while let Some(chunk) = chunks.last() {
+            let chunk_len = chunk.chars().count();
+            if current_len + chunk_len > width {
+                break;
+            }
+            current_len += chunk_len;
+            current.extend(chunks.pop());
+        }
+
+        if let Some(chunk) = chunks.last_mut() {
+            if chunk.chars().count() > width {
+                if break_long_words {
+                    let space_left = width.saturating_sub(current_len).max(1);
+                    let split = chunk
+                        .char_indices()
+                        .nth(space_left)
+                        .map_or(chunk.len(), |(idx, _)| idx);
+                    let rest = chunk.split_off(split);
+                    current.push(std::mem::replace(chunk, rest));
+                } else if current.is_empty() {
+                    current.extend(chunks.pop());
+                }
+            }
+        }
+
+        if current.last().is_some_and(|chunk| is_space(chunk)) {
+            current.pop();
+        }
+        if !current.is_empty() {
+            lines.push(current.concat());
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use minijinja::{context, AutoEscape, Environment, Value};
+
+    use super::register_filters;
+
+    fn render(autoescape: AutoEscape, source: &str, ctx: Value) -> String {
+        let mut env = Environment::new();
+        register_filters(&mut env);
+        env.set_auto_escape_callback(move |_| autoescape);
+        env.render_str(source, ctx).unwrap()
+    }
+
+    #[test]
+    fn join_plain_items() {
+        let ctx = context!(items => vec![Value::from("a"), Value::from(1), Value::from("<b>")]);
+        let source = "{{ items | join(', ') }}|{{ none | join }}|{{ 'xy' | join('-') }}";
+        assert_eq!(render(AutoEscape::None, source, ctx), "a, 1, <b>||x-y");
+    }
+
+    #[test]
+    fn join_keeps_safe_items_under_html_autoescape() {
+        let items = vec![Value::from_safe_string("<b>".into()), Value::from("<i>")];
+        let source = "{{ items | join('<br>') }}";
+        assert_eq!(
+            render(AutoEscape::Html, source, context!(items)),
+            "<b>&lt;br&gt;&lt;i&gt;"
+        );
+        assert_eq!(
+            render(AutoEscape::Html, "{{ ['<i>'] | join }}", context!()),
+            "&lt;i&gt;"
+        );
+    }
+
+    #[test]
+    fn durationformat_units() {
+        let source = "{{ 93784 | durationformat }}|{{ 5400 | durationformat(units=1) }}|\
+            {{ 59 | durationformat(18446744073709551615) }}|{{ 59 | durationformat(units=2) }}";
+        assert_eq!(
+            render(AutoEscape::None, source, context!()),
+            "1d 2h 3m 4s|2h|59s|59s"
+        );
+
+        let mut env = Environment::new();
+        register_filters(&mut env);
+        let err = env.render_str("{{ 60 | durationformat(units=0) }}", ()).unwrap_err();
+        assert_eq!(err.detail(), Some("durationformat needs at least one unit"));
+    }
+
+    #[test]
+    fn booleans_and_none_print_in_lowercase() {
+        let ctx = context!(flag => true, off => false, nothing => ());
+        let source = "{{ flag }} {{ nothing }}|{{ flag | string }}|{{ 'x' ~ off }}|\
+            {{ [flag, nothing] }}|{{ {'a': off} }}|{{ flag | sql_quote }}";
+        assert_eq!(
+            render(AutoEscape::None, source, ctx),
+            "true none|true|xfalse|[true, none]|{\"a\": false}|'true'"
+        );
+    }
+}
@@ -0,0 +1,182 @@
+use std::sync::Arc;
+
+use minijinja::{
+    default_auto_escape_callback, escape_formatter, AutoEscape, Environment, Error, Output,
+    UndefinedBehavior, Value,
+};
+
+use crate::timeout;
+
+/// A host provided function escaping a string, replacing the built-in escaping.
+pub(crate) type Escaper = Arc<dyn Fn(&str) -> Result<String, Error> + Send + Sync>;
+
+/// Picks the auto escape mode of a template from its name.
+pub(crate) type AutoEscapeCallback = Arc<dyn Fn(&str) -> AutoEscape + Send + Sync>;
+
+/// The auto escape mode that escapes output for use inside a JSON string.
+pub(crate) const JSON_ESCAPE: AutoEscape = AutoEscape::Custom("json");
+
+/// Controls how floats are printed by `{{ ... }}`.
+///
+/// The defaults match MiniJinja: the shortest representation that round-trips,
+/// with `.0` appended to integral values.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct FloatFormat {
+    /// The number of decimal places, `None` uses the shortest representation.
+    pub precision: Option<usize>,
+    /// Whether trailing zeros after the decimal point are kept.  Without them
+    /// `1.0` prints as `1` and, with a precision of 3, `0.100` as `0.1`.
+    pub trailing_zeros: bool,
+}
+
+impl Default for FloatFormat {
+    fn default() -> Self {
+        FloatFormat {
+            precision: None,
+            trailing_zeros: true,
+        }
+    }
+}
+
+impl FloatFormat {
+    /// Creates the format from its FFI representation, a negative precision
+    /// keeps the shortest representation.
+    pub fn from_ffi(precision: i32, trailing_zeros: bool) -> FloatFormat {
+        FloatFormat {
+            precision: usize::try_from(precision).ok(),
+            trailing_zeros,
+        }
+    }
+
+    /// Formats a value if it is a finite float.
+    fn format_value(&self, value: &Value) -> Option<String> {
+        if *self == FloatFormat::default() || !value.is_number() || value.is_integer() {
+            return None;
+        }
+        let num = f64::try_from(value.clone()).ok()?;
+        num.is_finite().then(|| self.format(num))
+    }
+
+    /// Formats a finite float.
+    fn format(&self, value: f64) -> String {
+        let mut num = match self.precision {
+            Some(precision) => format!("{:.*}", precision, value),
+            None => value.to_string(),
+        };
+        if self.trailing_zeros {
+            if self.precision.is_none() && !num.contains('.') {
+                num.push_str(".0");
+            }
+        } else if num.contains('.') {
+            num.truncate(num.trim_end_matches('0').trim_end_matches('.').len());
+        }
+        num
+    }
+}
+
+/// Picks the auto escape mode from the extension of a template name.
+///
+/// This follows MiniJinja's default, so `.html`, `.htm` and `.xml` templates
+/// are escaped for HTML, and additionally escapes `.json` templates for JSON
+/// strings.  A final `.j2`, `.jinja` or `.jinja2` extension is ignored.
+pub(crate) fn auto_escape_by_extension(name: &str) -> AutoEscape {
+    let base = [".j2", ".jinja2", ".jinja"]
+        .iter()
+        .find_map(|ext| name.strip_suffix(ext))
+        .unwrap_or(name);
+    if base.ends_with(".json") {
+        return JSON_ESCAPE;
+    }
+    default_auto_escape_callback(name)
+}
+
+/// Names an auto escape mode like the `autoescape_mode` option, e.g. `html`,
+/// or by the name of a custom mode.
+pub(crate) fn auto_escape_name(auto_escape: AutoEscape) -> &'static str {
+    match auto_escape {
+        AutoEscape::None => "none",
+        AutoEscape::Html => "html",
+        AutoEscape::Custom(mode) => mode,
+        _ => "unknown",
+    }
+}
+
+/// Writes `s` escaped for use inside a JSON string literal.
+///
+/// Besides quotes, backslashes and control characters this escapes the line
+/// and paragraph separators, so the output is also a valid JavaScript string.
+fn write_json_escaped(out: &mut Output, s: &str) -> Result<(), Error> {
+    let mut start = 0;
+    for (idx, c) in s.char_indices() {
+        let escaped = match c {
+            '"' => "\\\"",
+            '\\' => "\\\\",
+            '\n' => "\\n",
+            '\r' => "\\r",
+            '\t' => "\\t",
+            '\u{8}' => "\\b",
+            '\u{c}' => "\\f",
+            '\u{0}'..='\u{1f}' | '\u{2028}' | '\u{2029}' => "",
+            _ => continue,
+        };
+        out.write_str(&s[start..idx])?;
+        if escaped.is_empty() {
+            write!(out, "\\u{:04x}", c as u32)?;
+        } else {
+            out.write_str(escaped)?;
+        }
+        start = idx + c.len_utf8();
+    }
+    out.write_str(&s[start..])?;
+    Ok(())
+}
+
+/// Installs the formatter used to print values.
+///
+/// It applies the float format, prints undefined values as
+/// `undefined_placeholder` if given and implements the `JSON_ESCAPE` mode, all
+/// other values are printed by the default formatter.  With an `escaper` every
+/// value that is not safe is escaped by it whenever auto escaping is active,
+/// whatever the mode.  It also stops a render that ran past its timeout.
+pub(crate) fn install_formatter(
+    env: &mut Environment<'static>,
+    float_format: FloatFormat,
+    undefined_placeholder: Option<String>,
+    escaper: Option<Escaper>,
+) {
+    env.set_formatter(move |out, state, value| {
+        timeout::check_deadline()?;
+        // Strict and semi-strict undefined values still fail in `escape_formatter`.
+        let placeholder = undefined_placeholder.as_deref().filter(|_| {
+            value.is_undefined()
+                && matches!(
+                    state.undefined_behavior(),
+                    UndefinedBehavior::Lenient | UndefinedBehavior::Chainable
+                )
+        });
+        // The placeholder is configuration, not data, so it is not escaped.
+        let placeholder = placeholder.map(|text| Value::from_safe_string(text.to_owned()));
+        let value = placeholder.as_ref().unwrap_or(value);
+        if let Some(num) = float_format.format_value(value) {
+            return Ok(out.write_str(&num)?);
+        }
+        // Undefined values are left to `escape_formatter`, which knows whether
+        // they print as nothing or fail.
+        if let Some(escaper) = escaper.as_ref().filter(|_| {
+            state.auto_escape() != AutoEscape::None && !value.is_safe() && !value.is_undefined()
+        }) {
+            let escaped = match value.as_str() {
+                Some(s) => escaper(s)?,
+                None => escaper(&value.to_string())?,
+            };
+            return Ok(out.write_str(&escaped)?);
+        }
+        if state.auto_escape() == JSON_ESCAPE && !value.is_safe() {
+            return match value.as_str() {
+                Some(s) => write_json_escaped(out, s),
+                None => write_json_escaped(out, &value.to_string()),
+            };
+        }
+        escape_formatter(out, state, value)
+    });
+}
@@ -0,0 +1,337 @@
+use std::fmt::Write;
+
+use minijinja::machinery::{self, Instruction, Instructions};
+use minijinja::{Environment, Error, ErrorKind, Template, Value};
+use serde_json::json;
+
+use crate::error::{error_column, source_column};
+
+/// The tags that open a block which has to be closed with `end<tag>`.
+const BLOCK_TAGS: [&str; 9] =
+    ["for", "if", "block", "macro", "call", "filter", "with", "autoescape", "set"];
+
+/// Shows the source line of an error with a caret under its column, e.g.
+///
+/// ```text
+/// 2 | {{ a | }}
+///   |        ^
+/// ```
+pub(crate) fn source_excerpt(source: &str, err: &Error) -> Option<String> {
+    let line_no = err.line()?;
+    let line = source.split('\n').nth(line_no.checked_sub(1)?)?;
+    let line = line.strip_suffix('\r').unwrap_or(line);
+    let gutter = line_no.to_string();
+    let mut excerpt = format!("{} | {}", gutter, line);
+    if let Some(column) = error_column(err) {
+        // Tabs are kept so the caret lines up however they are displayed.
+        let indent: String = line
+            .chars()
+            .take(column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let _ = write!(excerpt, "\n{} | {}^", " ".repeat(gutter.len()), indent);
+    }
+    Some(excerpt)
+}
+
+/// Suggests how to fix a syntax error whose MiniJinja message only says what
+/// was unexpected, e.g. which block an `unexpected end of input` leaves open.
+///
+/// Blocks are matched with the default delimiters.
+pub(crate) fn syntax_hint(source: &str, err: &Error) -> Option<String> {
+    let detail = err.detail()?;
+    if let Some(name) = detail.strip_prefix("unknown statement ") {
+        return unknown_statement_hint(source, err, name);
+    }
+    match detail {
+        "unexpected end of input, expected end of variable block" => {
+            Some("close the expression with `}}`".to_string())
+        }
+        "unexpected end of input, expected end of block" => {
+            let (tag, line) = open_blocks(source, source.len()).pop()?;
+            Some(format!(
+                "`{{% {} %}}` on line {} is never closed with `{{% end{} %}}`",
+                tag, line, tag
+            ))
+        }
+        "unexpected end of comment" => Some("close the comment with `#}`".to_string()),
+        "unexpected end of string" => Some("close the string with a matching quote".to_string()),
+        "unexpected end of raw block" => {
+            Some("close the raw block with `{% endraw %}`".to_string())
+        }
+        _ => None,
+    }
+}
+
+fn unknown_statement_hint(source: &str, err: &Error, name: &str) -> Option<String> {
+    match name {
+        "else" => {
+            return Some(
+                "`{% else %}` must be inside an `{% if %}` or `{% for %}` block".to_string(),
+            )
+        }
+        "elif" => return Some("`{% elif %}` must be inside an `{% if %}` block".to_string()),
+        _ => {}
+    }
+    let closed = name.strip_prefix("end").filter(|tag| BLOCK_TAGS.contains(tag))?;
+    Some(match open_blocks(source, err.range()?.start).pop() {
+        Some((tag, line)) => format!(
+            "the `{{% {} %}}` on line {} has to be closed with `{{% end{} %}}` first",
+            tag, line, tag
+        ),
+        None => format!("there is no open `{{% {} %}}` for `{{% {} %}}` to close", closed, name),
+    })
+}
+
+/// Lists the blocks still open before byte `until`, innermost last, with the
+/// line each was opened on.
+///
+/// This is a plain scan of the tags, so it is only meant for hints: comments
+/// and raw blocks are skipped but a `%}` inside a string ends a tag early.
+fn open_blocks(source: &str, until: usize) -> Vec<(&str, usize)> {
+    let mut open = Vec::new();
+    let mut pos = 0;
+    while let Some(offset) = source[pos..].find('{') {
+        let start = pos + offset;
+        let rest = &source[start..];
+        let end_marker = if rest.starts_with("{%") {
+            "%}"
+        } else if rest.starts_with("{#") {
+            "#}"
+        } else {
+            pos = start + 1;
+            continue;
+        };
+        let Some(len) = rest[2..].find(end_marker) else {
+            break;
+        };
+        let end = start + 2 + len + 2;
+        if end > until {
+            break;
+        }
+        pos = end;
+        if end_marker == "#}" {
+            continue;
+        }
+
+        let body =
+            rest[2..2 + len].trim_matches(|c: char| c == '-' || c == '+' || c.is_whitespace());
+        let tag = body.split_whitespace().next().unwrap_or("");
+        if tag == "raw" {
+            match source[pos..].find("endraw") {
+                Some(offset) => pos += offset,
+                None => break,
+            }
+        } else if let Some(closed) = tag.strip_prefix("end") {
+            if open.last().is_some_and(|(tag, _)| *tag == closed) {
+                open.pop();
+            }
+        } else if BLOCK_TAGS.contains(&tag) && !(tag == "set" && body.contains('=')) {
+            let line = source[..start].matches('\n').count() + 1;
+            open.push((tag, line));
+        }
+    }
+    open
+}
+
+/// A step of an attribute or item access like `.name`, `[0]` or `['key']`.
+enum Segment<'a> {
+    Attr(&'a str),
+    Index(i64),
+    Key(&'a str),
+}
+
+impl Segment<'_> {
+    fn name(&self) -> String {
+        match self {
+            Segment::Attr(name) | Segment::Key(name) => name.to_string(),
+            Segment::Index(index) => index.to_string(),
+        }
+    }
+
+    fn lookup(&self, value: &Value) -> Option<Value> {
+        match self {
+            Segment::Attr(name) => value.get_attr(name),
+            Segment::Index(index) => value.get_item(&Value::from(*index)),
+            Segment::Key(key) => value.get_item(&Value::from(*key)),
+        }
+        .ok()
+        .filter(|value| !value.is_undefined())
+    }
+}
+
+/// An access path like `rows[0].name` at the end of a template source.
+struct AccessPath<'a> {
+    /// The byte offset the path starts at.
+    start: usize,
+    variable: &'a str,
+    /// The segments after the variable, each with its byte offset.
+    segments: Vec<(usize, Segment<'a>)>,
+}
+
+/// Splits the access path `text` ends with into its variable and segments,
+/// `None` if `text` does not end with one.
+fn trailing_path(text: &str) -> Option<AccessPath<'_>> {
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+    let mut segments = Vec::new();
+    let mut end = text.len();
+    loop {
+        let rest = &text[..end];
+        if let Some(inner) = rest.strip_suffix(']') {
+            let open = inner.rfind('[')?;
+            let key = &inner[open + 1..];
+            let segment = match key.parse() {
+                Ok(index) => Segment::Index(index),
+                Err(_) => {
+                    let quote = key.chars().next().filter(|c| *c == '\'' || *c == '"')?;
+                    let key = key.strip_prefix(quote)?.strip_suffix(quote)?;
+                    if key.contains(quote) {
+                        return None;
+                    }
+                    Segment::Key(key)
+                }
+            };
+            segments.push((open, segment));
+            end = open;
+            continue;
+        }
+        let start = rest.trim_end_matches(is_ident).len();
+        let ident = &rest[start..];
+        if ident.is_empty() || ident.starts_with(|c: char| c.is_numeric()) {
+            return None;
+        }
+        match rest[..start].strip_suffix('.') {
+            Some(before) => {
+                segments.push((before.len(), Segment::Attr(ident)));
+                end = before.len();
+            }
+            None => {
+                segments.reverse();
+                return Some(AccessPath {
+                    start,
+                    variable: ident,
+                    segments,
+                });
+            }
+        }
+    }
+}
+
+/// Describes which access made a render fail with an undefined value.
+///
+/// The error only points at the expression, so its path is looked up in
+/// `ctx` and the globals of `env` to find the first step that is undefined.
+/// Returns an object with the failing `expression`, the `path` of the value
+/// the access was made on and the `name` of the attribute, index or key it did
+/// not have.  A variable that is itself undefined has a `null` path.  When the
+/// path starts at a variable set in the template, such as a loop variable, only
+/// the expression is known and `path` and `name` are `null`.
+pub(crate) fn undefined_access(
+    env: &Environment,
+    err: &Error,
+    ctx: &Value,
+) -> Option<serde_json::Value> {
+    if err.kind() != ErrorKind::UndefinedError {
+        return None;
+    }
+    let tmpl = env.get_template(err.name()?).ok()?;
+    let source = tmpl.source().get(..err.range()?.end)?;
+    let AccessPath {
+        start,
+        variable,
+        segments,
+    } = trailing_path(source)?;
+    let expression = &source[start..];
+
+    let value = ctx
+        .get_attr(variable)
+        .ok()
+        .filter(|value| !value.is_undefined())
+        .or_else(|| env.globals().find(|(name, _)| *name == variable).map(|(_, value)| value));
+    let Some(mut value) = value else {
+        let name = tmpl.undeclared_variables(false).contains(variable).then_some(variable);
+        return Some(json!({"expression": expression, "path": null, "name": name}));
+    };
+    for (offset, segment) in &segments {
+        match segment.lookup(&value) {
+            Some(next) => value = next,
+            None => {
+                return Some(json!({
+                    "expression": expression,
+                    "path": &source[start..*offset],
+                    "name": segment.name(),
+                }))
+            }
+        }
+    }
+    None
+}
+
+/// Lists the filters, tests and functions a template calls that `env` does not
+/// have, each once with the line and column of its first use, e.g.
+/// `Unknown filter 'uppr' at line 1, column 6`.
+///
+/// MiniJinja only looks them up when a call runs, so this finds names on
+/// branches a render would not take.  A called name the template does not
+/// define and that is not a global would have to come from the context, which
+/// cannot hold functions, so it is reported as an unknown function.
+pub(crate) fn unknown_callables(env: &Environment, tmpl: &Template) -> Vec<String> {
+    let compiled = machinery::get_compiled_template(tmpl);
+    let undeclared = tmpl.undeclared_variables(false);
+    let state = tmpl.new_state();
+    let mut seen = Vec::new();
+    let mut unknown = Vec::new();
+    let all = std::iter::once(&compiled.instructions).chain(compiled.blocks.values());
+    for instructions in all {
+        for idx in 0..instructions.len() as u32 {
+            let (kind, name) = match instructions.get(idx) {
+                Some(Instruction::ApplyFilter(name, ..)) => ("filter", *name),
+                Some(Instruction::PerformTest(name, ..)) => ("test", *name),
+                Some(Instruction::CallFunction(name, _)) => ("function", *name),
+                _ => continue,
+            };
+            if seen.contains(&(kind, name)) {
+                continue;
+            }
+            seen.push((kind, name));
+            // Called without arguments a filter or test fails on its missing
+            // value before it runs, so this only tells whether it exists.
+            let known = match kind {
+                "filter" => state
+                    .apply_filter(name, &[])
+                    .map_or_else(|err| err.kind() != ErrorKind::UnknownFilter, |_| true),
+                "test" => state
+                    .perform_test(name, &[])
+                    .map_or_else(|err| err.kind() != ErrorKind::UnknownTest, |_| true),
+                _ => {
+                    name == "super"
+                        || !undeclared.contains(name)
+                        || env.globals().any(|(global, _)| global == name)
+                }
+            };
+            if !known {
+                unknown.push(format!(
+                    "Unknown {} '{}' at {}",
+                    kind,
+                    name,
+                    instruction_location(tmpl.source(), instructions, idx)
+                ));
+            }
+        }
+    }
+    unknown
+}
+
+/// Describes where the source of an instruction starts, like
+/// `error::error_location`.
+fn instruction_location(source: &str, instructions: &Instructions, idx: u32) -> String {
+    let span = instructions.get_span(idx);
+    let line = match span {
+        Some(span) => span.start_line as usize,
+        None => instructions.get_line(idx).unwrap_or(1),
+    };
+    match span.and_then(|span| source_column(source, span.start_offset as usize)) {
+        Some(column) => format!("line {}, column {}", line, column),
+        None => format!("line {}", line),
+    }
+}
@@ -1,10 +1,13 @@
-use std::slice;
-use std::str;
+use std::borrow::Cow;
 use std::path::Path;
-use std::ffi::{c_char, CString, CStr};
+use std::ffi::{c_char, c_void, CString, CStr};
 
-use minijinja::{Environment, Value, AutoEscape};
+use chrono::{DateTime, Utc};
+use minijinja::{AutoEscape, Environment, ErrorKind, Value};
 use std::error::Error;
+use std::io::{BufWriter, Write};
+use std::ops::ControlFlow;
+use std::sync::{Arc, Mutex, PoisonError};
 
 #[repr(C)]
 pub enum ResultCString {
@@ -12,20 +15,138 @@ pub enum ResultCString {
     Err(*mut c_char),
 }
 
+#[cfg(test)]
+impl ResultCString {
+    /// Takes the output or error message out of the result and frees it.
+    pub(crate) fn into_result(self) -> Result<String, String> {
+        let take = |ptr: *mut c_char| unsafe { CString::from_raw(ptr) }.into_string().unwrap();
+        match self {
+            ResultCString::Ok(ptr) => Ok(take(ptr)),
+            ResultCString::Err(ptr) => Err(take(ptr)),
+        }
+    }
+}
+
+/// A length-carrying result, the output may contain null bytes.
+///
+/// `ptr` points to `len` bytes which are not null terminated.  On success
+/// they hold the output, on failure the UTF-8 error message.  Must be
+/// released with `free_result_bytes`.
+#[repr(C)]
+pub struct ResultBytes {
+    pub is_ok: bool,
+    pub ptr: *mut u8,
+    pub len: usize,
+}
+
+impl ResultBytes {
+    fn new(is_ok: bool, data: Vec<u8>) -> ResultBytes {
+        let data = Box::into_raw(data.into_boxed_slice());
+        ResultBytes {
+            is_ok,
+            ptr: data as *mut u8,
+            len: data.len(),
+        }
+    }
+}
+
+/// Result types that can report an error from an FFI entry point.
+trait FfiResult {
+    fn from_error(code: i32, msg: String) -> Self;
+}
+
+impl FfiResult for ResultCString {
+    fn from_error(code: i32, msg: String) -> Self {
+        error_result(code, msg)
+    }
+}
+
+impl FfiResult for ResultBytes {
+    fn from_error(code: i32, msg: String) -> Self {
+        error::set_last_error_code(code);
+        ResultBytes::new(false, msg.into_bytes())
+    }
+}
+
 /// Converts a C char pointer and length to a Rust &str.
 ///
+/// Returns from the enclosing function with an error result naming `$what`
+/// if the pointer is null or the bytes are not valid UTF-8.  The error code
+/// for invalid UTF-8 defaults to `MJ_ERROR_INVALID_ARGUMENT`.
+///
 /// # Safety
 ///
-/// This macro uses `str::from_utf8_unchecked` which is safe here because:
-/// - DuckDB's VARCHAR type guarantees valid UTF-8 encoding
-/// - All string data passed from DuckDB C++ layer is already validated as UTF-8
-/// - The caller must ensure the pointer is valid and the length is correct
+/// The caller must ensure the pointer is valid and the length is correct.
 macro_rules! make_str {
-    ($s:expr, $len:expr) => {
-        unsafe { str::from_utf8_unchecked(slice::from_raw_parts($s as *const u8, $len)) }
+    ($s:expr, $len:expr, $what:expr) => {
+        make_str!($s, $len, $what, $crate::error::MJ_ERROR_INVALID_ARGUMENT)
+    };
+    ($s:expr, $len:expr, $what:expr, $code:expr) => {
+        match unsafe { $crate::str_from_raw($s, $len) } {
+            Ok(s) => s,
+            Err($crate::RawStrError::Null) => {
+                return $crate::FfiResult::from_error(
+                    $crate::error::MJ_ERROR_INVALID_ARGUMENT,
+                    format!("{} is null", $what),
+                );
+            }
+            Err($crate::RawStrError::InvalidUtf8) => {
+                return $crate::FfiResult::from_error(
+                    $code,
+                    format!("{} is not valid UTF-8", $what),
+                );
+            }
+        }
+    };
+}
+
+/// Borrows a byte buffer like `make_str!` without requiring it to be UTF-8.
+macro_rules! make_bytes {
+    ($s:expr, $len:expr, $what:expr) => {
+        if $s.is_null() {
+            return $crate::FfiResult::from_error(
+                $crate::error::MJ_ERROR_INVALID_ARGUMENT,
+                format!("{} is null", $what),
+            );
+        } else {
+            unsafe { std::slice::from_raw_parts($s as *const u8, $len) }
+        }
     };
 }
 
+mod arrow;
+mod buffer;
+mod builder;
+mod bundle;
+mod cache;
+mod callback;
+mod casing;
+mod compression;
+mod config;
+mod context;
+mod datetime;
+mod dynamic;
+mod env;
+mod error;
+mod filters;
+mod formatter;
+mod hints;
+mod os_env;
+mod prepared;
+mod random;
+mod syntax;
+mod timeout;
+
+// Re-exported for the benchmarks.
+pub use prepared::{mj_prepare, mj_prepared_free, mj_prepared_render, PreparedTemplate};
+
+use callback::{CallbackWriter, RowCallback, UserData, WriteCallback};
+use compression::TemplateCompression;
+use context::{json_to_value, ContextError, ContextFormat, Conversion};
+use error::RenderError;
+use formatter::FloatFormat;
+use syntax::SyntaxOptions;
+
 fn c_char_to_string(ptr: *const c_char) -> Option<String> {
     if ptr.is_null() {
         return None;
@@ -33,150 +154,2543 @@ fn c_char_to_string(ptr: *const c_char) -> Option<String> {
     unsafe { CStr::from_ptr(ptr).to_str().ok().map(|s| s.to_owned()) }
 }
 
+/// Why a pointer and length could not be read as a string.
+enum RawStrError {
+    Null,
+    InvalidUtf8,
+}
 
-fn json_to_value(json_str: &str) -> Result<Value, serde_json::Error> {
-    let json: Value = serde_json::from_str(json_str)?;
-    Ok(Value::from(json))
+/// Converts a C char pointer and length to a Rust &str, checking that it is UTF-8.
+///
+/// A null pointer is an error even for a length of zero.
+///
+/// # Safety
+/// `ptr` must be null or valid for `len` bytes for the lifetime `'a`.
+unsafe fn str_from_raw<'a>(ptr: *const c_char, len: usize) -> Result<&'a str, RawStrError> {
+    if ptr.is_null() {
+        return Err(RawStrError::Null);
+    }
+    std::str::from_utf8(std::slice::from_raw_parts(ptr as *const u8, len))
+        .map_err(|_| RawStrError::InvalidUtf8)
 }
 
-#[no_mangle]
-pub extern "C" fn render_template(
-    template_source: *const c_char,
-    template_source_len: usize,
-    json_context: *const c_char,
-    json_context_len: usize,
+/// Borrows `count` elements starting at `ptr`, an empty count allows null.
+///
+/// Returns `None` if `ptr` is null while `count` is not zero.
+///
+/// # Safety
+/// `ptr` must be null or valid for `count` elements for the lifetime `'a`.
+unsafe fn slice_from_raw<'a, T>(ptr: *const T, count: usize) -> Option<&'a [T]> {
+    if count == 0 {
+        Some(&[])
+    } else if ptr.is_null() {
+        None
+    } else {
+        Some(std::slice::from_raw_parts(ptr, count))
+    }
+}
+
+/// Like `c_char_to_string` but also treats an empty string as absent.
+fn non_empty_c_char_to_string(ptr: *const c_char) -> Option<String> {
+    c_char_to_string(ptr).filter(|s| !s.is_empty())
+}
+
+/// Creates an error result and records `code` for `mj_last_error_code`.
+fn error_result(code: i32, msg: String) -> ResultCString {
+    error::set_last_error_code(code);
+    let c_msg = CString::new(msg).unwrap_or_else(|_| {
+        CString::new("MiniJinja render error (message contained null byte)").unwrap()
+    });
+    ResultCString::Err(c_msg.into_raw())
+}
+
+/// Creates a success result, or an error result if `output` contains a null byte.
+///
+/// A null byte would silently truncate the output for C callers, so it is
+/// reported instead.
+fn ok_result(output: String) -> ResultCString {
+    match CString::new(output) {
+        Ok(s) => {
+            error::set_last_error_code(error::MJ_OK);
+            ResultCString::Ok(s.into_raw())
+        }
+        Err(err) => error_result(
+            error::MJ_ERROR_INVALID_OPERATION,
+            format!(
+                "MiniJinja render error: output contains a null byte at position {}",
+                err.nul_position()
+            ),
+        ),
+    }
+}
+
+/// Formats a render error including its chain of causes.
+pub(crate) fn render_error_message(err: &minijinja::Error) -> String {
+    let mut msg = format!("MiniJinja render error: {:?}\n", err);
+
+    // Add source chain
+    let mut source = err.source();
+    while let Some(s) = source {
+        msg.push_str(&format!("Caused by: {}\n", s));
+        source = s.source();
+    }
+
+    msg
+}
+
+fn render_result(result: Result<String, minijinja::Error>) -> ResultCString {
+    match result {
+        Ok(output) => ok_result(output),
+        Err(err) => error_result(error::error_code(&err), render_error_message(&err)),
+    }
+}
+
+/// Options shared by the stateless render entry points.
+///
+/// The defaults match the defaults of the SQL functions.
+struct RenderOptions {
+    template_path: Option<String>,
+    autoescape: bool,
+    undefined_behavior: Option<String>,
+    autoescape_on: Vec<String>,
+    syntax: SyntaxOptions,
+    trim_blocks: bool,
+    lstrip_blocks: bool,
+    keep_trailing_newline: bool,
+    fuel: i64,
+    recursion_limit: usize,
+    null_as_undefined: bool,
+    pycompat: bool,
+    float_format: FloatFormat,
+    autoescape_mode: Option<AutoescapeMode>,
+    now: Option<DateTime<Utc>>,
+    random_seed: Option<u64>,
+    allow_env: bool,
+    context_format: ContextFormat,
+    debug: bool,
+    /// The wall-clock limit of a render in milliseconds, 0 means unlimited.
+    timeout_ms: u64,
+    /// What undefined values print as if the undefined behavior allows
+    /// printing them, `None` prints nothing.
+    undefined_placeholder: Option<String>,
+    /// The variable a context that is not an object is bound to, `None`
+    /// rejects such contexts.
+    root_variable: Option<String>,
+    /// Whether a context object that repeats a key is rejected instead of
+    /// keeping the last value.
+    strict_context: bool,
+    /// Whether context integers beyond the safe range of JavaScript numbers
+    /// become strings.
+    big_integers_as_strings: bool,
+    /// How the template source is compressed.  Only configs can set this, the
+    /// per-option functions take their source as text.
+    template_compression: TemplateCompression,
+}
+
+/// An explicitly requested autoescape mode.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum AutoescapeMode {
+    /// Escapes every value for HTML.
+    Html,
+    /// Escapes every value for use inside a JSON string literal.
+    Json,
+    /// Never escapes.
+    None,
+    /// Picks the escaping from the template name: HTML for the extensions in
+    /// `autoescape_on`, or see `auto_escape_by_extension` if none are given.
+    Auto,
+}
+
+impl AutoescapeMode {
+    fn parse(mode: &str) -> Result<AutoescapeMode, String> {
+        match mode {
+            "html" => Ok(AutoescapeMode::Html),
+            "json" => Ok(AutoescapeMode::Json),
+            "none" => Ok(AutoescapeMode::None),
+            "auto" => Ok(AutoescapeMode::Auto),
+            _ => Err(format!(
+                "Invalid autoescape mode '{}': expected html, json, none or auto",
+                mode
+            )),
+        }
+    }
+
+    /// Returns the auto escape callback implementing this mode.
+    fn callback(self, autoescape_on: &[String]) -> formatter::AutoEscapeCallback {
+        match self {
+            AutoescapeMode::Html => Arc::new(|_| AutoEscape::Html),
+            AutoescapeMode::Json => Arc::new(|_| formatter::JSON_ESCAPE),
+            AutoescapeMode::None => Arc::new(|_| AutoEscape::None),
+            AutoescapeMode::Auto if !autoescape_on.is_empty() => {
+                let exts = autoescape_on.to_vec();
+                Arc::new(move |name| {
+                    if exts.iter().any(|ext| name.ends_with(ext)) {
+                        return AutoEscape::Html;
+                    }
+                    AutoEscape::None
+                })
+            }
+            AutoescapeMode::Auto => Arc::new(formatter::auto_escape_by_extension),
+        }
+    }
+
+    /// Installs the auto escape callback implementing this mode.
+    fn install(self, env: &mut Environment<'static>, autoescape_on: &[String]) {
+        let callback = self.callback(autoescape_on);
+        env.set_auto_escape_callback(move |name| callback(name));
+    }
+}
+
+/// Reads the render options from their FFI representation.
+#[allow(clippy::too_many_arguments)]
+unsafe fn render_options_from_ffi(
     template_path: *const c_char,
     autoescape: bool,
     undefined_behavior: *const c_char,
     autoescape_on: *const *const c_char,
     autoescape_on_count: usize,
-) -> ResultCString {
-    let template_str = make_str!(template_source, template_source_len);
-    let json_str = make_str!(json_context, json_context_len);
-    let mut template_path_str = c_char_to_string(template_path);
-    if let Some(ref mut path) = template_path_str {
-        if path.is_empty() {
-            template_path_str = None;
+    block_start: *const c_char,
+    block_end: *const c_char,
+    variable_start: *const c_char,
+    variable_end: *const c_char,
+    comment_start: *const c_char,
+    comment_end: *const c_char,
+    line_statement_prefix: *const c_char,
+    line_comment_prefix: *const c_char,
+    trim_blocks: bool,
+    lstrip_blocks: bool,
+    keep_trailing_newline: bool,
+    fuel: i64,
+    recursion_limit: usize,
+    null_as_undefined: bool,
+    pycompat: bool,
+    float_precision: i32,
+    float_trailing_zeros: bool,
+    autoescape_mode: *const c_char,
+    now: *const c_char,
+    random_seed: i64,
+    allow_env: bool,
+    context_format: *const c_char,
+    debug: bool,
+) -> Result<RenderOptions, String> {
+    let mut syntax = SyntaxOptions::from_delimiters(
+        (non_empty_c_char_to_string(block_start), non_empty_c_char_to_string(block_end)),
+        (non_empty_c_char_to_string(variable_start), non_empty_c_char_to_string(variable_end)),
+        (non_empty_c_char_to_string(comment_start), non_empty_c_char_to_string(comment_end)),
+    )?;
+    syntax.line_statement_prefix = non_empty_c_char_to_string(line_statement_prefix);
+    syntax.line_comment_prefix = non_empty_c_char_to_string(line_comment_prefix);
+
+    let autoescape_on = slice_from_raw(autoescape_on, autoescape_on_count)
+        .ok_or_else(|| "autoescape_on is null".to_string())?
+        .iter()
+        .filter(|ptr| !ptr.is_null())
+        .map(|&ptr| CStr::from_ptr(ptr).to_string_lossy().into_owned())
+        .collect();
+
+    Ok(RenderOptions {
+        template_path: non_empty_c_char_to_string(template_path),
+        autoescape,
+        undefined_behavior: non_empty_c_char_to_string(undefined_behavior),
+        autoescape_on,
+        syntax,
+        trim_blocks,
+        lstrip_blocks,
+        keep_trailing_newline,
+        fuel,
+        recursion_limit,
+        null_as_undefined,
+        pycompat,
+        float_format: FloatFormat::from_ffi(float_precision, float_trailing_zeros),
+        autoescape_mode: non_empty_c_char_to_string(autoescape_mode)
+            .map(|mode| AutoescapeMode::parse(&mode))
+            .transpose()?,
+        now: non_empty_c_char_to_string(now)
+            .map(|now| datetime::parse_frozen_now(&now))
+            .transpose()?,
+        random_seed: u64::try_from(random_seed).ok(),
+        allow_env,
+        context_format: non_empty_c_char_to_string(context_format)
+            .map(|format| ContextFormat::parse(&format))
+            .transpose()?
+            .unwrap_or_default(),
+        debug,
+        // Options added later are only read from the JSON config.
+        ..RenderOptions::default()
+    })
+}
+
+/// Defines a render export taking the positional options of `render_template`.
+///
+/// The options follow the arguments of the export itself and are read with
+/// `render_options_from_ffi` into `$options`, a `Result<RenderOptions, String>`.
+/// The positional options are frozen so the C ABI stays stable, options added
+/// later are only read from the JSON config of the `_with_config` exports.
+macro_rules! with_positional_options {
+    (
+        $(#[$attr:meta])*
+        fn $name:ident($($arg:ident: $ty:ty),* $(,)?) -> $ret:ty, $options:ident => $body:block
+    ) => {
+        $(#[$attr])*
+        #[no_mangle]
+        pub unsafe extern "C" fn $name(
+            $($arg: $ty,)*
+            template_path: *const c_char,
+            autoescape: bool,
+            undefined_behavior: *const c_char,
+            autoescape_on: *const *const c_char,
+            autoescape_on_count: usize,
+            block_start: *const c_char,
+            block_end: *const c_char,
+            variable_start: *const c_char,
+            variable_end: *const c_char,
+            comment_start: *const c_char,
+            comment_end: *const c_char,
+            line_statement_prefix: *const c_char,
+            line_comment_prefix: *const c_char,
+            trim_blocks: bool,
+            lstrip_blocks: bool,
+            keep_trailing_newline: bool,
+            fuel: i64,
+            recursion_limit: usize,
+            null_as_undefined: bool,
+            pycompat: bool,
+            float_precision: i32,
+            float_trailing_zeros: bool,
+            autoescape_mode: *const c_char,
+            now: *const c_char,
+            random_seed: i64,
+            allow_env: bool,
+            context_format: *const c_char,
+            debug: bool,
+        ) -> $ret {
+            let $options = render_options_from_ffi(
+                template_path,
+                autoescape,
+                undefined_behavior,
+                autoescape_on,
+                autoescape_on_count,
+                block_start,
+                block_end,
+                variable_start,
+                variable_end,
+                comment_start,
+                comment_end,
+                line_statement_prefix,
+                line_comment_prefix,
+                trim_blocks,
+                lstrip_blocks,
+                keep_trailing_newline,
+                fuel,
+                recursion_limit,
+                null_as_undefined,
+                pycompat,
+                float_precision,
+                float_trailing_zeros,
+                autoescape_mode,
+                now,
+                random_seed,
+                allow_env,
+                context_format,
+                debug,
+            );
+            $body
         }
-    }
+    };
+}
 
-    let mut undefined_behavior_str = c_char_to_string(undefined_behavior);
-    if let Some(ref mut behavior) = undefined_behavior_str {
-        if behavior.is_empty() {
-            undefined_behavior_str = None;
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            template_path: None,
+            autoescape: true,
+            undefined_behavior: None,
+            autoescape_on: Vec::new(),
+            syntax: SyntaxOptions::default(),
+            trim_blocks: false,
+            lstrip_blocks: false,
+            keep_trailing_newline: false,
+            fuel: 0,
+            recursion_limit: 0,
+            null_as_undefined: false,
+            pycompat: false,
+            float_format: FloatFormat::default(),
+            autoescape_mode: None,
+            now: None,
+            random_seed: None,
+            allow_env: false,
+            context_format: ContextFormat::Json,
+            debug: false,
+            timeout_ms: 0,
+            undefined_placeholder: None,
+            root_variable: None,
+            strict_context: false,
+            big_integers_as_strings: false,
+            template_compression: TemplateCompression::None,
         }
     }
+}
 
+impl RenderOptions {
+    /// How the values of render contexts and globals are converted.
+    fn conversion(&self) -> Conversion {
+        Conversion {
+            null_as_undefined: self.null_as_undefined,
+            big_integers_as_strings: self.big_integers_as_strings,
+        }
+    }
 
-    // Parse JSON context
-    let ctx = match json_to_value(json_str) {
-        Ok(c) => c,
-        Err(e) => {
-            let msg = CString::new(format!("Invalid JSON: {}", e)).unwrap();
-            return ResultCString::Err(msg.into_raw());
+    /// Parses a render context in the configured format.
+    ///
+    /// A context that is not an object is bound to `root_variable`, see
+    /// `context::bind_root`.  With `strict_context` objects that repeat a key
+    /// are rejected.
+    fn parse_context(&self, document: &[u8]) -> Result<Value, ContextError> {
+        if self.strict_context {
+            self.context_format.check_duplicate_keys(document)?;
         }
-    };
+        let ctx = self.context_format.to_value(document, self.conversion())?;
+        context::bind_root(ctx, self.root_variable.as_deref())
+    }
 
-    // Build environment
-    let mut env = Environment::new();
-    // Configure undefined behavior
-    if let Some(behavior) = undefined_behavior_str {
-        match behavior.as_str() {
-            "strict" => {
-                env.set_undefined_behavior(minijinja::UndefinedBehavior::Strict);
+    /// Builds a fresh environment configured with these options.
+    fn build_environment(&self) -> Result<Environment<'static>, String> {
+        let mut env = Environment::new();
+        filters::register_filters(&mut env);
+        datetime::register_now(&mut env, self.now);
+        random::register_random(&mut env, self.random_seed);
+        os_env::register_env(&mut env, self.allow_env);
+        timeout::register_range(&mut env);
+        // Configure undefined behavior
+        if let Some(behavior) = &self.undefined_behavior {
+            match behavior.as_str() {
+                "strict" => {
+                    env.set_undefined_behavior(minijinja::UndefinedBehavior::Strict);
+                }
+                "semistrict" => {
+                    env.set_undefined_behavior(minijinja::UndefinedBehavior::SemiStrict);
+                }
+                "chainable" => {
+                    env.set_undefined_behavior(minijinja::UndefinedBehavior::Chainable);
+                }
+                "lenient" => {
+                    env.set_undefined_behavior(minijinja::UndefinedBehavior::Lenient);
+                }
+                _ => {
+                    env.set_undefined_behavior(minijinja::UndefinedBehavior::Lenient);
+                }
             }
-            "semistrict" => {
-                env.set_undefined_behavior(minijinja::UndefinedBehavior::SemiStrict);
-            }
-            "chainable" => {
-                env.set_undefined_behavior(minijinja::UndefinedBehavior::Chainable);
-            }
-            "lenient" => {
-                env.set_undefined_behavior(minijinja::UndefinedBehavior::Lenient);
-            }
-            _ => {
-                env.set_undefined_behavior(minijinja::UndefinedBehavior::Lenient);
+        }
+
+        // Configure custom syntax
+        if !self.syntax.is_default() {
+            env.set_syntax(self.syntax.build()?);
+        }
+
+        // Attach the template source to errors only when asked to, as error
+        // messages may end up in places where the source must not be shown.
+        env.set_debug(self.debug);
+
+        // Configure whitespace handling
+        env.set_trim_blocks(self.trim_blocks);
+        env.set_lstrip_blocks(self.lstrip_blocks);
+        env.set_keep_trailing_newline(self.keep_trailing_newline);
+
+        // Bound the number of instructions executed
+        if self.fuel > 0 {
+            env.set_fuel(Some(self.fuel as u64));
+        }
+
+        // Bound the recursion depth
+        if self.recursion_limit > 0 {
+            env.set_recursion_limit(self.recursion_limit);
+        }
+
+        // Load templates if template_path is provided
+        if let Some(ref path) = self.template_path {
+            let p = Path::new(path);
+            if p.is_dir() {
+                env.set_loader(minijinja::path_loader(p));
+            } else if p.is_file() {
+                if let Some(parent) = p.parent() {
+                    env.set_loader(minijinja::path_loader(parent));
+                }
             }
         }
+
+        // Emulate the Python methods Jinja2 templates commonly call
+        if self.pycompat {
+            env.set_unknown_method_callback(minijinja_contrib::pycompat::unknown_method_callback);
+        }
+
+        // Configure how values are printed
+        formatter::install_formatter(
+            &mut env,
+            self.float_format,
+            self.undefined_placeholder.clone(),
+            None,
+        );
+
+        env.set_auto_escape_callback(|_| AutoEscape::Html);
+
+        // Configure autoescape
+        if let Some(mode) = self.autoescape_mode {
+            mode.install(&mut env, &self.autoescape_on);
+        } else if !self.autoescape && self.autoescape_on.is_empty() {
+            env.set_auto_escape_callback(|_| AutoEscape::None);
+        } else if self.autoescape_on.is_empty() && self.template_path.is_some() {
+            // Files are escaped by their own name, like Jinja2's `select_autoescape`.
+            env.set_auto_escape_callback(formatter::auto_escape_by_extension);
+        } else if !self.autoescape_on.is_empty() {
+            let exts = self.autoescape_on.clone();
+            env.set_auto_escape_callback(move |name| {
+                if exts.iter().any(|ext| name.ends_with(ext)) {
+                    return AutoEscape::Html;
+                }
+
+                AutoEscape::None
+            });
+        }
+
+        Ok(env)
     }
 
-    // Load templates if template_path is provided
-    if let Some(ref path) = template_path_str {
-        let p = Path::new(path);
-        if p.is_dir() {
-            env.set_loader(minijinja::path_loader(p));
-        } else if p.is_file() {
-            if let Some(parent) = p.parent() {
-                env.set_loader(minijinja::path_loader(parent));
-            }
+    /// Adds the configured limit to out of fuel errors.
+    fn map_fuel_error(&self, err: minijinja::Error) -> minijinja::Error {
+        map_fuel_error(self.fuel, err)
+    }
+
+    /// Renders a template (or, with a template path, a template name) with `ctx`.
+    ///
+    /// Inline templates are compiled through the template cache, templates
+    /// loaded from a path are read again on every render.
+    fn render(&self, template_str: &str, ctx: &Value) -> Result<String, RenderError> {
+        let (env, name) = self.render_target(template_str)?;
+        let ctx = timeout::bound_context(self.timeout_ms, ctx);
+        let fuel = self.fuel;
+        timeout::run_with_timeout(self.timeout_ms, move || {
+            env.get_template(&name)
+                .and_then(|tmpl| tmpl.render(ctx))
+                .map_err(|err| map_fuel_error(fuel, err))
+        })
+    }
+
+    /// Like `render`, but an error that happens while rendering comes with
+    /// the output written before it and, for undefined values, the access
+    /// that failed.
+    ///
+    /// A render that timed out returns what it wrote until the deadline.
+    fn render_capturing(&self, template_str: &str, ctx: &Value) -> Result<String, FailedRender> {
+        let (env, name) = self.render_target(template_str)?;
+        let buffer = SharedBuffer::default();
+        let mut writer = buffer.clone();
+        let render_env = env.clone();
+        let render_ctx = timeout::bound_context(self.timeout_ms, ctx);
+        let fuel = self.fuel;
+        let result = timeout::run_with_timeout(self.timeout_ms, move || {
+            render_env
+                .get_template(&name)
+                .and_then(|tmpl| tmpl.render_to_write(render_ctx, &mut writer).map(|_| ()))
+                .map_err(|err| map_fuel_error(fuel, err))
+        });
+        let output = buffer.take();
+        match result {
+            Ok(()) => Ok(output),
+            Err(error) => Err(FailedRender {
+                undefined: match &error {
+                    RenderError::Template(err) => hints::undefined_access(&env, err, ctx),
+                    _ => None,
+                },
+                error,
+                partial_output: Some(output),
+            }),
         }
     }
 
-    env.set_auto_escape_callback(|_| AutoEscape::Html);
+    /// Returns the environment and the name of the template to render.
+    fn render_target(
+        &self,
+        template_str: &str,
+    ) -> Result<(Arc<Environment<'static>>, String), RenderError> {
+        if self.template_path.is_some() {
+            let env = self
+                .build_environment()
+                .map_err(RenderError::InvalidOptions)?;
+            Ok((Arc::new(env), template_str.to_owned()))
+        } else {
+            // Inline template only
+            let env = cache::compiled_template(self, template_str)?;
+            Ok((env, cache::CACHED_TEMPLATE_NAME.to_owned()))
+        }
+    }
 
-    // Configure autoescape
-    if !autoescape && autoescape_on_count == 0 {
-        env.set_auto_escape_callback(|_| AutoEscape::None);
-    } else {
-        if autoescape_on_count > 0 {
-            unsafe {
-                let slice: &[*const c_char] = std::slice::from_raw_parts(autoescape_on, autoescape_on_count);
-                let exts: Vec<String> = slice
-                    .iter()
-                    .filter_map(|&ptr| (!ptr.is_null()).then(|| CStr::from_ptr(ptr).to_string_lossy().into_owned()))
-                    .collect();
-
-                env.set_auto_escape_callback(move |name| {
-                    if exts.iter().any(|ext| name.ends_with(ext)) {
-                        return AutoEscape::Html;
-                    }
+    /// Renders a template with `ctx` and reports the undefined variables.
+    ///
+    /// Returns a JSON object with the rendered `output`, the sorted list of
+    /// `undefined` variable names, see `undefined_variables`, and the size of
+    /// the output in `output_bytes`.  With a fuel limit `fuel_consumed` holds
+    /// the instructions the render executed.
+    fn render_debug(&self, template_str: &str, ctx: &Value) -> ResultCString {
+        let (env, name) = match self.render_target(template_str) {
+            Ok(target) => target,
+            Err(RenderError::Template(err)) => return render_result(Err(err)),
+            Err(err) => return error_result(err.code(), err.to_string()),
+        };
+        let tmpl = match env.get_template(&name) {
+            Ok(tmpl) => tmpl,
+            Err(err) => return render_result(Err(err)),
+        };
 
-                    AutoEscape::None
-                });
-            }
+        let render_env = env.clone();
+        let render_ctx = timeout::bound_context(self.timeout_ms, ctx);
+        let fuel = self.fuel;
+        let rendered = timeout::run_with_timeout(self.timeout_ms, move || {
+            let tmpl = render_env.get_template(&name)?;
+            let (output, state) = tmpl
+                .render_and_return_state(render_ctx)
+                .map_err(|err| map_fuel_error(fuel, err))?;
+            Ok((output, state.fuel_levels().map(|(consumed, _)| consumed)))
+        });
+        let (output, fuel_consumed) = match rendered {
+            Ok(rendered) => rendered,
+            Err(RenderError::Template(err)) => return render_result(Err(err)),
+            Err(err) => return error_result(err.code(), err.to_string()),
+        };
+        let output_bytes = output.len();
+        let mut report = serde_json::json!({
+            "output": output,
+            "undefined": undefined_variables(&env, &tmpl, ctx),
+            "output_bytes": output_bytes,
+        });
+        if let Some(fuel_consumed) = fuel_consumed {
+            report["fuel_consumed"] = fuel_consumed.into();
+        }
+        ok_result(report.to_string())
+    }
+
+    /// Renders a template with `ctx` and returns the output as JSON.
+    ///
+    /// A template consisting of a single `{{ ... }}` expression is evaluated
+    /// and the resulting value is serialized with its type intact, any other
+    /// template is rendered and returned as a JSON string.  Templates are
+    /// looked up like in `render`, so inline ones go through the template
+    /// cache and loaded ones render under their own name.
+    fn render_json(&self, template_str: &str, ctx: &Value) -> ResultCString {
+        let (env, name) = match self.render_target(template_str) {
+            Ok(target) => target,
+            Err(RenderError::Template(err)) => return json_result(Err(err)),
+            Err(err) => return error_result(err.code(), err.to_string()),
+        };
+
+        let variable_delimiters = self.syntax.variable_delimiters();
+        let (start, end) = (variable_delimiters.0.to_owned(), variable_delimiters.1.to_owned());
+        let ctx = timeout::bound_context(self.timeout_ms, ctx);
+        let fuel = self.fuel;
+        let value = timeout::run_with_timeout(self.timeout_ms, move || {
+            let tmpl = env.get_template(&name)?;
+            // The template compiled, so its only tag compiles as an expression.
+            let value = match single_expression(tmpl.source(), (&start, &end)) {
+                Some(expr) => env.compile_expression(expr)?.eval(&ctx).or_else(|err| {
+                    // The expression has no name or line, the same error
+                    // from rendering the template points at its source.
+                    tmpl.render(&ctx).and(Err(err))
+                }),
+                None => tmpl.render(ctx).map(Value::from),
+            };
+            value.map_err(|err| map_fuel_error(fuel, err))
+        });
+
+        match value {
+            Ok(value) => json_result(Ok(value)),
+            Err(RenderError::Template(err)) => json_result(Err(err)),
+            Err(err) => error_result(err.code(), err.to_string()),
         }
     }
+}
 
-    // Render
-    let result = if template_path_str.is_some() {
-        env.get_template(template_str).and_then(|tmpl| tmpl.render(&ctx))
+/// Adds the fuel limit to out of fuel errors.
+fn map_fuel_error(fuel: i64, err: minijinja::Error) -> minijinja::Error {
+    if err.kind() == ErrorKind::OutOfFuel {
+        minijinja::Error::new(
+            ErrorKind::OutOfFuel,
+            format!("template exceeded the fuel limit of {} instructions", fuel),
+        )
+        .with_source(err)
     } else {
-        // Inline template only
-        env.render_str(template_str, &ctx)
+        err
+    }
+}
+
+/// A buffer the render writes into that stays readable by the caller.
+///
+/// A render on a worker thread is abandoned at its timeout, so its output is
+/// shared instead of being returned.
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl SharedBuffer {
+    /// Takes the output written so far.
+    fn take(&self) -> String {
+        let bytes = std::mem::take(&mut *self.0.lock().unwrap_or_else(PoisonError::into_inner));
+        String::from_utf8(bytes)
+            .unwrap_or_else(|err| String::from_utf8_lossy(err.as_bytes()).into_owned())
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Serializes a template value as the JSON result of an FFI call.
+fn json_result(value: Result<Value, minijinja::Error>) -> ResultCString {
+    match value {
+        Ok(value) => match serde_json::to_string(&value) {
+            Ok(json) => ok_result(json),
+            Err(e) => error_result(
+                error::MJ_ERROR_SERIALIZATION,
+                format!("Could not serialize the result as JSON: {}", e),
+            ),
+        },
+        Err(err) => render_result(Err(err)),
+    }
+}
+
+/// Renders one template against every context of a JSON array.
+///
+/// The template is compiled once.  Each element of the output array is either
+/// `{"ok": output}` or `{"err": message}`, so a failing context does not fail
+/// the others.  The keys of the `globals` object are globals of every render,
+/// which a context overrides by having the same key.
+fn render_batch(
+    options: &RenderOptions,
+    template_str: &str,
+    globals: Option<&str>,
+    contexts: &str,
+) -> ResultCString {
+    let rows = match render_rows(options, template_str, globals, contexts) {
+        Ok(rows) => rows,
+        Err((code, msg)) => return error_result(code, msg),
     };
+    let results: Vec<serde_json::Value> = rows
+        .into_iter()
+        .map(|rendered| match rendered {
+            Ok(output) => serde_json::json!({ "ok": output }),
+            Err(msg) => serde_json::json!({ "err": msg }),
+        })
+        .collect();
 
-    // Return
-    match result {
-        Ok(output) => {
-            let s = CString::new(output).unwrap();
-            ResultCString::Ok(s.into_raw())
+    match serde_json::to_string(&results) {
+        Ok(json) => ok_result(json),
+        Err(e) => error_result(
+            error::MJ_ERROR_SERIALIZATION,
+            format!("Could not serialize the results as JSON: {}", e),
+        ),
+    }
+}
+
+/// Renders one template against every context of a JSON array like
+/// `render_batch`, returning the output or error message of each context.
+///
+/// Fails with the `MJ_ERROR_*` code and message of an error that affects all
+/// contexts, such as a template that does not compile.
+fn render_rows(
+    options: &RenderOptions,
+    template_str: &str,
+    globals: Option<&str>,
+    contexts: &str,
+) -> Result<Vec<Result<String, String>>, (i32, String)> {
+    let mut rows = Vec::new();
+    for_each_row(options, template_str, globals, contexts, |_, row| {
+        rows.push(row);
+        ControlFlow::Continue(())
+    })?;
+    Ok(rows)
+}
+
+/// Renders one template against every context of a JSON array and hands the
+/// result of each context to `on_row` with its index as soon as it is
+/// rendered, stopping early once `on_row` breaks.
+///
+/// Fails like `render_rows`.
+fn for_each_row(
+    options: &RenderOptions,
+    template_str: &str,
+    globals: Option<&str>,
+    contexts: &str,
+    mut on_row: impl FnMut(usize, Result<String, String>) -> ControlFlow<()>,
+) -> Result<(), (i32, String)> {
+    let contexts = match serde_json::from_str::<serde_json::Value>(contexts) {
+        Ok(serde_json::Value::Array(contexts)) => contexts,
+        Ok(_) => {
+            return Err((
+                error::MJ_ERROR_INVALID_CONTEXT,
+                "Invalid JSON: expected an array of contexts".to_string(),
+            ))
         }
-         Err(err) => {
-        let mut msg = format!("MiniJinja render error: {:?}\n", err);
+        Err(e) => return Err((error::MJ_ERROR_INVALID_CONTEXT, format!("Invalid JSON: {}", e))),
+    };
+
+    let mut env = options
+        .build_environment()
+        .map_err(|msg| (error::MJ_ERROR_INVALID_ARGUMENT, msg))?;
+    if let Some(globals) = globals {
+        let globals = match serde_json::from_str::<serde_json::Value>(globals) {
+            Ok(serde_json::Value::Object(globals)) => globals,
+            Ok(_) => {
+                return Err((
+                    error::MJ_ERROR_INVALID_CONTEXT,
+                    "Invalid JSON: expected an object of globals".to_string(),
+                ))
+            }
+            Err(e) => {
+                return Err((error::MJ_ERROR_INVALID_CONTEXT, format!("Invalid JSON: {}", e)))
+            }
+        };
+        for (name, value) in globals {
+            let value = context::json_value_to_value(value, options.conversion())
+                .map_err(|e| (error::MJ_ERROR_INVALID_CONTEXT, e.to_string()))?;
+            env.add_global(name, value);
+        }
+    }
+    let tmpl = env
+        .template_from_str(template_str)
+        .map_err(|err| (error::error_code(&err), render_error_message(&err)))?;
 
-        // Add source chain
-        let mut source = err.source();
-        while let Some(s) = source {
-            msg.push_str(&format!("Caused by: {}\n", s));
-            source = s.source();
+    for (index, ctx) in contexts.into_iter().enumerate() {
+        let row = context::json_value_to_value(ctx, options.conversion())
+            .and_then(|ctx| context::bind_root(ctx, options.root_variable.as_deref()))
+            .map_err(|e| e.to_string())
+            .and_then(|ctx| {
+                let ctx = timeout::bound_context(options.timeout_ms, &ctx);
+                timeout::run_inline_with_timeout(options.timeout_ms, || {
+                    tmpl.render(ctx).map_err(|err| options.map_fuel_error(err))
+                })
+                .map_err(|err| err.to_string())
+            });
+        if on_row(index, row).is_break() {
+            break;
         }
+    }
+    Ok(())
+}
 
-        let c_msg = CString::new(msg).unwrap_or_else(|_| {
-            CString::new("MiniJinja render error (message contained null byte)").unwrap()
+/// Size of the chunks handed to a `WriteCallback`.
+const STREAM_CHUNK_SIZE: usize = 8192;
+
+/// Renders a template and streams the output to `callback` in chunks.
+fn render_stream(
+    options: &RenderOptions,
+    template_str: &str,
+    ctx: &Value,
+    callback: WriteCallback,
+    user_data: UserData,
+) -> ResultCString {
+    let (env, name) = match options.render_target(template_str) {
+        Ok(target) => target,
+        Err(RenderError::Template(err)) => return render_result(Err(err)),
+        Err(err) => return error_result(err.code(), err.to_string()),
+    };
+    let tmpl = match env.get_template(&name) {
+        Ok(tmpl) => tmpl,
+        Err(err) => return render_result(Err(err)),
+    };
+
+    let mut writer = BufWriter::with_capacity(
+        STREAM_CHUNK_SIZE,
+        CallbackWriter {
+            callback,
+            user_data,
+            aborted: false,
+        },
+    );
+    // The callback runs on the calling thread, so the timeout is only
+    // checked while rendering.
+    let ctx = timeout::bound_context(options.timeout_ms, ctx);
+    let render = timeout::run_inline_with_timeout(options.timeout_ms, || {
+        tmpl.render_to_write(ctx, &mut writer)
+            .map(|_| ())
+            .map_err(|err| options.map_fuel_error(err))
+    });
+    let result = match render {
+        Ok(()) => Ok(()),
+        Err(RenderError::Template(err)) => Err(err),
+        Err(err) => return error_result(err.code(), err.to_string()),
+    };
+    let result = result
+        .and_then(|_| {
+            writer.flush().map_err(|e| {
+                minijinja::Error::new(ErrorKind::WriteFailure, "could not write the output")
+                    .with_source(e)
+            })
         });
 
-        ResultCString::Err(c_msg.into_raw())
+    match result {
+        Ok(()) => ok_result(String::new()),
+        Err(_) if writer.get_ref().aborted => {
+            error_result(
+                error::MJ_ERROR_IO,
+                "MiniJinja render error: aborted by the write callback".to_string(),
+            )
         }
+        Err(err) => render_result(Err(err)),
     }
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn free_result_cstring(result: ResultCString) {
-    match result {
-        ResultCString::Ok(ptr) | ResultCString::Err(ptr) => {
-            if !ptr.is_null() {
-                let _ = CString::from_raw(ptr);
+/// Returns the variables a template references that are undefined in `ctx`.
+///
+/// The template is analysed statically, so this also finds variables on
+/// branches that were not taken.  Nested lookups such as `user.name` are
+/// reported with the full path up to the first undefined segment and names of
+/// globals (e.g. `range`) are not reported.
+fn undefined_variables(env: &Environment, tmpl: &minijinja::Template, ctx: &Value) -> Vec<String> {
+    let mut undefined: Vec<String> = tmpl
+        .undeclared_variables(true)
+        .into_iter()
+        .filter_map(|path| {
+            let mut segments = path.split('.');
+            let root = segments.next()?;
+            let mut value = ctx.get_attr(root).unwrap_or(Value::UNDEFINED);
+            if value.is_undefined() {
+                if env.globals().any(|(name, _)| name == root) {
+                    return None;
+                }
+                return Some(root.to_string());
             }
+            let mut resolved = root.len();
+            for segment in segments {
+                value = value.get_attr(segment).unwrap_or(Value::UNDEFINED);
+                resolved += 1 + segment.len();
+                if value.is_undefined() {
+                    return Some(path[..resolved].to_string());
+                }
+            }
+            None
+        })
+        .collect();
+    undefined.sort();
+    undefined.dedup();
+    undefined
+}
+
+/// Returns the expression of a template made of a single variable tag.
+///
+/// Whitespace around the tag and whitespace control markers are ignored.
+fn single_expression<'a>(source: &'a str, (start, end): (&str, &str)) -> Option<&'a str> {
+    let inner = source.trim().strip_prefix(start)?.strip_suffix(end)?;
+    let inner = inner.strip_prefix('-').unwrap_or(inner);
+    let inner = inner.strip_suffix('-').unwrap_or(inner);
+    if inner.contains(end) {
+        return None;
+    }
+    Some(inner)
+}
+
+with_positional_options! {
+    /// Renders a Minijinja template from a file or a string with context variables provided as JSON.
+    ///
+    /// # Arguments
+    /// * `template_source` - Either the filename of the template or the template content as a string.
+    /// * `json_context` - JSON string containing context variables.
+    /// * `template_path` - Directory (or file) to load templates from; when set `template_source` is a template name.
+    /// * `autoescape` - Whether HTML autoescaping is enabled.  Templates loaded from
+    ///   `template_path` are escaped by their own name instead, for HTML if it ends
+    ///   in `.html`, `.htm` or `.xml` and for JSON strings if it ends in `.json`.
+    /// * `undefined_behavior` - One of `strict`, `semistrict`, `chainable` or `lenient`.
+    /// * `autoescape_on` - File extensions for which autoescaping is applied.
+    /// * `block_start`, `block_end` - Block delimiters, null or empty keeps `{%` and `%}`.
+    /// * `variable_start`, `variable_end` - Variable delimiters, null or empty keeps `{{` and `}}`.
+    /// * `comment_start`, `comment_end` - Comment delimiters, null or empty keeps `{#` and `#}`.
+    /// * `line_statement_prefix` - Enables line statements (e.g. `#`), null or empty disables them.
+    ///   Enabling this changes how lines starting with the prefix are parsed.
+    /// * `line_comment_prefix` - Enables line comments (e.g. `##`), null or empty disables them.
+    /// * `trim_blocks` - Removes the first newline after a block tag.
+    /// * `lstrip_blocks` - Strips whitespace from the start of a line up to a block tag.
+    /// * `keep_trailing_newline` - Keeps the trailing newline at the end of the template.
+    /// * `fuel` - Maximum number of instructions a render may execute, `0` or negative is unlimited.
+    ///   A render that runs out fails with `MJ_ERROR_OUT_OF_FUEL`.
+    /// * `recursion_limit` - Maximum recursion depth, `0` keeps the MiniJinja default of 500.
+    ///   Values above 500 are capped.
+    /// * `null_as_undefined` - Whether JSON nulls in the context become undefined instead of none.
+    /// * `pycompat` - Enables Python methods such as `dict.items()` and `str.upper()`.
+    /// * `float_precision` - Number of decimal places printed for floats, negative keeps the
+    ///   shortest representation.
+    /// * `float_trailing_zeros` - Whether printed floats keep trailing zeros such as in `1.0`.
+    /// * `autoescape_mode` - `html`, `json`, `none` or `auto` (by template extension), null or
+    ///   empty derives the mode from `autoescape` and `autoescape_on`.
+    /// * `now` - ISO 8601 timestamp or Unix epoch seconds returned by `now()`, null or empty
+    ///   uses the system clock.
+    /// * `random_seed` - Seed of `uuid()`, `random()` and `randrange()`, negative seeds every
+    ///   render randomly.
+    /// * `allow_env` - Whether `env()` may read the environment variables of the process.
+    /// * `context_format` - `json`, `yaml` or `msgpack`, the format of `json_context`.  Null or
+    ///   empty is JSON.  A MessagePack context is read as raw bytes, binary values in it become
+    ///   bytes like blobs.
+    /// * `debug` - Whether error messages show the failing line of the template with the values
+    ///   of the variables in scope.
+    ///
+    /// # Returns
+    /// * `Ok` containing the rendered template, or `Err` with the error message if rendering fails.
+    ///
+    /// The positional options are frozen and shared by the other positional render
+    /// functions, options added later are only available through `render_template_with_config`.
+    ///
+    /// # Safety
+    /// All pointers must be valid for the given lengths and `autoescape_on` must point to
+    /// `autoescape_on_count` C strings.
+    /// Null pointers are reported as errors instead of being dereferenced.
+    fn render_template(
+        template_source: *const c_char,
+        template_source_len: usize,
+        json_context: *const c_char,
+        json_context_len: usize,
+    ) -> ResultCString, options => {
+        let template_str = make_str!(template_source, template_source_len, "template source");
+        let context_bytes = make_bytes!(json_context, json_context_len, "JSON context");
+
+        let options = match options {
+            Ok(options) => options,
+            Err(msg) => return error_result(error::MJ_ERROR_INVALID_ARGUMENT, msg),
+        };
+
+        // Parse the context
+        let ctx = match options.parse_context(context_bytes) {
+            Ok(c) => c,
+            Err(e) => return error_result(error::MJ_ERROR_INVALID_CONTEXT, e.to_string()),
+        };
+
+        match options.render(template_str, &ctx) {
+            Ok(output) => ok_result(output),
+            Err(err) => error_result(err.code(), err.to_string()),
+        }
+    }
+}
+
+/// Reads the options, the template source and the context of a render
+/// configured by `config_json`, see `render_template_with_config`.
+fn inputs_from_config<'a>(
+    template_bytes: &'a [u8],
+    context_bytes: &[u8],
+    config_str: &str,
+) -> Result<(RenderOptions, Cow<'a, str>, Value), (i32, String)> {
+    let (options, template_str) = template_from_config(template_bytes, config_str)?;
+    let ctx = options
+        .parse_context(context_bytes)
+        .map_err(|e| (error::MJ_ERROR_INVALID_CONTEXT, e.to_string()))?;
+    Ok((options, template_str, ctx))
+}
+
+/// Reads the options and the template source like `inputs_from_config`, for
+/// renders that parse their contexts themselves.
+fn template_from_config<'a>(
+    template_bytes: &'a [u8],
+    config_str: &str,
+) -> Result<(RenderOptions, Cow<'a, str>), (i32, String)> {
+    let options = config::render_options_from_json(config_str)
+        .map_err(|msg| (error::MJ_ERROR_INVALID_ARGUMENT, msg))?;
+    let template_str = options
+        .template_compression
+        .decode(template_bytes)
+        .map_err(|msg| (error::MJ_ERROR_INVALID_ARGUMENT, msg))?;
+    Ok((options, template_str))
+}
+
+/// Renders a Minijinja template with its options given as a JSON object.
+///
+/// This behaves like `render_template`, but instead of one parameter per
+/// option the options are read from `config_json`, so new options do not
+/// change the signature.  The keys are named like the options of the SQL
+/// functions, e.g. `{"autoescape": false, "undefined_behavior": "strict",
+/// "variable_delimiters": ["[[", "]]"], "fuel": 1000, "debug": true}`.
+/// Missing or null keys keep their defaults.  Unknown keys, e.g. a misspelt `fuel`,
+/// are rejected with `MJ_ERROR_INVALID_ARGUMENT` unless the config sets
+/// `"ignore_unknown_keys": true`.
+///
+/// The config can also set `"template_compression": "gzip"` to pass the
+/// template source gzip compressed, it is decompressed before parsing.  A source
+/// that decompresses to more than 64 MiB is rejected with `MJ_ERROR_INVALID_ARGUMENT`.
+///
+/// Options added after this function are only available in the config:
+/// * `timeout_ms` - The wall-clock limit of the render in milliseconds, 0 means unlimited.
+///   A render that exceeds it fails with `MJ_ERROR_TIMEOUT`.  With both limits the one
+///   that stops the render first is reported.  The render is stopped at its next output or
+///   iteration over `range` or the context, which includes filters such as `map` that
+///   iterate it, set `fuel` to also bound recursive macros that print nothing.  Renders with
+///   a timeout run on at most 64 worker threads, which stay busy with timed out renders until
+///   they stop, a render finding none free fails with `MJ_ERROR_INVALID_OPERATION`.
+/// * `undefined_placeholder` - What undefined values print as under the `lenient` and
+///   `chainable` undefined behaviors, e.g. `N/A`.  It is printed without escaping, null or
+///   empty prints nothing.
+/// * `root_variable` - The variable a context that is not an object, such as an array or a
+///   scalar, is bound to, e.g. `value`.  If it is null or empty such a context is rejected
+///   with `MJ_ERROR_INVALID_CONTEXT`, a null context renders without variables.
+/// * `strict_context` - Whether a context object that repeats a key is rejected with
+///   `MJ_ERROR_INVALID_CONTEXT` naming the key, instead of keeping its last value.
+/// * `big_integers_as_strings` - Whether context integers beyond the safe range of JavaScript
+///   numbers, `2^53 - 1`, become strings of their digits, so IDs keep their exact digits when
+///   they are serialized, e.g. with `tojson`.
+///
+/// # Arguments
+/// * `template_source` - The template content, compressed as given by `template_compression`.
+/// * `json_context` - The context in the format given by `context_format`, JSON by default.
+/// * `config_json` - A JSON object with the render options.
+///
+/// # Returns
+/// * `Ok` containing the rendered template, or `Err` with the error message if the config
+///   is not a JSON object, holds an invalid value for a known key, the template source
+///   cannot be decompressed, or rendering fails.
+///
+/// # Safety
+/// All pointers must be valid for the given lengths.
+#[no_mangle]
+pub unsafe extern "C" fn render_template_with_config(
+    template_source: *const c_char,
+    template_source_len: usize,
+    json_context: *const c_char,
+    json_context_len: usize,
+    config_json: *const c_char,
+    config_json_len: usize,
+) -> ResultCString {
+    let template_bytes = make_bytes!(template_source, template_source_len, "template source");
+    let context_bytes = make_bytes!(json_context, json_context_len, "JSON context");
+    let config_str = make_str!(config_json, config_json_len, "config");
+
+    let (options, template_str, ctx) =
+        match inputs_from_config(template_bytes, context_bytes, config_str) {
+            Ok(inputs) => inputs,
+            Err((code, msg)) => return error_result(code, msg),
+        };
+    match options.render(&template_str, &ctx) {
+        Ok(output) => ok_result(output),
+        Err(err) => error_result(err.code(), err.to_string()),
+    }
+}
+
+/// Renders a Minijinja template like `render_template_with_config` and returns
+/// the output with its length, like `render_template_bytes`.
+///
+/// # Returns
+/// * A `ResultBytes` holding the output, or the error message if rendering
+///   fails.  It must be released with `free_result_bytes`.
+///
+/// # Safety
+/// All pointers must be valid for the given lengths.
+#[no_mangle]
+pub unsafe extern "C" fn render_template_bytes_with_config(
+    template_source: *const c_char,
+    template_source_len: usize,
+    json_context: *const c_char,
+    json_context_len: usize,
+    config_json: *const c_char,
+    config_json_len: usize,
+) -> ResultBytes {
+    let template_bytes = make_bytes!(template_source, template_source_len, "template source");
+    let context_bytes = make_bytes!(json_context, json_context_len, "JSON context");
+    let config_str = make_str!(config_json, config_json_len, "config");
+
+    let (options, template_str, ctx) =
+        match inputs_from_config(template_bytes, context_bytes, config_str) {
+            Ok(inputs) => inputs,
+            Err((code, msg)) => return ResultBytes::from_error(code, msg),
+        };
+    match options.render(&template_str, &ctx) {
+        Ok(output) => {
+            error::set_last_error_code(error::MJ_OK);
+            ResultBytes::new(true, output.into_bytes())
         }
+        Err(err) => ResultBytes::from_error(err.code(), err.to_string()),
     }
-}
\ No newline at end of file
+}
+
+/// Renders a Minijinja template like `render_template_with_config` and returns
+/// the output as JSON, like `render_template_json`.
+///
+/// # Returns
+/// * `Ok` containing the JSON document, or `Err` with the error message if rendering fails.
+///
+/// # Safety
+/// All pointers must be valid for the given lengths.
+#[no_mangle]
+pub unsafe extern "C" fn render_template_json_with_config(
+    template_source: *const c_char,
+    template_source_len: usize,
+    json_context: *const c_char,
+    json_context_len: usize,
+    config_json: *const c_char,
+    config_json_len: usize,
+) -> ResultCString {
+    let template_bytes = make_bytes!(template_source, template_source_len, "template source");
+    let context_bytes = make_bytes!(json_context, json_context_len, "JSON context");
+    let config_str = make_str!(config_json, config_json_len, "config");
+
+    match inputs_from_config(template_bytes, context_bytes, config_str) {
+        Ok((options, template_str, ctx)) => options.render_json(&template_str, &ctx),
+        Err((code, msg)) => error_result(code, msg),
+    }
+}
+
+/// Renders a template file.
+///
+/// The directory of the file becomes the template directory and the file is
+/// loaded by its own name, so `{% extends %}`, `{% include %}` and
+/// `{% import %}` resolve relative to it.  The template is rendered with the
+/// default options of `render_template`.
+///
+/// # Arguments
+/// * `path` - The path of the template file.
+/// * `json_context` - JSON string containing context variables.
+///
+/// # Returns
+/// * `Ok` containing the rendered template, or `Err` with the error message if
+///   the file does not exist or rendering fails.
+///
+/// # Safety
+/// All pointers must be valid for the given lengths.
+#[no_mangle]
+pub unsafe extern "C" fn render_template_file(
+    path: *const c_char,
+    path_len: usize,
+    json_context: *const c_char,
+    json_context_len: usize,
+) -> ResultCString {
+    let path_str = make_str!(path, path_len, "template path");
+    let context_bytes = make_bytes!(json_context, json_context_len, "JSON context");
+
+    let path = Path::new(path_str);
+    let file_name = match path.file_name().and_then(|name| name.to_str()) {
+        Some(name) if path.is_file() => name,
+        _ => {
+            return error_result(
+                error::MJ_ERROR_TEMPLATE_NOT_FOUND,
+                format!("Template file '{}' does not exist", path_str),
+            )
+        }
+    };
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let options = RenderOptions {
+        template_path: Some(dir.to_string_lossy().into_owned()),
+        ..RenderOptions::default()
+    };
+
+    let ctx = match options.parse_context(context_bytes) {
+        Ok(c) => c,
+        Err(e) => return error_result(error::MJ_ERROR_INVALID_CONTEXT, e.to_string()),
+    };
+
+    match options.render(file_name, &ctx) {
+        Ok(output) => ok_result(output),
+        Err(err) => error_result(err.code(), err.to_string()),
+    }
+}
+
+with_positional_options! {
+    /// Renders a Minijinja template like `render_template` and returns the output with its length.
+    ///
+    /// Unlike `render_template` the output may contain null bytes and callers do
+    /// not need to measure it.  The arguments are the same as for `render_template`.
+    ///
+    /// # Returns
+    /// * A `ResultBytes` holding the output, or the error message if rendering
+    ///   fails.  It must be released with `free_result_bytes`.
+    ///
+    /// # Safety
+    /// All pointers must be valid for the given lengths and `autoescape_on` must point to
+    /// `autoescape_on_count` C strings.
+    fn render_template_bytes(
+        template_source: *const c_char,
+        template_source_len: usize,
+        json_context: *const c_char,
+        json_context_len: usize,
+    ) -> ResultBytes, options => {
+        let template_str = make_str!(template_source, template_source_len, "template source");
+        let context_bytes = make_bytes!(json_context, json_context_len, "JSON context");
+
+        let options = match options {
+            Ok(options) => options,
+            Err(msg) => return ResultBytes::from_error(error::MJ_ERROR_INVALID_ARGUMENT, msg),
+        };
+
+        // Parse the context
+        let ctx = match options.parse_context(context_bytes) {
+            Ok(c) => c,
+            Err(e) => return ResultBytes::from_error(error::MJ_ERROR_INVALID_CONTEXT, e.to_string()),
+        };
+
+        match options.render(template_str, &ctx) {
+            Ok(output) => {
+                error::set_last_error_code(error::MJ_OK);
+                ResultBytes::new(true, output.into_bytes())
+            }
+            Err(err) => ResultBytes::from_error(err.code(), err.to_string()),
+        }
+    }
+}
+
+with_positional_options! {
+    /// Renders a Minijinja template and reports errors as JSON.
+    ///
+    /// This behaves like `render_template`, but the error is a JSON object with
+    /// the fields `code`, `kind`, `message`, `detail`, `template_name`, `line`,
+    /// `column`, `debug_info`, `partial_output` and `undefined` so callers do not
+    /// have to parse the error text.  `debug_info` holds the source snippet of the error, for
+    /// render errors only with `debug` enabled.  `partial_output` is the output
+    /// written before the error, which shows how far a failed render got, and
+    /// `null` if the render did not start, e.g. for errors in the arguments.
+    /// `code` is one of
+    /// the `MJ_ERROR_*` constants.  `kind` is the
+    /// name of the MiniJinja error kind (e.g. `SyntaxError`), or `InvalidOptions`
+    /// and `InvalidContext` for errors in the arguments, or `Timeout` for a render
+    /// that exceeded `timeout_ms`.  Unknown fields are `null`, lines and columns
+    /// start at 1.
+    ///
+    /// For an `UndefinedError` caused by an attribute, item or variable access,
+    /// `undefined` describes it: the failing `expression`, e.g. `order.customer.name`,
+    /// the `path` of the value the access was made on, e.g. `order.customer`, and
+    /// the `name` of the attribute, index or key it lacks, e.g. `name`.  An
+    /// undefined variable has a `null` path.  For paths starting at a variable set
+    /// in the template, such as a loop variable, only the expression is known.
+    ///
+    /// # Returns
+    /// * `Ok` containing the rendered template, or `Err` with the JSON error object.
+    ///
+    /// # Safety
+    /// All pointers must be valid for the given lengths and `autoescape_on` must point to
+    /// `autoescape_on_count` C strings.
+    fn render_template_ex(
+        template_source: *const c_char,
+        template_source_len: usize,
+        json_context: *const c_char,
+        json_context_len: usize,
+    ) -> ResultCString, options => {
+        let template_str = make_str!(template_source, template_source_len, "template source");
+        let context_bytes = make_bytes!(json_context, json_context_len, "JSON context");
+
+        let options = match options {
+            Ok(options) => options,
+            Err(msg) => {
+                return error_report(RenderError::InvalidOptions(msg).into());
+            }
+        };
+
+        // Parse the context
+        let ctx = match options.parse_context(context_bytes) {
+            Ok(c) => c,
+            Err(e) => {
+                return error_report(RenderError::InvalidContext(e.to_string()).into());
+            }
+        };
+
+        match options.render_capturing(template_str, &ctx) {
+            Ok(output) => ok_result(output),
+            Err(failed) => error_report(failed),
+        }
+    }
+}
+
+/// Renders a Minijinja template like `render_template_with_config` and reports
+/// errors as JSON, like `render_template_ex`.
+///
+/// # Returns
+/// * `Ok` containing the rendered template, or `Err` with the JSON error object.
+///
+/// # Safety
+/// All pointers must be valid for the given lengths.
+#[no_mangle]
+pub unsafe extern "C" fn render_template_ex_with_config(
+    template_source: *const c_char,
+    template_source_len: usize,
+    json_context: *const c_char,
+    json_context_len: usize,
+    config_json: *const c_char,
+    config_json_len: usize,
+) -> ResultCString {
+    let template_bytes = make_bytes!(template_source, template_source_len, "template source");
+    let context_bytes = make_bytes!(json_context, json_context_len, "JSON context");
+    let config_str = make_str!(config_json, config_json_len, "config");
+
+    let (options, template_str, ctx) =
+        match inputs_from_config(template_bytes, context_bytes, config_str) {
+            Ok(inputs) => inputs,
+            Err((error::MJ_ERROR_INVALID_CONTEXT, msg)) => {
+                return error_report(RenderError::InvalidContext(msg).into());
+            }
+            Err((_, msg)) => return error_report(RenderError::InvalidOptions(msg).into()),
+        };
+
+    match options.render_capturing(&template_str, &ctx) {
+        Ok(output) => ok_result(output),
+        Err(failed) => error_report(failed),
+    }
+}
+
+/// A failed render of `render_template_ex` with what is known about it.
+struct FailedRender {
+    error: RenderError,
+    /// The output written before the error, `None` if the render did not start.
+    partial_output: Option<String>,
+    /// The access an undefined value error was caused by, see `hints::undefined_access`.
+    undefined: Option<serde_json::Value>,
+}
+
+impl From<RenderError> for FailedRender {
+    fn from(error: RenderError) -> Self {
+        FailedRender {
+            error,
+            partial_output: None,
+            undefined: None,
+        }
+    }
+}
+
+/// Reports a failed render of `render_template_ex` as its JSON error object.
+fn error_report(failed: FailedRender) -> ResultCString {
+    let mut report = failed.error.to_json();
+    report["partial_output"] = failed.partial_output.into();
+    report["undefined"] = failed.undefined.into();
+    error_result(failed.error.code(), report.to_string())
+}
+
+with_positional_options! {
+    /// Renders a Minijinja template and reports the variables that were undefined.
+    ///
+    /// The variables referenced by the template are checked against the context,
+    /// which also catches typos such as `{{ custmer }}` under lenient undefined
+    /// behavior where they would otherwise render as empty strings.  Variables
+    /// only referenced by included or imported templates are not reported.  The
+    /// arguments are the same as for `render_template`.
+    ///
+    /// # Returns
+    /// * `Ok` containing a JSON object `{"output": ..., "undefined": [...],
+    ///   "output_bytes": ...}` with the rendered template, the sorted names of the
+    ///   undefined variables and the length of the output in bytes, or `Err` with
+    ///   the error message if rendering fails.  If `fuel` is positive the object
+    ///   also has `fuel_consumed`, the number of instructions the render executed,
+    ///   to help find expensive templates.
+    ///
+    /// # Safety
+    /// All pointers must be valid for the given lengths and `autoescape_on` must point to
+    /// `autoescape_on_count` C strings.
+    fn render_template_debug(
+        template_source: *const c_char,
+        template_source_len: usize,
+        json_context: *const c_char,
+        json_context_len: usize,
+    ) -> ResultCString, options => {
+        let template_str = make_str!(template_source, template_source_len, "template source");
+        let context_bytes = make_bytes!(json_context, json_context_len, "JSON context");
+
+        let options = match options {
+            Ok(options) => options,
+            Err(msg) => return error_result(error::MJ_ERROR_INVALID_ARGUMENT, msg),
+        };
+
+        // Parse the context
+        let ctx = match options.parse_context(context_bytes) {
+            Ok(c) => c,
+            Err(e) => return error_result(error::MJ_ERROR_INVALID_CONTEXT, e.to_string()),
+        };
+
+        options.render_debug(template_str, &ctx)
+    }
+}
+
+/// Renders a Minijinja template like `render_template_with_config` and reports
+/// the undefined variables, like `render_template_debug`.
+///
+/// # Returns
+/// * `Ok` containing the JSON report of `render_template_debug`, or `Err` with
+///   the error message if rendering fails.
+///
+/// # Safety
+/// All pointers must be valid for the given lengths.
+#[no_mangle]
+pub unsafe extern "C" fn render_template_debug_with_config(
+    template_source: *const c_char,
+    template_source_len: usize,
+    json_context: *const c_char,
+    json_context_len: usize,
+    config_json: *const c_char,
+    config_json_len: usize,
+) -> ResultCString {
+    let template_bytes = make_bytes!(template_source, template_source_len, "template source");
+    let context_bytes = make_bytes!(json_context, json_context_len, "JSON context");
+    let config_str = make_str!(config_json, config_json_len, "config");
+
+    match inputs_from_config(template_bytes, context_bytes, config_str) {
+        Ok((options, template_str, ctx)) => options.render_debug(&template_str, &ctx),
+        Err((code, msg)) => error_result(code, msg),
+    }
+}
+
+with_positional_options! {
+    /// Renders a Minijinja template and returns the result as JSON.
+    ///
+    /// A template consisting of a single `{{ ... }}` expression returns the value
+    /// of the expression serialized as JSON, so `{{ items | length }}` produces a
+    /// number and `{{ user }}` an object.  Any other template is rendered and the
+    /// output is returned as a JSON string.  The arguments are the same as for
+    /// `render_template`.
+    ///
+    /// # Returns
+    /// * `Ok` containing the JSON document, or `Err` with the error message if rendering fails.
+    ///
+    /// # Safety
+    /// All pointers must be valid for the given lengths and `autoescape_on` must point to
+    /// `autoescape_on_count` C strings.
+    fn render_template_json(
+        template_source: *const c_char,
+        template_source_len: usize,
+        json_context: *const c_char,
+        json_context_len: usize,
+    ) -> ResultCString, options => {
+        let template_str = make_str!(template_source, template_source_len, "template source");
+        let context_bytes = make_bytes!(json_context, json_context_len, "JSON context");
+
+        let options = match options {
+            Ok(options) => options,
+            Err(msg) => return error_result(error::MJ_ERROR_INVALID_ARGUMENT, msg),
+        };
+
+        // Parse the context
+        let ctx = match options.parse_context(context_bytes) {
+            Ok(c) => c,
+            Err(e) => return error_result(error::MJ_ERROR_INVALID_CONTEXT, e.to_string()),
+        };
+
+        options.render_json(template_str, &ctx)
+    }
+}
+
+with_positional_options! {
+    /// Renders a Minijinja template with several JSON contexts merged into one.
+    ///
+    /// The contexts are deep-merged from left to right: objects are merged key by
+    /// key and any other value is replaced by the later document.  All other
+    /// arguments behave as in `render_template`, except that MessagePack contexts
+    /// cannot be merged.
+    ///
+    /// # Arguments
+    /// * `json_contexts` - An array of `json_context_count` JSON documents.
+    /// * `json_context_lens` - The byte lengths of the documents in `json_contexts`.
+    /// * `json_context_count` - The number of documents, zero renders with an
+    ///   empty context.
+    ///
+    /// # Safety
+    /// `json_contexts` and `json_context_lens` must point to `json_context_count`
+    /// elements, with every document valid for its length.  The remaining
+    /// pointers follow the requirements of `render_template`.
+    fn render_template_multi(
+        template_source: *const c_char,
+        template_source_len: usize,
+        json_contexts: *const *const c_char,
+        json_context_lens: *const usize,
+        json_context_count: usize,
+    ) -> ResultCString, options => {
+        let template_str = make_str!(template_source, template_source_len, "template source");
+
+        let options = match options {
+            Ok(options) => options,
+            Err(msg) => return error_result(error::MJ_ERROR_INVALID_ARGUMENT, msg),
+        };
+
+        let (Some(ptrs), Some(lens)) = (
+            slice_from_raw(json_contexts, json_context_count),
+            slice_from_raw(json_context_lens, json_context_count),
+        ) else {
+            return error_result(
+                error::MJ_ERROR_INVALID_ARGUMENT,
+                "json_contexts is null".to_string(),
+            );
+        };
+        let mut documents: Vec<&[u8]> = Vec::with_capacity(json_context_count);
+        for (index, (&ptr, &len)) in ptrs.iter().zip(lens).enumerate() {
+            let document = make_bytes!(ptr, len, format!("JSON context {}", index));
+            documents.push(document);
+        }
+
+        // Merge the contexts
+        let ctx = match context::merge_json_documents(
+            &documents,
+            options.context_format,
+            options.conversion(),
+        ) {
+            Ok(merged) => merged,
+            Err(msg) => return error_result(error::MJ_ERROR_INVALID_ARGUMENT, msg),
+        };
+        let ctx = match context::bind_root(ctx, options.root_variable.as_deref()) {
+            Ok(ctx) => ctx,
+            Err(e) => return error_result(error::MJ_ERROR_INVALID_CONTEXT, e.to_string()),
+        };
+
+        match options.render(template_str, &ctx) {
+            Ok(output) => ok_result(output),
+            Err(err) => error_result(err.code(), err.to_string()),
+        }
+    }
+}
+
+/// Renders one template against each context of a JSON array.
+///
+/// The template is compiled once and rendered with the default options of
+/// `render_template` for every element of `json_contexts`.
+///
+/// # Arguments
+/// * `template_source` - The template content as a string.
+/// * `json_contexts` - A JSON array of contexts.
+///
+/// # Returns
+/// * `Ok` containing a JSON array with one `{"ok": output}` or `{"err": message}`
+///   object per context, or `Err` if the template does not compile or
+///   `json_contexts` is not a JSON array.
+///
+/// # Safety
+/// All pointers must be valid for the given lengths.
+#[no_mangle]
+pub unsafe extern "C" fn render_template_batch(
+    template_source: *const c_char,
+    template_source_len: usize,
+    json_contexts: *const c_char,
+    json_contexts_len: usize,
+) -> ResultCString {
+    let template_str = make_str!(template_source, template_source_len, "template source");
+    let contexts_str = make_str!(json_contexts, json_contexts_len, "JSON contexts", error::MJ_ERROR_INVALID_CONTEXT);
+
+    render_batch(&RenderOptions::default(), template_str, None, contexts_str)
+}
+
+/// Renders one template against each context of a JSON array like
+/// `render_template_batch`, with the options of `render_template_with_config`.
+///
+/// # Returns
+/// * `Ok` containing the JSON array of results of `render_template_batch`, or
+///   `Err` if the config is invalid, the template does not compile or
+///   `json_contexts` is not a JSON array.
+///
+/// # Safety
+/// All pointers must be valid for the given lengths.
+#[no_mangle]
+pub unsafe extern "C" fn render_template_batch_with_config(
+    template_source: *const c_char,
+    template_source_len: usize,
+    json_contexts: *const c_char,
+    json_contexts_len: usize,
+    config_json: *const c_char,
+    config_json_len: usize,
+) -> ResultCString {
+    let template_bytes = make_bytes!(template_source, template_source_len, "template source");
+    let contexts_str = make_str!(json_contexts, json_contexts_len, "JSON contexts", error::MJ_ERROR_INVALID_CONTEXT);
+    let config_str = make_str!(config_json, config_json_len, "config");
+
+    match template_from_config(template_bytes, config_str) {
+        Ok((options, template_str)) => render_batch(&options, &template_str, None, contexts_str),
+        Err((code, msg)) => error_result(code, msg),
+    }
+}
+
+/// Renders one template against each row of a JSON array with shared globals.
+///
+/// Like `render_template_batch`, but the keys of `json_globals` are available
+/// to every row, so invariant values are passed once instead of being repeated
+/// in each row.  A row overrides a global by having the same key, which
+/// replaces the whole value; nested objects are not merged.
+///
+/// # Arguments
+/// * `template_source` - The template content as a string.
+/// * `json_globals` - A JSON object of variables shared by all rows.
+/// * `json_rows` - A JSON array of row contexts.
+///
+/// # Returns
+/// * `Ok` containing a JSON array with one `{"ok": output}` or `{"err": message}`
+///   object per row, or `Err` if the template does not compile, `json_globals`
+///   is not a JSON object or `json_rows` is not a JSON array.
+///
+/// # Safety
+/// All pointers must be valid for the given lengths.
+#[no_mangle]
+pub unsafe extern "C" fn render_template_batch_ctx(
+    template_source: *const c_char,
+    template_source_len: usize,
+    json_globals: *const c_char,
+    json_globals_len: usize,
+    json_rows: *const c_char,
+    json_rows_len: usize,
+) -> ResultCString {
+    let template_str = make_str!(template_source, template_source_len, "template source");
+    let globals_str = make_str!(json_globals, json_globals_len, "JSON globals", error::MJ_ERROR_INVALID_CONTEXT);
+    let rows_str = make_str!(json_rows, json_rows_len, "JSON rows", error::MJ_ERROR_INVALID_CONTEXT);
+
+    render_batch(&RenderOptions::default(), template_str, Some(globals_str), rows_str)
+}
+
+/// Renders one template against each row of a JSON array with shared globals
+/// like `render_template_batch_ctx`, with the options of
+/// `render_template_with_config`.
+///
+/// # Returns
+/// * `Ok` containing the JSON array of results of `render_template_batch`, or
+///   `Err` if the config is invalid, the template does not compile,
+///   `json_globals` is not a JSON object or `json_rows` is not a JSON array.
+///
+/// # Safety
+/// All pointers must be valid for the given lengths.
+#[no_mangle]
+pub unsafe extern "C" fn render_template_batch_ctx_with_config(
+    template_source: *const c_char,
+    template_source_len: usize,
+    json_globals: *const c_char,
+    json_globals_len: usize,
+    json_rows: *const c_char,
+    json_rows_len: usize,
+    config_json: *const c_char,
+    config_json_len: usize,
+) -> ResultCString {
+    let template_bytes = make_bytes!(template_source, template_source_len, "template source");
+    let globals_str = make_str!(json_globals, json_globals_len, "JSON globals", error::MJ_ERROR_INVALID_CONTEXT);
+    let rows_str = make_str!(json_rows, json_rows_len, "JSON rows", error::MJ_ERROR_INVALID_CONTEXT);
+    let config_str = make_str!(config_json, config_json_len, "config");
+
+    match template_from_config(template_bytes, config_str) {
+        Ok((options, template_str)) => {
+            render_batch(&options, &template_str, Some(globals_str), rows_str)
+        }
+        Err((code, msg)) => error_result(code, msg),
+    }
+}
+
+/// Renders one template against each context of a JSON array into Arrow.
+///
+/// Like `render_template_batch`, but the results are an [Arrow IPC stream]
+/// holding one record batch with a row per context and two nullable `Utf8`
+/// columns: `output` holds the rendered template and is null for contexts that
+/// failed, `error` holds the error message of those and is null otherwise.
+/// The buffers of the batch are 8 byte aligned and little endian.
+///
+/// [Arrow IPC stream]: https://arrow.apache.org/docs/format/Columnar.html#ipc-streaming-format
+///
+/// # Arguments
+/// * `template_source` - The template content as a string.
+/// * `json_contexts` - A JSON array of contexts.
+///
+/// # Returns
+/// * `Ok` containing the Arrow IPC stream, or `Err` with the error message if
+///   the template does not compile, `json_contexts` is not a JSON array or a
+///   column exceeds the 2 GiB an Arrow string array holds.
+///
+/// # Safety
+/// All pointers must be valid for the given lengths.
+#[no_mangle]
+pub unsafe extern "C" fn render_template_batch_arrow(
+    template_source: *const c_char,
+    template_source_len: usize,
+    json_contexts: *const c_char,
+    json_contexts_len: usize,
+) -> ResultBytes {
+    let template_str = make_str!(template_source, template_source_len, "template source");
+    let contexts_str = make_str!(json_contexts, json_contexts_len, "JSON contexts", error::MJ_ERROR_INVALID_CONTEXT);
+
+    render_batch_arrow(&RenderOptions::default(), template_str, contexts_str)
+}
+
+/// Renders one template against each context of a JSON array into Arrow like
+/// `render_template_batch_arrow`, with the options of
+/// `render_template_with_config`.
+///
+/// # Returns
+/// * `Ok` containing the Arrow IPC stream of `render_template_batch_arrow`, or
+///   `Err` with the error message if the config is invalid or the batch fails
+///   like `render_template_batch_arrow`.
+///
+/// # Safety
+/// All pointers must be valid for the given lengths.
+#[no_mangle]
+pub unsafe extern "C" fn render_template_batch_arrow_with_config(
+    template_source: *const c_char,
+    template_source_len: usize,
+    json_contexts: *const c_char,
+    json_contexts_len: usize,
+    config_json: *const c_char,
+    config_json_len: usize,
+) -> ResultBytes {
+    let template_bytes = make_bytes!(template_source, template_source_len, "template source");
+    let contexts_str = make_str!(json_contexts, json_contexts_len, "JSON contexts", error::MJ_ERROR_INVALID_CONTEXT);
+    let config_str = make_str!(config_json, config_json_len, "config");
+
+    match template_from_config(template_bytes, config_str) {
+        Ok((options, template_str)) => render_batch_arrow(&options, &template_str, contexts_str),
+        Err((code, msg)) => ResultBytes::from_error(code, msg),
+    }
+}
+
+/// Renders a batch like `render_batch` into an Arrow IPC stream with the
+/// `output` and `error` columns of `render_template_batch_arrow`.
+fn render_batch_arrow(options: &RenderOptions, template_str: &str, contexts: &str) -> ResultBytes {
+    let rows = match render_rows(options, template_str, None, contexts) {
+        Ok(rows) => rows,
+        Err((code, msg)) => return ResultBytes::from_error(code, msg),
+    };
+    let columns = [
+        arrow::StringColumn {
+            name: "output",
+            values: rows.iter().map(|row| row.as_deref().ok()).collect(),
+        },
+        arrow::StringColumn {
+            name: "error",
+            values: rows.iter().map(|row| row.as_ref().err().map(String::as_str)).collect(),
+        },
+    ];
+    match arrow::ipc_stream(&columns) {
+        Ok(stream) => {
+            error::set_last_error_code(error::MJ_OK);
+            ResultBytes::new(true, stream)
+        }
+        Err(msg) => ResultBytes::from_error(error::MJ_ERROR_SERIALIZATION, msg),
+    }
+}
+
+/// Renders one template against each context of a JSON array, passing each
+/// result to a callback.
+///
+/// Like `render_template_batch`, but `row_callback` receives the output or
+/// error message of every context in order as soon as it is rendered, so the
+/// results are never collected.  The template is compiled once.
+///
+/// # Arguments
+/// * `template_source` - The template content as a string.
+/// * `json_contexts` - A JSON array of contexts.
+/// * `row_callback` - Receives the result of each context, returning nonzero
+///   aborts the batch.
+/// * `user_data` - Passed to every call of `row_callback`.
+///
+/// # Returns
+/// * `Ok` with an empty string once every context was passed to the callback,
+///   or `Err` with the error message if the template does not compile,
+///   `json_contexts` is not a JSON array or the callback aborted the batch.
+///   An abort is reported with `MJ_ERROR_IO`.
+///
+/// # Safety
+/// All pointers must be valid for the given lengths.
+#[no_mangle]
+pub unsafe extern "C" fn render_template_batch_stream(
+    template_source: *const c_char,
+    template_source_len: usize,
+    json_contexts: *const c_char,
+    json_contexts_len: usize,
+    row_callback: Option<RowCallback>,
+    user_data: *mut c_void,
+) -> ResultCString {
+    let Some(row_callback) = row_callback else {
+        return error_result(error::MJ_ERROR_INVALID_ARGUMENT, "Row callback is null".to_string());
+    };
+    let template_str = make_str!(template_source, template_source_len, "template source");
+    let contexts_str = make_str!(json_contexts, json_contexts_len, "JSON contexts", error::MJ_ERROR_INVALID_CONTEXT);
+
+    render_batch_stream(
+        &RenderOptions::default(),
+        template_str,
+        contexts_str,
+        row_callback,
+        user_data,
+    )
+}
+
+/// Renders one template against each context of a JSON array, passing each
+/// result to a callback like `render_template_batch_stream`, with the options
+/// of `render_template_with_config`.
+///
+/// # Returns
+/// * `Ok` with an empty string once every context was passed to the callback,
+///   or `Err` with the error message if the config is invalid or the batch
+///   fails like `render_template_batch_stream`.
+///
+/// # Safety
+/// All pointers must be valid for the given lengths.
+#[no_mangle]
+pub unsafe extern "C" fn render_template_batch_stream_with_config(
+    template_source: *const c_char,
+    template_source_len: usize,
+    json_contexts: *const c_char,
+    json_contexts_len: usize,
+    config_json: *const c_char,
+    config_json_len: usize,
+    row_callback: Option<RowCallback>,
+    user_data: *mut c_void,
+) -> ResultCString {
+    let Some(row_callback) = row_callback else {
+        return error_result(error::MJ_ERROR_INVALID_ARGUMENT, "Row callback is null".to_string());
+    };
+    let template_bytes = make_bytes!(template_source, template_source_len, "template source");
+    let contexts_str = make_str!(json_contexts, json_contexts_len, "JSON contexts", error::MJ_ERROR_INVALID_CONTEXT);
+    let config_str = make_str!(config_json, config_json_len, "config");
+
+    match template_from_config(template_bytes, config_str) {
+        Ok((options, template_str)) => {
+            render_batch_stream(&options, &template_str, contexts_str, row_callback, user_data)
+        }
+        Err((code, msg)) => error_result(code, msg),
+    }
+}
+
+/// Renders a batch like `render_batch`, passing the result of each context to
+/// `row_callback` as `render_template_batch_stream` describes.
+///
+/// # Safety
+/// `row_callback` must be safe to call with `user_data`.
+unsafe fn render_batch_stream(
+    options: &RenderOptions,
+    template_str: &str,
+    contexts: &str,
+    row_callback: RowCallback,
+    user_data: *mut c_void,
+) -> ResultCString {
+    let mut aborted_at = None;
+    let result = for_each_row(options, template_str, None, contexts, |index, row| {
+        let (ok, text) = match &row {
+            Ok(output) => (true, output),
+            Err(msg) => (false, msg),
+        };
+        let status =
+            row_callback(index, ok, text.as_ptr() as *const c_char, text.len(), user_data);
+        if status == 0 {
+            return ControlFlow::Continue(());
+        }
+        aborted_at = Some(index);
+        ControlFlow::Break(())
+    });
+    match (result, aborted_at) {
+        (Err((code, msg)), _) => error_result(code, msg),
+        (Ok(()), Some(index)) => error_result(
+            error::MJ_ERROR_IO,
+            format!("MiniJinja render error: aborted by the row callback at row {}", index),
+        ),
+        (Ok(()), None) => ok_result(String::new()),
+    }
+}
+
+/// Renders a Minijinja template and streams the output to a callback.
+///
+/// The output is passed to `write_callback` in chunks as it is produced instead
+/// of being collected into one string.  The template is rendered with the
+/// default options of `render_template`.
+///
+/// # Arguments
+/// * `template_source` - The template content as a string.
+/// * `json_context` - JSON string containing context variables.
+/// * `write_callback` - Receives the output, returning nonzero aborts the render.
+/// * `user_data` - Passed to every call of `write_callback`.
+///
+/// # Returns
+/// * `Ok` with an empty string once all output was written, or `Err` with the
+///   error message if rendering fails or the callback aborted it.  Output
+///   written before an error is not retracted.
+///
+/// # Safety
+/// All pointers must be valid for the given lengths.
+#[no_mangle]
+pub unsafe extern "C" fn render_template_stream(
+    template_source: *const c_char,
+    template_source_len: usize,
+    json_context: *const c_char,
+    json_context_len: usize,
+    write_callback: Option<WriteCallback>,
+    user_data: *mut c_void,
+) -> ResultCString {
+    let Some(write_callback) = write_callback else {
+        return error_result(
+            error::MJ_ERROR_INVALID_ARGUMENT,
+            "Write callback is null".to_string(),
+        );
+    };
+    let template_str = make_str!(template_source, template_source_len, "template source");
+    let json_str = make_str!(json_context, json_context_len, "JSON context", error::MJ_ERROR_INVALID_CONTEXT);
+
+    let options = RenderOptions::default();
+    let ctx = match json_to_value(json_str, options.conversion()) {
+        Ok(c) => c,
+        Err(e) => return error_result(error::MJ_ERROR_INVALID_CONTEXT, e.to_string()),
+    };
+
+    render_stream(&options, template_str, &ctx, write_callback, UserData(user_data))
+}
+
+/// Renders a Minijinja template and streams the output to a callback like
+/// `render_template_stream`, with the options of `render_template_with_config`.
+///
+/// The callback runs on the calling thread, so `timeout_ms` is only checked
+/// while rendering, see `timeout_ms` of `render_template_with_config`.  Time
+/// spent in the callback counts towards it.
+///
+/// # Returns
+/// * `Ok` with an empty string once all output was written, or `Err` with the
+///   error message if the config is invalid, rendering fails or the callback
+///   aborted it.
+///
+/// # Safety
+/// All pointers must be valid for the given lengths.
+#[no_mangle]
+pub unsafe extern "C" fn render_template_stream_with_config(
+    template_source: *const c_char,
+    template_source_len: usize,
+    json_context: *const c_char,
+    json_context_len: usize,
+    config_json: *const c_char,
+    config_json_len: usize,
+    write_callback: Option<WriteCallback>,
+    user_data: *mut c_void,
+) -> ResultCString {
+    let Some(write_callback) = write_callback else {
+        return error_result(
+            error::MJ_ERROR_INVALID_ARGUMENT,
+            "Write callback is null".to_string(),
+        );
+    };
+    let template_bytes = make_bytes!(template_source, template_source_len, "template source");
+    let context_bytes = make_bytes!(json_context, json_context_len, "JSON context");
+    let config_str = make_str!(config_json, config_json_len, "config");
+
+    match inputs_from_config(template_bytes, context_bytes, config_str) {
+        Ok((options, template_str, ctx)) => {
+            render_stream(&options, &template_str, &ctx, write_callback, UserData(user_data))
+        }
+        Err((code, msg)) => error_result(code, msg),
+    }
+}
+
+/// Checks that a template compiles without rendering it.
+///
+/// The template is parsed with the default syntax and no template logic is
+/// executed, so no context is needed.
+///
+/// # Arguments
+/// * `template_source` - The template content as a string.
+/// * `strict` - Whether the filters, tests and functions the template calls are
+///   also checked against those of `render_template`, which otherwise fail only
+///   when a render reaches them, e.g. a misspelled `{{ x | uppr }}`.
+///
+/// # Returns
+/// * `Ok` with an empty string if the template is valid, or `Err` with the
+///   syntax error and the line and column where it occurred.  The message
+///   goes on with the failing source line and a caret under the column and,
+///   for errors such as `unexpected end of input`, a `hint:` line naming
+///   what is missing, e.g. the `{% endfor %}` of an open loop.  In strict
+///   mode a template calling unknown names fails with
+///   `MJ_ERROR_UNKNOWN_CALLABLE` and a line per name, such as
+///   `Unknown filter 'uppr' at line 1, column 8`.
+///
+/// # Safety
+/// `template_source` must be valid for `template_source_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn mj_validate_template(
+    template_source: *const c_char,
+    template_source_len: usize,
+    strict: bool,
+) -> ResultCString {
+    let template_str = make_str!(template_source, template_source_len, "template source");
+
+    let env = match RenderOptions::default().build_environment() {
+        Ok(env) => env,
+        Err(msg) => return error_result(error::MJ_ERROR_INVALID_ARGUMENT, msg),
+    };
+    match env.template_from_str(template_str) {
+        Ok(tmpl) if strict => {
+            let unknown = hints::unknown_callables(&env, &tmpl);
+            if unknown.is_empty() {
+                ok_result(String::new())
+            } else {
+                error_result(error::MJ_ERROR_UNKNOWN_CALLABLE, unknown.join("\n"))
+            }
+        }
+        Ok(_) => ok_result(String::new()),
+        Err(err) => {
+            let detail = err.detail().unwrap_or("invalid template");
+            let mut msg = match error::error_location(&err) {
+                Some(location) => format!("Template syntax error at {}: {}", location, detail),
+                None => format!("Template syntax error: {}", detail),
+            };
+            if let Some(excerpt) = hints::source_excerpt(template_str, &err) {
+                msg.push('\n');
+                msg.push_str(&excerpt);
+            }
+            if let Some(hint) = hints::syntax_hint(template_str, &err) {
+                msg.push_str("\nhint: ");
+                msg.push_str(&hint);
+            }
+            error_result(error::error_code(&err), msg)
+        }
+    }
+}
+
+/// Lists the variables a template expects from its context.
+///
+/// The template is parsed with the default syntax and analysed without being
+/// rendered.  Variables the template assigns itself and globals such as
+/// `range` or `now` are not reported, nested lookups such as `user.name`
+/// report the root variable `user`.
+///
+/// # Arguments
+/// * `template_source` - The template content as a string.
+///
+/// # Returns
+/// * `Ok` containing a sorted JSON array of variable names, or `Err` with the
+///   syntax error if the template does not compile.
+///
+/// # Safety
+/// `template_source` must be valid for `template_source_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn mj_template_variables(
+    template_source: *const c_char,
+    template_source_len: usize,
+) -> ResultCString {
+    let template_str = make_str!(template_source, template_source_len, "template source");
+
+    let env = match RenderOptions::default().build_environment() {
+        Ok(env) => env,
+        Err(msg) => return error_result(error::MJ_ERROR_INVALID_ARGUMENT, msg),
+    };
+    let tmpl = match env.template_from_str(template_str) {
+        Ok(tmpl) => tmpl,
+        Err(err) => return render_result(Err(err)),
+    };
+    let mut names: Vec<String> = tmpl
+        .undeclared_variables(false)
+        .into_iter()
+        .filter(|name| !env.globals().any(|(global, _)| global == name))
+        .collect();
+    names.sort();
+
+    match serde_json::to_string(&names) {
+        Ok(json) => ok_result(json),
+        Err(e) => error_result(
+            error::MJ_ERROR_SERIALIZATION,
+            format!("Failed to serialize variable names: {}", e),
+        ),
+    }
+}
+
+/// Lists the variables a template expects that are missing from a context.
+///
+/// This is a pre-flight check before rendering: the template is analysed
+/// like `mj_template_variables` and the variables that are not keys of the
+/// context are returned, so a caller can report all of them at once instead
+/// of failing at the first one in strict mode.  Nested lookups such as
+/// `user.name` report the root variable `user`, and globals such as `range`
+/// or `now` are never missing.  A key holding `null` counts as present.
+///
+/// # Arguments
+/// * `template_source` - The template content as a string.
+/// * `json_context` - JSON string containing context variables.
+///
+/// # Returns
+/// * `Ok` containing a sorted JSON array of the missing variable names, or
+///   `Err` if the template does not compile or the context is invalid.
+///
+/// # Safety
+/// All pointers must be valid for the given lengths.
+#[no_mangle]
+pub unsafe extern "C" fn mj_missing_variables(
+    template_source: *const c_char,
+    template_source_len: usize,
+    json_context: *const c_char,
+    json_context_len: usize,
+) -> ResultCString {
+    let template_str = make_str!(template_source, template_source_len, "template source");
+    let context_bytes = make_bytes!(json_context, json_context_len, "JSON context");
+
+    let options = RenderOptions::default();
+    let ctx = match options.parse_context(context_bytes) {
+        Ok(c) => c,
+        Err(e) => return error_result(error::MJ_ERROR_INVALID_CONTEXT, e.to_string()),
+    };
+    missing_variables(&options, template_str, &ctx)
+}
+
+/// Lists the variables a template expects that are missing from a context
+/// like `mj_missing_variables`, with the options of
+/// `render_template_with_config`.
+///
+/// The template is parsed with the configured syntax and, with a
+/// `template_path`, loaded by name, and the context is read like the context
+/// of a render.
+///
+/// # Returns
+/// * `Ok` containing a sorted JSON array of the missing variable names, or
+///   `Err` if the config is invalid, the template does not compile or the
+///   context is invalid.
+///
+/// # Safety
+/// All pointers must be valid for the given lengths.
+#[no_mangle]
+pub unsafe extern "C" fn mj_missing_variables_with_config(
+    template_source: *const c_char,
+    template_source_len: usize,
+    json_context: *const c_char,
+    json_context_len: usize,
+    config_json: *const c_char,
+    config_json_len: usize,
+) -> ResultCString {
+    let template_bytes = make_bytes!(template_source, template_source_len, "template source");
+    let context_bytes = make_bytes!(json_context, json_context_len, "JSON context");
+    let config_str = make_str!(config_json, config_json_len, "config");
+
+    match inputs_from_config(template_bytes, context_bytes, config_str) {
+        Ok((options, template_str, ctx)) => missing_variables(&options, &template_str, &ctx),
+        Err((code, msg)) => error_result(code, msg),
+    }
+}
+
+/// Returns the sorted JSON array of the variables of a template that are
+/// missing from `ctx`, see `mj_missing_variables`.
+fn missing_variables(options: &RenderOptions, template_str: &str, ctx: &Value) -> ResultCString {
+    let (env, name) = match options.render_target(template_str) {
+        Ok(target) => target,
+        Err(RenderError::Template(err)) => return render_result(Err(err)),
+        Err(err) => return error_result(err.code(), err.to_string()),
+    };
+    let tmpl = match env.get_template(&name) {
+        Ok(tmpl) => tmpl,
+        Err(err) => return render_result(Err(err)),
+    };
+    let mut missing: Vec<String> = tmpl
+        .undeclared_variables(false)
+        .into_iter()
+        .filter(|name| ctx.get_attr(name).map_or(true, |value| value.is_undefined()))
+        .filter(|name| !env.globals().any(|(global, _)| global == name))
+        .collect();
+    missing.sort();
+
+    match serde_json::to_string(&missing) {
+        Ok(json) => ok_result(json),
+        Err(e) => error_result(
+            error::MJ_ERROR_SERIALIZATION,
+            format!("Failed to serialize variable names: {}", e),
+        ),
+    }
+}
+
+/// Evaluates a single expression against a context and returns its value.
+///
+/// The expression is written without delimiters, e.g. `amount * 1.2` or
+/// `items | length`, and is evaluated with the default options of
+/// `render_template`.
+///
+/// # Arguments
+/// * `expression` - The expression source.
+/// * `json_context` - JSON string containing context variables.
+///
+/// # Returns
+/// * `Ok` containing the value of the expression as JSON, or `Err` with the
+///   error message if the expression does not compile or fails to evaluate.
+///
+/// # Safety
+/// All pointers must be valid for the given lengths.
+#[no_mangle]
+pub unsafe extern "C" fn mj_eval_expr(
+    expression: *const c_char,
+    expression_len: usize,
+    json_context: *const c_char,
+    json_context_len: usize,
+) -> ResultCString {
+    let expr_str = make_str!(expression, expression_len, "expression");
+    let json_str = make_str!(json_context, json_context_len, "JSON context", error::MJ_ERROR_INVALID_CONTEXT);
+
+    let options = RenderOptions::default();
+    let ctx = match json_to_value(json_str, options.conversion()) {
+        Ok(c) => c,
+        Err(e) => return error_result(error::MJ_ERROR_INVALID_CONTEXT, e.to_string()),
+    };
+    eval_expr(&options, expr_str, &ctx)
+}
+
+/// Evaluates a single expression against a context like `mj_eval_expr`, with
+/// the options of `render_template_with_config`.
+///
+/// The context is read like the context of a render and `timeout_ms` and
+/// `fuel` bound the evaluation.  `template_path` and `template_compression`
+/// do not apply to expressions.
+///
+/// # Returns
+/// * `Ok` containing the value of the expression as JSON, or `Err` with the
+///   error message if the config is invalid or the expression does not
+///   compile or fails to evaluate.
+///
+/// # Safety
+/// All pointers must be valid for the given lengths.
+#[no_mangle]
+pub unsafe extern "C" fn mj_eval_expr_with_config(
+    expression: *const c_char,
+    expression_len: usize,
+    json_context: *const c_char,
+    json_context_len: usize,
+    config_json: *const c_char,
+    config_json_len: usize,
+) -> ResultCString {
+    let expr_str = make_str!(expression, expression_len, "expression");
+    let context_bytes = make_bytes!(json_context, json_context_len, "JSON context");
+    let config_str = make_str!(config_json, config_json_len, "config");
+
+    let options = match config::render_options_from_json(config_str) {
+        Ok(options) => options,
+        Err(msg) => return error_result(error::MJ_ERROR_INVALID_ARGUMENT, msg),
+    };
+    let ctx = match options.parse_context(context_bytes) {
+        Ok(c) => c,
+        Err(e) => return error_result(error::MJ_ERROR_INVALID_CONTEXT, e.to_string()),
+    };
+    eval_expr(&options, expr_str, &ctx)
+}
+
+/// Evaluates `expr_str` against `ctx` and returns its value as JSON, see
+/// `mj_eval_expr`.
+fn eval_expr(options: &RenderOptions, expr_str: &str, ctx: &Value) -> ResultCString {
+    let env = match options.build_environment() {
+        Ok(env) => env,
+        Err(msg) => return error_result(error::MJ_ERROR_INVALID_ARGUMENT, msg),
+    };
+
+    let expr_str = expr_str.to_owned();
+    let ctx = timeout::bound_context(options.timeout_ms, ctx);
+    let fuel = options.fuel;
+    let value = timeout::run_with_timeout(options.timeout_ms, move || {
+        env.compile_expression(&expr_str)
+            .and_then(|expr| expr.eval(ctx))
+            .map_err(|err| map_fuel_error(fuel, err))
+    });
+    match value {
+        Ok(value) => json_result(Ok(value)),
+        Err(RenderError::Template(err)) => json_result(Err(err)),
+        Err(err) => error_result(err.code(), err.to_string()),
+    }
+}
+
+/// The version string returned by `mj_version`.
+const VERSION: &str = concat!(
+    "minijinja ",
+    env!("MINIJINJA_VERSION"),
+    ", duckdb_minijinja_binding ",
+    env!("CARGO_PKG_VERSION"),
+    "\0"
+);
+
+/// Returns the MiniJinja version and the version of this binding.
+///
+/// The string looks like `minijinja 2.14.0, duckdb_minijinja_binding 0.1.0`
+/// and is meant for bug reports and compatibility checks.
+///
+/// # Returns
+/// * A static NUL terminated string that must not be freed.
+#[no_mangle]
+pub extern "C" fn mj_version() -> *const c_char {
+    VERSION.as_ptr() as *const c_char
+}
+
+/// Frees the memory allocated for a ResultCString.
+///
+/// # Arguments
+/// * `result` - The ResultCString to free
+///
+/// # Safety
+/// This function is unsafe because it takes ownership of raw pointers.
+/// The caller must ensure that:
+/// - The ResultCString was created by this library
+/// - The ResultCString is not used after calling this function
+/// - This function is called exactly once for each ResultCString
+#[no_mangle]
+pub unsafe extern "C" fn free_result_cstring(result: ResultCString) {
+    match result {
+        ResultCString::Ok(ptr) | ResultCString::Err(ptr) => {
+            if !ptr.is_null() {
+                let _ = CString::from_raw(ptr);
+            }
+        }
+    }
+}
+/// Frees the memory allocated for a ResultBytes.
+///
+/// # Safety
+/// The ResultBytes must have been created by this library and must not be
+/// used after calling this function, which must be called exactly once for it.
+#[no_mangle]
+pub unsafe extern "C" fn free_result_bytes(result: ResultBytes) {
+    if !result.ptr.is_null() {
+        let data = std::ptr::slice_from_raw_parts_mut(result.ptr, result.len);
+        drop(Box::from_raw(data));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    unsafe extern "C" fn discard(_: *const c_char, _: usize, _: *mut c_void) -> std::ffi::c_int {
+        0
+    }
+
+    /// Streams `template` with `context`, returning the error code and result.
+    fn render_stream_raw(template: &[u8], context: &[u8]) -> (i32, Result<String, String>) {
+        let result = unsafe {
+            render_template_stream(
+                template.as_ptr() as *const c_char,
+                template.len(),
+                context.as_ptr() as *const c_char,
+                context.len(),
+                Some(discard),
+                std::ptr::null_mut(),
+            )
+        };
+        (error::mj_last_error_code(), result.into_result())
+    }
+
+    #[test]
+    fn render_json_renders_loaded_templates_under_their_name() {
+        let dir = std::env::temp_dir().join(format!("mj-render-json-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("page.html"), "{{ title }} {% include 'part.html' %}").unwrap();
+        std::fs::write(dir.join("part.html"), "{{ title }}").unwrap();
+        std::fs::write(dir.join("count.txt"), "{{ items | length }}").unwrap();
+        std::fs::write(dir.join("broken.txt"), "\n{{ items | nope }}").unwrap();
+        let options = RenderOptions {
+            template_path: Some(dir.to_string_lossy().into_owned()),
+            ..RenderOptions::default()
+        };
+        let ctx = Value::from_serialize(serde_json::json!({"title": "<b>", "items": [1, 2]}));
+        let render = |name| options.render_json(name, &ctx).into_result();
+
+        assert_eq!(render("page.html"), Ok(r#""&lt;b&gt; &lt;b&gt;""#.to_string()));
+        assert_eq!(render("count.txt"), Ok("2".to_string()));
+        let err = render("broken.txt").unwrap_err();
+        assert!(err.contains(r#"name: "broken.txt", line: 2"#), "{}", err);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Calls a `_with_config` render function with `template`, `{}` and `config`.
+    fn render_with_config(
+        render: unsafe extern "C" fn(
+            *const c_char,
+            usize,
+            *const c_char,
+            usize,
+            *const c_char,
+            usize,
+        ) -> ResultCString,
+        template: &str,
+        config: &str,
+    ) -> (i32, Result<String, String>) {
+        let result = unsafe {
+            render(
+                template.as_ptr().cast(),
+                template.len(),
+                "{}".as_ptr().cast(),
+                2,
+                config.as_ptr().cast(),
+                config.len(),
+            )
+        };
+        (error::mj_last_error_code(), result.into_result())
+    }
+
+    #[test]
+    fn ex_and_debug_renders_take_their_options_from_the_config() {
+        let _workers = timeout::lock_idle_workers();
+        let silent_loop = "{% for i in range(100000) %}{% for j in range(100000) %}\
+            {% endfor %}{% endfor %}";
+        let timeout = r#"{"timeout_ms": 50}"#;
+        let (code, result) =
+            render_with_config(render_template_ex_with_config, silent_loop, timeout);
+        assert_eq!(code, error::MJ_ERROR_TIMEOUT);
+        let report: serde_json::Value = serde_json::from_str(&result.unwrap_err()).unwrap();
+        assert_eq!(report["kind"], "Timeout");
+
+        let (code, result) =
+            render_with_config(render_template_debug_with_config, silent_loop, timeout);
+        assert_eq!(code, error::MJ_ERROR_TIMEOUT);
+        assert_eq!(result, Err("render exceeded the timeout of 50 ms".to_string()));
+
+        let (code, result) =
+            render_with_config(render_template_debug_with_config, "{{ x }}", r#"{"fuel": 100}"#);
+        assert_eq!(code, error::MJ_OK);
+        let report: serde_json::Value = serde_json::from_str(&result.unwrap()).unwrap();
+        assert_eq!(report["undefined"], serde_json::json!(["x"]));
+        assert!(report["fuel_consumed"].as_u64().unwrap() > 0, "{}", report);
+
+        let (code, _) = render_with_config(render_template_ex_with_config, "x", r#"{"nope": 1}"#);
+        assert_eq!(code, error::MJ_ERROR_INVALID_ARGUMENT);
+    }
+
+    #[test]
+    fn batch_eval_and_missing_variables_take_their_options_from_the_config() {
+        let delimiters = r#"{"variable_delimiters": ["[[", "]]"]}"#;
+        let result = unsafe {
+            let (template, contexts) = ("[[ x ]]", r#"[{"x": 1}, {"y": 2}]"#);
+            render_template_batch_with_config(
+                template.as_ptr().cast(),
+                template.len(),
+                contexts.as_ptr().cast(),
+                contexts.len(),
+                delimiters.as_ptr().cast(),
+                delimiters.len(),
+            )
+        };
+        assert_eq!(result.into_result(), Ok(r#"[{"ok":"1"},{"ok":""}]"#.to_string()));
+
+        let (code, result) =
+            render_with_config(mj_missing_variables_with_config, "[[ a ]]", delimiters);
+        assert_eq!((code, result), (error::MJ_OK, Ok(r#"["a"]"#.to_string())));
+
+        let result = unsafe {
+            let (expr, context) = ("value | length", "[1, 2, 3]");
+            let config = r#"{"root_variable": "value"}"#;
+            mj_eval_expr_with_config(
+                expr.as_ptr().cast(),
+                expr.len(),
+                context.as_ptr().cast(),
+                context.len(),
+                config.as_ptr().cast(),
+                config.len(),
+            )
+        };
+        assert_eq!(result.into_result(), Ok("3".to_string()));
+        let (code, result) = render_with_config(mj_eval_expr_with_config, "x", r#"{"nope": 1}"#);
+        assert_eq!(
+            (code, result),
+            (error::MJ_ERROR_INVALID_ARGUMENT, Err("Unknown config key 'nope'".to_string()))
+        );
+    }
+
+    #[test]
+    fn template_variables_leave_out_the_registered_globals() {
+        let source = "{{ now() }}{{ uuid() }}{{ random() }}{{ randrange(3) }}{{ env('HOME') }}\
+            {{ range(2) }}{{ user.name }}{% set local = 1 %}{{ local }}{{ total }}";
+        let result = unsafe { mj_template_variables(source.as_ptr().cast(), source.len()) };
+        assert_eq!(result.into_result(), Ok(r#"["total","user"]"#.to_string()));
+    }
+
+    #[test]
+    fn invalid_utf8_is_rejected_with_the_code_of_the_argument() {
+        assert_eq!(
+            render_stream_raw(b"Hello \xff", b"{}"),
+            (
+                error::MJ_ERROR_INVALID_ARGUMENT,
+                Err("template source is not valid UTF-8".to_string())
+            )
+        );
+        assert_eq!(
+            render_stream_raw(b"Hello", b"{\"name\": \"\xc3\"}"),
+            (
+                error::MJ_ERROR_INVALID_CONTEXT,
+                Err("JSON context is not valid UTF-8".to_string())
+            )
+        );
+        assert_eq!(render_stream_raw(b"Hello", b"{}"), (error::MJ_OK, Ok(String::new())));
+    }
+}
@@ -0,0 +1,36 @@
+use std::env::VarError;
+
+use minijinja::{Environment, Error, ErrorKind, Value};
+
+/// Registers the `env()` global function.
+///
+/// Reading the environment of the process is a sensitive capability, so
+/// unless `allowed` is set `env()` fails instead of returning a value.
+pub(crate) fn register_env(env: &mut Environment<'static>, allowed: bool) {
+    env.add_function("env", move |name: &str, default: Option<Value>| {
+        if !allowed {
+            return Err(Error::new(
+                ErrorKind::InvalidOperation,
+                format!(
+                    "environment access disabled, enable allow_env to read '{}'",
+                    name
+                ),
+            ));
+        }
+        read_env(name, default)
+    });
+}
+
+/// Returns an environment variable, e.g. `{{ env('HOME') }}`.
+///
+/// Missing variables return `default`, or undefined without one.
+fn read_env(name: &str, default: Option<Value>) -> Result<Value, Error> {
+    match std::env::var(name) {
+        Ok(value) => Ok(Value::from(value)),
+        Err(VarError::NotPresent) => Ok(default.unwrap_or(Value::UNDEFINED)),
+        Err(VarError::NotUnicode(_)) => Err(Error::new(
+            ErrorKind::InvalidOperation,
+            format!("environment variable '{}' is not valid UTF-8", name),
+        )),
+    }
+}
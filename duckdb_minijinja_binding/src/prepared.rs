@@ -0,0 +1,267 @@
+use std::ffi::c_char;
+use std::ptr;
+
+use minijinja::value::ValueKind;
+use minijinja::{Environment, Value};
+
+use crate::builder::ContextHandle;
+use crate::config::render_options_from_json;
+use crate::context::bind_root;
+use crate::error::{RenderError, MJ_ERROR_INVALID_ARGUMENT, MJ_ERROR_INVALID_CONTEXT};
+use crate::{
+    error_result, json_to_value, ok_result, render_result, timeout, RenderOptions, ResultCString,
+};
+
+/// The name the prepared template is registered under in its environment.
+const PREPARED_TEMPLATE_NAME: &str = "<string>";
+
+/// An opaque handle holding a compiled template for repeated renders.
+///
+/// Prepared templates are created with `mj_prepare` and must be released with
+/// `mj_prepared_free`.  Rendering only reads the template, so a prepared
+/// template may be rendered from several threads at once.
+pub struct PreparedTemplate {
+    env: Environment<'static>,
+    options: RenderOptions,
+}
+
+/// Compiles a template once so it can be rendered for many contexts.
+///
+/// # Arguments
+/// * `template_source` - The template content as a string.
+/// * `json_invariant` - A JSON object of the variables shared by every render,
+///   passed once instead of being repeated in each row.  A row overrides an
+///   invariant variable by having the same key, which replaces the whole
+///   value.  Null means no invariant variables.
+/// * `config_json` - The options as a JSON object, see
+///   `render_template_with_config`.  Null renders with the default options of
+///   `render_template`.
+/// * `prepared_out` - Receives the prepared template, which must be freed with
+///   `mj_prepared_free`, or null if preparing it fails.
+///
+/// # Returns
+/// * `Ok` with an empty string once the template is prepared, or `Err` with
+///   the error message if the config or the invariant variables are invalid or
+///   the template does not compile.
+///
+/// # Safety
+/// All pointers must be null or valid for the given lengths and `prepared_out`
+/// must be valid for writing.
+#[no_mangle]
+pub unsafe extern "C" fn mj_prepare(
+    template_source: *const c_char,
+    template_source_len: usize,
+    json_invariant: *const c_char,
+    json_invariant_len: usize,
+    config_json: *const c_char,
+    config_json_len: usize,
+    prepared_out: *mut *mut PreparedTemplate,
+) -> ResultCString {
+    let Some(prepared_out) = prepared_out.as_mut() else {
+        return error_result(MJ_ERROR_INVALID_ARGUMENT, "prepared_out is null".to_string());
+    };
+    *prepared_out = ptr::null_mut();
+    let template_bytes = make_bytes!(template_source, template_source_len, "template source");
+    let invariant = if json_invariant.is_null() {
+        None
+    } else {
+        Some(std::slice::from_raw_parts(json_invariant as *const u8, json_invariant_len))
+    };
+    let options = if config_json.is_null() {
+        RenderOptions::default()
+    } else {
+        let config_str = make_str!(config_json, config_json_len, "config");
+        match render_options_from_json(config_str) {
+            Ok(options) => options,
+            Err(msg) => return error_result(MJ_ERROR_INVALID_ARGUMENT, msg),
+        }
+    };
+
+    match prepare(options, template_bytes, invariant) {
+        Ok(prepared) => {
+            *prepared_out = Box::into_raw(Box::new(prepared));
+            ok_result(String::new())
+        }
+        Err(RenderError::Template(err)) => render_result(Err(err)),
+        Err(err) => error_result(err.code(), err.to_string()),
+    }
+}
+
+/// Compiles the template of `mj_prepare` with the invariant variables as
+/// globals of its environment, so the variables of a row take precedence.
+fn prepare(
+    options: RenderOptions,
+    template_bytes: &[u8],
+    invariant: Option<&[u8]>,
+) -> Result<PreparedTemplate, RenderError> {
+    let template_str = options
+        .template_compression
+        .decode(template_bytes)
+        .map_err(RenderError::InvalidOptions)?;
+    let mut env = options.build_environment().map_err(RenderError::InvalidOptions)?;
+    if let Some(invariant) = invariant {
+        let invariant = options
+            .parse_context(invariant)
+            .map_err(|err| RenderError::InvalidContext(err.to_string()))?;
+        let names = match invariant.try_iter() {
+            Ok(names) if invariant.kind() == ValueKind::Map => names,
+            _ => {
+                return Err(RenderError::InvalidContext(
+                    "the invariant variables must be an object".to_string(),
+                ))
+            }
+        };
+        for name in names {
+            let value = invariant.get_item(&name).unwrap_or_default();
+            if let Some(name) = name.as_str() {
+                env.add_global(name.to_owned(), value);
+            }
+        }
+    }
+    env.add_template_owned(PREPARED_TEMPLATE_NAME, template_str.into_owned())?;
+    Ok(PreparedTemplate { env, options })
+}
+
+/// Renders a prepared template with one context.
+///
+/// # Arguments
+/// * `prepared` - The template returned by `mj_prepare`.
+/// * `json_row` - JSON string containing the context variables of this render.
+///
+/// # Safety
+/// `prepared` must be a valid prepared template and `json_row` must be valid
+/// for `json_row_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn mj_prepared_render(
+    prepared: *const PreparedTemplate,
+    json_row: *const c_char,
+    json_row_len: usize,
+) -> ResultCString {
+    let Some(prepared) = prepared.as_ref() else {
+        return error_result(MJ_ERROR_INVALID_ARGUMENT, "Prepared template is null".to_string());
+    };
+    let json_str = make_str!(json_row, json_row_len, "JSON context", MJ_ERROR_INVALID_CONTEXT);
+
+    let ctx = match json_to_value(json_str, prepared.options.conversion())
+        .and_then(|ctx| bind_root(ctx, prepared.options.root_variable.as_deref()))
+    {
+        Ok(c) => c,
+        Err(e) => return error_result(MJ_ERROR_INVALID_CONTEXT, e.to_string()),
+    };
+
+    render_prepared(prepared, ctx)
+}
+
+/// Renders a prepared template with a context built with `mj_ctx_new`.
+///
+/// The context is only read, so it may be rendered again afterwards.
+///
+/// # Safety
+/// `prepared` must be a valid prepared template and `ctx` a valid context.
+#[no_mangle]
+pub unsafe extern "C" fn mj_prepared_render_ctx(
+    prepared: *const PreparedTemplate,
+    ctx: *const ContextHandle,
+) -> ResultCString {
+    let Some(prepared) = prepared.as_ref() else {
+        return error_result(MJ_ERROR_INVALID_ARGUMENT, "Prepared template is null".to_string());
+    };
+    let Some(ctx) = ctx.as_ref() else {
+        return error_result(MJ_ERROR_INVALID_ARGUMENT, "Context is null".to_string());
+    };
+
+    render_prepared(prepared, ctx.to_value())
+}
+
+/// Renders on the calling thread, so a timeout is only checked while rendering.
+fn render_prepared(prepared: &PreparedTemplate, ctx: Value) -> ResultCString {
+    let options = &prepared.options;
+    let ctx = timeout::bound_context(options.timeout_ms, &ctx);
+    let rendered = timeout::run_inline_with_timeout(options.timeout_ms, || {
+        prepared
+            .env
+            .get_template(PREPARED_TEMPLATE_NAME)
+            .and_then(|tmpl| tmpl.render(ctx))
+            .map_err(|err| options.map_fuel_error(err))
+    });
+    match rendered {
+        Ok(output) => render_result(Ok(output)),
+        Err(RenderError::Template(err)) => render_result(Err(err)),
+        Err(err) => error_result(err.code(), err.to_string()),
+    }
+}
+
+/// Frees a prepared template created by `mj_prepare`.
+///
+/// # Safety
+/// `prepared` must have been created by `mj_prepare` and must not be used
+/// after this call. Passing a null pointer is a no-op.
+#[no_mangle]
+pub unsafe extern "C" fn mj_prepared_free(prepared: *mut PreparedTemplate) {
+    if !prepared.is_null() {
+        drop(Box::from_raw(prepared));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::error::{mj_last_error_code, MJ_ERROR_SYNTAX};
+
+    /// Prepares `template` with `invariant` and `config`, null if they are `None`.
+    fn prepare_raw(
+        template: &str,
+        invariant: Option<&str>,
+        config: Option<&str>,
+    ) -> (*mut PreparedTemplate, Result<String, String>) {
+        let raw = |s: Option<&str>| s.map_or((ptr::null(), 0), |s| (s.as_ptr().cast(), s.len()));
+        let ((invariant, invariant_len), (config, config_len)) = (raw(invariant), raw(config));
+        let mut prepared = ptr::null_mut();
+        let result = unsafe {
+            mj_prepare(
+                template.as_ptr().cast(),
+                template.len(),
+                invariant,
+                invariant_len,
+                config,
+                config_len,
+                &mut prepared,
+            )
+        };
+        (prepared, result.into_result())
+    }
+
+    fn render(prepared: *mut PreparedTemplate, row: &str) -> Result<String, String> {
+        unsafe { mj_prepared_render(prepared, row.as_ptr().cast(), row.len()) }.into_result()
+    }
+
+    #[test]
+    fn rows_are_merged_over_the_invariant_variables() {
+        let invariant = r#"{"greeting": "Hello", "name": "World"}"#;
+        let config = r#"{"variable_delimiters": ["[[", "]]"]}"#;
+        let (prepared, result) =
+            prepare_raw("[[ greeting ]] [[ name ]]", Some(invariant), Some(config));
+        assert_eq!(result, Ok(String::new()));
+        assert_eq!(render(prepared, "{}"), Ok("Hello World".to_string()));
+        assert_eq!(render(prepared, r#"{"name": "Ann"}"#), Ok("Hello Ann".to_string()));
+        unsafe { mj_prepared_free(prepared) };
+    }
+
+    #[test]
+    fn failures_are_reported_with_their_message() {
+        let (prepared, result) = prepare_raw("{% if %}", None, None);
+        assert!(prepared.is_null());
+        assert_eq!(mj_last_error_code(), MJ_ERROR_SYNTAX);
+        assert!(result.unwrap_err().contains("SyntaxError"));
+
+        let (prepared, result) = prepare_raw("Hello", Some("[1, 2]"), None);
+        assert!(prepared.is_null());
+        assert_eq!(mj_last_error_code(), MJ_ERROR_INVALID_CONTEXT);
+        assert!(result.unwrap_err().contains("expected an object of variables"));
+
+        let (prepared, result) = prepare_raw("Hello", None, Some(r#"{"nope": 1}"#));
+        assert!(prepared.is_null());
+        assert_eq!(result, Err("Unknown config key 'nope'".to_string()));
+    }
+}
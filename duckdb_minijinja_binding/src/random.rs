@@ -0,0 +1,130 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::SystemTime;
+
+use minijinja::value::Object;
+use minijinja::{Environment, Error, ErrorKind, State};
+
+/// The name the generator of a render is stored under in its temps.
+const RNG_TEMP_NAME: &str = "duckdb_minijinja.rng";
+
+/// A SplitMix64 generator, small and fast but not cryptographically secure.
+#[derive(Debug)]
+struct Rng {
+    state: Mutex<u64>,
+}
+
+impl Object for Rng {}
+
+impl Rng {
+    fn new(seed: u64) -> Rng {
+        Rng {
+            state: Mutex::new(seed),
+        }
+    }
+
+    fn next_u64(&self) -> u64 {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = *state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a uniformly distributed number below `bound`, which must not be 0.
+    fn below(&self, bound: u64) -> u64 {
+        // Rejecting the lowest values leaves a multiple of `bound` to pick from.
+        let threshold = bound.wrapping_neg() % bound;
+        loop {
+            let num = self.next_u64();
+            if num >= threshold {
+                return num % bound;
+            }
+        }
+    }
+}
+
+/// Derives a seed from the randomly keyed hasher of the standard library.
+fn random_seed() -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    if let Ok(elapsed) = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+        hasher.write_u128(elapsed.as_nanos());
+    }
+    hasher.finish()
+}
+
+/// Returns the generator of the current render, creating it on first use.
+fn rng(state: &State, seed: Option<u64>) -> Arc<Rng> {
+    state.get_or_set_temp_object(RNG_TEMP_NAME, || {
+        Rng::new(seed.unwrap_or_else(random_seed))
+    })
+}
+
+/// Registers the `uuid()`, `random()` and `randrange()` global functions.
+///
+/// Every render starts a new sequence.  With `seed` set it always starts from
+/// that seed, so a render produces the same values every time, otherwise the
+/// sequence is seeded randomly.
+pub(crate) fn register_random(env: &mut Environment<'static>, seed: Option<u64>) {
+    env.add_function("uuid", move |state: &State| uuid(&rng(state, seed)));
+    env.add_function("random", move |state: &State| random(&rng(state, seed)));
+    env.add_function(
+        "randrange",
+        move |state: &State, start: i64, stop: Option<i64>, step: Option<i64>| {
+            randrange(&rng(state, seed), start, stop, step)
+        },
+    );
+}
+
+/// Returns a random version 4 UUID, e.g. `{{ uuid() }}`.
+fn uuid(rng: &Rng) -> String {
+    let high = (rng.next_u64() & !0xf000) | 0x4000;
+    let low = (rng.next_u64() & !(0xc0 << 56)) | (0x80 << 56);
+    format!(
+        "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+        high >> 32,
+        (high >> 16) & 0xffff,
+        high & 0xffff,
+        low >> 48,
+        low & 0xffff_ffff_ffff
+    )
+}
+
+/// Returns a random float in `[0, 1)`, e.g. `{{ random() }}`.
+fn random(rng: &Rng) -> f64 {
+    (rng.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Returns a random integer like Python's `random.randrange`.
+///
+/// `randrange(stop)` picks from `0` up to but excluding `stop`,
+/// `randrange(start, stop, step)` from `start`, `start + step`, ... before
+/// `stop`.
+fn randrange(rng: &Rng, start: i64, stop: Option<i64>, step: Option<i64>) -> Result<i64, Error> {
+    let (start, stop) = match stop {
+        Some(stop) => (start, stop),
+        None => (0, start),
+    };
+    let step = step.unwrap_or(1);
+    if step == 0 {
+        return Err(Error::new(
+            ErrorKind::InvalidOperation,
+            "randrange step must not be zero",
+        ));
+    }
+    let (start, stop, step) = (start as i128, stop as i128, step as i128);
+    let count = (stop - start + step - step.signum()) / step;
+    if count <= 0 {
+        return Err(Error::new(
+            ErrorKind::InvalidOperation,
+            format!(
+                "randrange got an empty range ({}, {}, {})",
+                start, stop, step
+            ),
+        ));
+    }
+    let offset = rng.below(count as u64) as i128;
+    Ok((start + offset * step) as i64)
+}
@@ -0,0 +1,156 @@
+use minijinja::syntax::SyntaxConfig;
+
+const DEFAULT_BLOCK_DELIMITERS: (&str, &str) = ("{%", "%}");
+const DEFAULT_VARIABLE_DELIMITERS: (&str, &str) = ("{{", "}}");
+const DEFAULT_COMMENT_DELIMITERS: (&str, &str) = ("{#", "#}");
+
+/// Overrides for the template syntax, `None` keeps the MiniJinja default.
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
+pub(crate) struct SyntaxOptions {
+    pub block_delimiters: Option<(String, String)>,
+    pub variable_delimiters: Option<(String, String)>,
+    pub comment_delimiters: Option<(String, String)>,
+    /// Enables line statements such as `# for x in items`.
+    ///
+    /// Lines starting with the prefix (after optional whitespace) are parsed as
+    /// blocks and the whole line including its newline is consumed, so the
+    /// output does not contain a blank line where the statement was.
+    pub line_statement_prefix: Option<String>,
+    /// Enables line comments, everything after the prefix up to the end of
+    /// the line is dropped.
+    pub line_comment_prefix: Option<String>,
+}
+
+/// Combines an optional start and end delimiter into a pair.
+///
+/// Either both or neither must be given.
+fn delimiter_pair(
+    kind: &str,
+    start: Option<String>,
+    end: Option<String>,
+) -> Result<Option<(String, String)>, String> {
+    match (start, end) {
+        (None, None) => Ok(None),
+        (Some(start), Some(end)) => Ok(Some((start, end))),
+        _ => Err(format!(
+            "Invalid syntax configuration: {} delimiters require both a start and an end",
+            kind
+        )),
+    }
+}
+
+/// Returns the configured delimiters, or `default` if they were not overridden.
+fn effective<'a>(
+    configured: &'a Option<(String, String)>,
+    default: (&'static str, &'static str),
+) -> (&'a str, &'a str) {
+    match configured {
+        Some((start, end)) => (start.as_str(), end.as_str()),
+        None => default,
+    }
+}
+
+impl SyntaxOptions {
+    /// Creates the options from optional `(start, end)` delimiter pairs.
+    pub fn from_delimiters(
+        block: (Option<String>, Option<String>),
+        variable: (Option<String>, Option<String>),
+        comment: (Option<String>, Option<String>),
+    ) -> Result<SyntaxOptions, String> {
+        Ok(SyntaxOptions {
+            block_delimiters: delimiter_pair("block", block.0, block.1)?,
+            variable_delimiters: delimiter_pair("variable", variable.0, variable.1)?,
+            comment_delimiters: delimiter_pair("comment", comment.0, comment.1)?,
+            line_statement_prefix: None,
+            line_comment_prefix: None,
+        })
+    }
+
+    /// Returns true if no part of the syntax was overridden.
+    pub fn is_default(&self) -> bool {
+        self.block_delimiters.is_none()
+            && self.variable_delimiters.is_none()
+            && self.comment_delimiters.is_none()
+            && self.line_statement_prefix.is_none()
+            && self.line_comment_prefix.is_none()
+    }
+
+    /// Returns the effective variable delimiters.
+    pub fn variable_delimiters(&self) -> (&str, &str) {
+        effective(&self.variable_delimiters, DEFAULT_VARIABLE_DELIMITERS)
+    }
+
+    /// Describes the effective syntax as a JSON object with the keys of a
+    /// render config, the line prefixes are `null` when disabled.
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "block_delimiters": effective(&self.block_delimiters, DEFAULT_BLOCK_DELIMITERS),
+            "variable_delimiters": self.variable_delimiters(),
+            "comment_delimiters": effective(&self.comment_delimiters, DEFAULT_COMMENT_DELIMITERS),
+            "line_statement_prefix": self.line_statement_prefix,
+            "line_comment_prefix": self.line_comment_prefix,
+        })
+    }
+
+    /// Validates the overrides and builds the MiniJinja syntax config.
+    pub fn build(&self) -> Result<SyntaxConfig, String> {
+        let delimiters = [
+            ("block", &self.block_delimiters, DEFAULT_BLOCK_DELIMITERS),
+            ("variable", &self.variable_delimiters, DEFAULT_VARIABLE_DELIMITERS),
+            ("comment", &self.comment_delimiters, DEFAULT_COMMENT_DELIMITERS),
+        ];
+
+        let mut starts: Vec<(&str, &str)> = Vec::with_capacity(delimiters.len());
+        for (kind, configured, default) in delimiters {
+            let (start, end) = effective(configured, default);
+            if start.is_empty() || end.is_empty() {
+                return Err(format!(
+                    "Invalid syntax configuration: {} delimiters must not be empty",
+                    kind
+                ));
+            }
+            if let Some((other, _)) = starts.iter().find(|(_, s)| *s == start) {
+                return Err(format!(
+                    "Invalid syntax configuration: {} and {} start delimiters are both '{}'",
+                    other, kind, start
+                ));
+            }
+            starts.push((kind, start));
+        }
+        for (kind, prefix) in [
+            ("line statement", &self.line_statement_prefix),
+            ("line comment", &self.line_comment_prefix),
+        ] {
+            let Some(prefix) = prefix.as_deref() else {
+                continue;
+            };
+            if let Some((other, _)) = starts.iter().find(|(_, s)| *s == prefix) {
+                return Err(format!(
+                    "Invalid syntax configuration: {} start delimiter and {} prefix are both '{}'",
+                    other, kind, prefix
+                ));
+            }
+            starts.push((kind, prefix));
+        }
+
+        let mut builder = SyntaxConfig::builder();
+        if let Some((start, end)) = &self.block_delimiters {
+            builder.block_delimiters(start.clone(), end.clone());
+        }
+        if let Some((start, end)) = &self.variable_delimiters {
+            builder.variable_delimiters(start.clone(), end.clone());
+        }
+        if let Some((start, end)) = &self.comment_delimiters {
+            builder.comment_delimiters(start.clone(), end.clone());
+        }
+        if let Some(prefix) = &self.line_statement_prefix {
+            builder.line_statement_prefix(prefix.clone());
+        }
+        if let Some(prefix) = &self.line_comment_prefix {
+            builder.line_comment_prefix(prefix.clone());
+        }
+        builder
+            .build()
+            .map_err(|e| format!("Invalid syntax configuration: {}", e))
+    }
+}
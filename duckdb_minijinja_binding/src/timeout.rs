@@ -0,0 +1,423 @@
+use std::cell::Cell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use minijinja::value::{Enumerator, Object, ObjectRepr, Value, ValueIter, ValueKind};
+use minijinja::{Environment, Error, ErrorKind};
+
+use crate::error::RenderError;
+
+thread_local! {
+    /// When the render running on this thread has to stop, with its timeout.
+    static DEADLINE: Cell<Option<(Instant, u64)>> = const { Cell::new(None) };
+    /// Whether `check_deadline` stopped the render running on this thread.
+    static TIMED_OUT: Cell<bool> = const { Cell::new(false) };
+}
+
+/// A render handed to a worker thread.
+type Job = Box<dyn FnOnce() + Send>;
+
+/// How many idle workers are kept for later renders, further ones exit.
+const MAX_IDLE_WORKERS: usize = 8;
+
+/// How many workers may exist at once, busy or idle.
+///
+/// A worker stays busy with a render that timed out until the render reaches
+/// its next check, so renders that never do would otherwise pile up threads.
+const MAX_WORKERS: usize = 64;
+
+/// The workers waiting for their next render.
+static IDLE_WORKERS: Mutex<Vec<mpsc::Sender<Job>>> = Mutex::new(Vec::new());
+
+/// How many workers exist, busy or idle.
+static WORKERS: AtomicUsize = AtomicUsize::new(0);
+
+/// Fails once the deadline of the render on the current thread has passed.
+///
+/// MiniJinja cannot be interrupted, so this is checked whenever a value is
+/// printed and on every iteration of `range` and of the sequences and maps in
+/// the context, see `bound_context`, which covers the loops and filters over
+/// them.  A render that timed out stops at its next check instead of running
+/// to completion in the background.
+pub(crate) fn check_deadline() -> Result<(), Error> {
+    match DEADLINE.with(Cell::get) {
+        Some((deadline, timeout_ms)) if Instant::now() >= deadline => {
+            TIMED_OUT.with(|cell| cell.set(true));
+            Err(Error::new(
+                ErrorKind::InvalidOperation,
+                format!("render exceeded the timeout of {} ms", timeout_ms),
+            ))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Registers a `range` that ends its iteration at the deadline of the render.
+///
+/// Loops that print nothing never reach the formatter, this bounds the ones
+/// over `range`.  Loops over the context are bounded by `bound_context`.
+pub(crate) fn register_range(env: &mut Environment) {
+    env.add_function("range", range);
+}
+
+fn range(lower: isize, upper: Option<isize>, step: Option<isize>) -> Result<Value, Error> {
+    let range = minijinja::functions::range(lower, upper, step)?;
+    Ok(Value::make_object_iterable(range, |range| match range.try_iter() {
+        Ok(iter) => Box::new(DeadlineIter(iter)),
+        Err(_) => Box::new(std::iter::empty()),
+    }))
+}
+
+/// Returns `ctx` with its sequences and maps ending their iteration at the
+/// deadline of the render, unchanged if `timeout_ms` is `0`.
+///
+/// Loops that print nothing never reach the formatter, this bounds the ones
+/// over the context and the filters that iterate it, such as `map` or `sort`.
+pub(crate) fn bound_context(timeout_ms: u64, ctx: &Value) -> Value {
+    if timeout_ms == 0 {
+        ctx.clone()
+    } else {
+        bound(ctx.clone())
+    }
+}
+
+fn bound(value: Value) -> Value {
+    match value.kind() {
+        ValueKind::Seq | ValueKind::Map => Value::from_object(Bounded(value)),
+        _ => value,
+    }
+}
+
+/// A sequence or map whose items are bound too, see `bound_context`.
+#[derive(Debug)]
+struct Bounded(Value);
+
+impl Object for Bounded {
+    fn repr(self: &Arc<Self>) -> ObjectRepr {
+        match self.0.kind() {
+            ValueKind::Map => ObjectRepr::Map,
+            _ => ObjectRepr::Seq,
+        }
+    }
+
+    fn get_value(self: &Arc<Self>, key: &Value) -> Option<Value> {
+        match self.0.get_item(key) {
+            Ok(value) if !value.is_undefined() => Some(bound(value)),
+            _ => None,
+        }
+    }
+
+    fn enumerate(self: &Arc<Self>) -> Enumerator {
+        match self.0.try_iter() {
+            Ok(iter) => Enumerator::Iter(Box::new(DeadlineIter(iter).map(bound))),
+            Err(_) => Enumerator::NonEnumerable,
+        }
+    }
+
+    fn enumerator_len(self: &Arc<Self>) -> Option<usize> {
+        self.0.len()
+    }
+}
+
+/// Iterates until the deadline of the render on the current thread passes.
+struct DeadlineIter(ValueIter);
+
+impl Iterator for DeadlineIter {
+    type Item = Value;
+
+    fn next(&mut self) -> Option<Value> {
+        check_deadline().ok()?;
+        self.0.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+/// Runs `render` with the deadline set, returning whether it was reached.
+fn with_deadline<T>(
+    deadline: Instant,
+    timeout_ms: u64,
+    render: impl FnOnce() -> Result<T, Error>,
+) -> (Result<T, Error>, bool) {
+    let previous = DEADLINE.with(|cell| cell.replace(Some((deadline, timeout_ms))));
+    let timed_out = TIMED_OUT.with(|cell| cell.replace(false));
+    let result = render();
+    DEADLINE.with(|cell| cell.set(previous));
+    (result, TIMED_OUT.with(|cell| cell.replace(timed_out)))
+}
+
+/// Runs a render on the calling thread, stopping it at the first check after
+/// `timeout_ms` milliseconds.  `0` runs the render without a limit.
+///
+/// Unlike `run_with_timeout` this does not bound slow filters and functions,
+/// it is used where the render calls back into the caller.
+pub(crate) fn run_inline_with_timeout<T>(
+    timeout_ms: u64,
+    render: impl FnOnce() -> Result<T, Error>,
+) -> Result<T, RenderError> {
+    if timeout_ms == 0 {
+        return Ok(render()?);
+    }
+
+    let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+    match with_deadline(deadline, timeout_ms, render) {
+        (_, true) => Err(RenderError::Timeout(timeout_ms)),
+        (result, false) => Ok(result?),
+    }
+}
+
+/// Runs a render, giving up after `timeout_ms` milliseconds.
+///
+/// With a timeout the render runs on a worker thread and the caller stops
+/// waiting for it at the deadline, which also bounds slow filters and
+/// functions.  The worker stops the render at its next check, see
+/// `check_deadline`, and then waits for the next one.  `0` runs the render on
+/// the calling thread without a limit.
+///
+/// Only a render stopped by the deadline fails with `RenderError::Timeout`.
+/// Any other error is returned as is, even once the deadline has passed, so a
+/// render that ran out of fuel is reported as such whichever limit is closer.
+pub(crate) fn run_with_timeout<T, F>(timeout_ms: u64, render: F) -> Result<T, RenderError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, Error> + Send + 'static,
+{
+    if timeout_ms == 0 {
+        return Ok(render()?);
+    }
+
+    let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+    let (sender, receiver) = mpsc::channel();
+    let job = Box::new(move || {
+        let result = with_deadline(deadline, timeout_ms, render);
+        // The caller may have stopped waiting, so a failed send is fine.
+        let _ = sender.send(result);
+    });
+    if let Err(msg) = run_on_worker(job) {
+        return Err(RenderError::Template(Error::new(ErrorKind::InvalidOperation, msg)));
+    }
+
+    match receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+        Ok((_, true)) => Err(RenderError::Timeout(timeout_ms)),
+        Ok((result, false)) => Ok(result?),
+        Err(mpsc::RecvTimeoutError::Timeout) => Err(RenderError::Timeout(timeout_ms)),
+        Err(mpsc::RecvTimeoutError::Disconnected) => Err(RenderError::Template(Error::new(
+            ErrorKind::InvalidOperation,
+            "the render thread panicked",
+        ))),
+    }
+}
+
+/// Hands `job` to an idle worker, starting a new one if all are busy.
+///
+/// Fails without running `job` if `MAX_WORKERS` workers are busy.
+fn run_on_worker(mut job: Job) -> Result<(), String> {
+    loop {
+        let idle = IDLE_WORKERS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pop();
+        match idle {
+            Some(worker) => match worker.send(job) {
+                Ok(()) => return Ok(()),
+                // The worker panicked, try the next one.
+                Err(mpsc::SendError(unsent)) => job = unsent,
+            },
+            None => break,
+        }
+    }
+
+    if WORKERS.fetch_add(1, Ordering::SeqCst) >= MAX_WORKERS {
+        WORKERS.fetch_sub(1, Ordering::SeqCst);
+        return Err(format!(
+            "could not start the render, all {} render threads are busy",
+            MAX_WORKERS
+        ));
+    }
+
+    let (sender, receiver) = mpsc::channel();
+    // The receiver is alive until the worker is spawned.
+    let _ = sender.send(job);
+    thread::Builder::new()
+        .name("minijinja-render".to_string())
+        .spawn(move || {
+            let _worker = Worker;
+            while let Ok(job) = receiver.recv() {
+                job();
+                let mut idle = IDLE_WORKERS.lock().unwrap_or_else(PoisonError::into_inner);
+                if idle.len() >= MAX_IDLE_WORKERS {
+                    break;
+                }
+                idle.push(sender.clone());
+            }
+        })
+        .map(|_| ())
+        .map_err(|err| {
+            WORKERS.fetch_sub(1, Ordering::SeqCst);
+            format!("could not start the render thread: {}", err)
+        })
+}
+
+/// Counts a running worker in `WORKERS`, also if its render panics.
+struct Worker;
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        WORKERS.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Held by the tests that use the workers, see `lock_idle_workers`.
+#[cfg(test)]
+static WORKERS_IN_TEST: Mutex<()> = Mutex::new(());
+
+/// Keeps other tests from using the workers and waits until all are idle.
+#[cfg(test)]
+pub(crate) fn lock_idle_workers() -> std::sync::MutexGuard<'static, ()> {
+    let guard = WORKERS_IN_TEST.lock().unwrap_or_else(PoisonError::into_inner);
+    wait_until_idle();
+    guard
+}
+
+/// Waits until the workers finished their renders, including timed out ones.
+#[cfg(test)]
+fn wait_until_idle() {
+    let started = Instant::now();
+    while IDLE_WORKERS.lock().unwrap().len() != WORKERS.load(Ordering::SeqCst) {
+        assert!(started.elapsed() < Duration::from_secs(5), "the workers did not become idle");
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use minijinja::context;
+
+    use crate::RenderOptions;
+
+    /// Loops 10^10 times without printing anything.
+    const SILENT_LOOP: &str =
+        "{% for i in range(100000) %}{% for j in range(100000) %}{% endfor %}{% endfor %}";
+
+    /// Renders `source` on a worker, sending its thread once the render is done.
+    fn render_on_worker(
+        source: &'static str,
+        done: mpsc::Sender<thread::ThreadId>,
+    ) -> Result<String, RenderError> {
+        let env = RenderOptions::default().build_environment().unwrap();
+        run_with_timeout(50, move || {
+            let result = env.render_str(source, ());
+            let _ = done.send(thread::current().id());
+            result
+        })
+    }
+
+    #[test]
+    fn render_without_output_stops_at_the_deadline() {
+        let _workers = lock_idle_workers();
+        let (done, finished) = mpsc::channel();
+        let result = render_on_worker(SILENT_LOOP, done.clone());
+        assert!(matches!(result, Err(RenderError::Timeout(50))), "{:?}", result);
+        let worker = finished
+            .recv_timeout(Duration::from_secs(5))
+            .expect("the timed out render is still running");
+
+        // The worker waits for the next render instead of exiting.
+        wait_until_idle();
+        assert_eq!(render_on_worker("{{ 1 + 1 }}", done).unwrap(), "2");
+        assert_eq!(finished.recv().unwrap(), worker);
+    }
+
+    #[test]
+    fn loops_over_the_context_stop_at_the_deadline() {
+        let _workers = lock_idle_workers();
+        let env = RenderOptions::default().build_environment().unwrap();
+        let ctx = bound_context(50, &context! { rows => vec![1; 5000] });
+        let result = run_with_timeout(50, move || {
+            env.render_str(
+                "{% for a in rows %}{% for b in rows %}{% for c in rows %}\
+                {% endfor %}{% endfor %}{% endfor %}",
+                ctx,
+            )
+        });
+        assert!(matches!(result, Err(RenderError::Timeout(50))), "{:?}", result);
+        wait_until_idle();
+    }
+
+    #[test]
+    fn bound_context_behaves_like_the_context() {
+        let env = RenderOptions::default().build_environment().unwrap();
+        let ctx = context! {
+            items => vec![3, 1, 2],
+            user => context! { name => "Ann", tags => vec!["a", "b"] },
+        };
+        let bound = bound_context(50, &ctx);
+        for source in [
+            "{{ items }} {{ user }} {{ items | length }} {{ items[0] }} {{ items[-1] }}",
+            "{{ items | sort | list }} {{ items | reverse | list }} {{ items[1:] }}",
+            "{% for i in items %}{{ loop.index }}/{{ loop.length }} {% endfor %}",
+            "{{ user.name }} {{ user['tags'] | join(',') }} {{ user.missing is defined }}",
+            "{% for k, v in user | items %}{{ k }}={{ v }} {% endfor %}{{ user | dictsort }}",
+            "{{ items | tojson }} {{ user | tojson }} {{ items == [3, 1, 2] }}",
+            "{{ user.tags is sequence }} {{ user is mapping }} {{ 'name' in user }}",
+            "{{ 2 in items }} {{ items | first }} {{ items | last }}",
+            "{{ items | map('string') | join }} {{ items | sum }} {{ items | max }}",
+        ] {
+            assert_eq!(
+                env.render_str(source, &bound).unwrap(),
+                env.render_str(source, &ctx).unwrap(),
+                "{}",
+                source
+            );
+        }
+        assert!(bound.downcast_object_ref::<Bounded>().is_some());
+        assert!(bound_context(0, &ctx).downcast_object_ref::<Bounded>().is_none());
+    }
+
+    #[test]
+    fn renders_beyond_the_worker_limit_fail_fast() {
+        let _workers = lock_idle_workers();
+        // Renders that never reach a check keep their workers busy.
+        let mut releases = Vec::new();
+        for _ in 0..MAX_WORKERS {
+            let (release, released) = mpsc::channel::<()>();
+            releases.push(release);
+            let result = run_with_timeout(1, move || {
+                let _ = released.recv();
+                Ok(())
+            });
+            assert!(matches!(result, Err(RenderError::Timeout(1))), "{:?}", result);
+        }
+
+        let started = Instant::now();
+        let result = run_with_timeout(1000, || Ok(()));
+        assert!(started.elapsed() < Duration::from_millis(500));
+        match result {
+            Err(RenderError::Template(err)) => assert_eq!(
+                err.detail(),
+                Some("could not start the render, all 64 render threads are busy")
+            ),
+            other => panic!("{:?}", other),
+        }
+
+        drop(releases);
+        wait_until_idle();
+        assert_eq!(run_with_timeout(1000, || Ok(1)).unwrap(), 1);
+    }
+
+    #[test]
+    fn range_behaves_like_the_builtin() {
+        let env = RenderOptions::default().build_environment().unwrap();
+        let render = |source| env.render_str(source, ()).unwrap();
+        assert_eq!(render("{{ range(3) | list }}"), "[0, 1, 2]");
+        assert_eq!(render("{{ range(1, 10, 4) | list }}"), "[1, 5, 9]");
+        assert_eq!(render("{% for i in range(3) %}{{ loop.length }}{% endfor %}"), "333");
+        assert_eq!(render("{{ range(4) | length }}"), "4");
+    }
+}
@@ -7,7 +7,262 @@
 #include <stdint.h>
 #include <stdlib.h>
 
+/**
+ * The call succeeded.
+ */
+#define MJ_OK 0
+
+/**
+ * An argument is invalid, e.g. a null handle or conflicting delimiters.
+ */
+#define MJ_ERROR_INVALID_ARGUMENT 1
+
+/**
+ * The context is not valid JSON or contains an invalid blob.
+ */
+#define MJ_ERROR_INVALID_CONTEXT 2
+
+/**
+ * The template has a syntax error.
+ */
+#define MJ_ERROR_SYNTAX 3
+
+/**
+ * An undefined value was used, e.g. under strict undefined behavior.
+ */
+#define MJ_ERROR_UNDEFINED 4
+
+/**
+ * A template could not be found.
+ */
+#define MJ_ERROR_TEMPLATE_NOT_FOUND 5
+
+/**
+ * A filter, test or function was called with bad arguments.
+ */
+#define MJ_ERROR_BAD_ARGUMENTS 6
+
+/**
+ * An unknown filter, test, function, method or block was used.
+ */
+#define MJ_ERROR_UNKNOWN_CALLABLE 7
+
+/**
+ * An operation failed at runtime, e.g. adding a number and a string.
+ */
+#define MJ_ERROR_INVALID_OPERATION 8
+
+/**
+ * A value could not be serialized or deserialized.
+ */
+#define MJ_ERROR_SERIALIZATION 9
+
+/**
+ * Writing the output or loading a template failed.
+ */
+#define MJ_ERROR_IO 10
+
+/**
+ * The render exceeded its fuel limit.
+ */
+#define MJ_ERROR_OUT_OF_FUEL 11
+
+/**
+ * The render did not finish within its timeout.
+ */
+#define MJ_ERROR_TIMEOUT 12
+
+/**
+ * The output did not fit into the buffer passed to `render_template_into`.
+ */
+#define MJ_ERROR_BUFFER_TOO_SMALL 13
+
+/**
+ * Any other error.
+ */
+#define MJ_ERROR_OTHER 99
+
 extern "C" {
+/**
+ * An opaque handle owning a MiniJinja `Environment` across FFI calls.
+ *
+ * Handles are created with `mj_env_create` and must be released with
+ * `mj_env_destroy`.
+ *
+ * A handle may be shared between threads.  Functions taking a `const`
+ * handle only read the environment and run concurrently with each other,
+ * functions taking a mutable handle wait for running renders to finish and
+ * have exclusive access while they modify it.  Callbacks invoked during a
+ * render must therefore not modify the handle they were invoked from.
+ */
+typedef struct EnvHandle EnvHandle;
+
+/**
+ * Receives the outcome of a callback invocation.
+ *
+ * Callbacks report their result with `mj_callback_set_ok` or
+ * `mj_callback_set_err`.  The data is copied, so the callback keeps ownership
+ * of the strings it passes in.
+ */
+typedef struct CallbackResult CallbackResult;
+
+/**
+ * An opaque handle holding a compiled template for repeated renders.
+ *
+ * Prepared templates are created with `mj_prepare` and must be released with
+ * `mj_prepared_free`.  Rendering only reads the template, so a prepared
+ * template may be rendered from several threads at once.
+ */
+typedef struct PreparedTemplate PreparedTemplate;
+
+/**
+ * An opaque handle holding a render context built value by value.
+ *
+ * A context is either a struct, created with `mj_ctx_new`, or a list,
+ * created with `mj_ctx_new_list`.  It is filled with the `mj_ctx_set_*`
+ * functions and must be released with `mj_ctx_free`.  Building the context
+ * directly skips serializing it to JSON and parsing it back.
+ *
+ * A context may be rendered any number of times and from several threads at
+ * once, but must not be modified while it is rendered.
+ */
+typedef struct ContextHandle ContextHandle;
+
+/**
+ * The render state passed to a filter added with `mj_env_add_state_filter`.
+ *
+ * It is only valid during the callback it was passed to.  Variables are
+ * looked up with `mj_filter_state_lookup`.
+ */
+typedef struct FilterState FilterState;
+
+/**
+ * A host callback taking JSON encoded arguments.
+ *
+ * # Arguments
+ * * `args_json` - A JSON array with the arguments of the call, valid for
+ *   `args_json_len` bytes and not null terminated.  For filters the filtered
+ *   value is the first element.
+ * * `user_data` - The pointer supplied when the callback was registered.
+ * * `result` - Where the callback reports its JSON encoded result or an error.
+ *
+ * The callback may be invoked from multiple threads at once.
+ */
+typedef void (*JsonCallback)(const char *args_json, uintptr_t args_json_len, void *user_data,
+                             CallbackResult *result);
+
+/**
+ * A host callback that loads template sources by name.
+ *
+ * # Arguments
+ * * `name` - The name of the requested template, valid for `name_len` bytes
+ *   and not null terminated.
+ * * `user_data` - The pointer supplied when the loader was registered.
+ * * `result` - Where the callback reports the template source with
+ *   `mj_callback_set_ok`.  Leaving it unset means the template does not exist.
+ *
+ * The callback may be invoked from multiple threads at once.
+ */
+typedef void (*LoaderCallback)(const char *name, uintptr_t name_len, void *user_data, CallbackResult *result);
+
+/**
+ * A host callback escaping a printed value for auto escaping.
+ *
+ * # Arguments
+ * * `value` - The value as a string, valid for `value_len` bytes and not null
+ *   terminated.
+ * * `user_data` - The pointer supplied when the callback was registered.
+ * * `result` - Where the callback reports the escaped string with
+ *   `mj_callback_set_ok` or an error.  Leaving it unset fails the render.
+ *
+ * The callback may be invoked from multiple threads at once.
+ */
+typedef void (*EscapeCallback)(const char *value, uintptr_t value_len, void *user_data,
+                               CallbackResult *result);
+
+/**
+ * A host callback resolving the variables of a dynamic context.
+ *
+ * # Arguments
+ * * `name` - The name of the requested variable, valid for `name_len` bytes
+ *   and not null terminated.
+ * * `user_data` - The pointer supplied with the render call.
+ * * `result` - Where the callback reports the JSON encoded value of the
+ *   variable.  Leaving it unset means the variable is undefined.
+ */
+typedef void (*ResolverCallback)(const char *name, uintptr_t name_len, void *user_data,
+                                 CallbackResult *result);
+
+/**
+ * A host callback answering method calls that MiniJinja does not know.
+ *
+ * # Arguments
+ * * `object_json` - The value the method was called on as JSON, valid for
+ *   `object_json_len` bytes and not null terminated.
+ * * `method` - The name of the method, valid for `method_len` bytes and not
+ *   null terminated.
+ * * `args_json` - A JSON array with the arguments of the call, valid for
+ *   `args_json_len` bytes and not null terminated.
+ * * `user_data` - The pointer supplied when the callback was registered.
+ * * `result` - Where the callback reports its JSON encoded result or an error.
+ *   Leaving it unset means the object has no such method.
+ *
+ * The callback may be invoked from multiple threads at once.
+ */
+typedef void (*MethodCallback)(const char *object_json, uintptr_t object_json_len, const char *method,
+                               uintptr_t method_len, const char *args_json, uintptr_t args_json_len,
+                               void *user_data, CallbackResult *result);
+
+/**
+ * A host callback implementing a filter that sees the render state.
+ *
+ * # Arguments
+ * * `args_json` - A JSON array holding the filtered value followed by the
+ *   filter arguments, valid for `args_json_len` bytes and not null terminated.
+ * * `state_json` - A JSON object describing the render state, valid for
+ *   `state_json_len` bytes and not null terminated.  It holds the `name` of
+ *   the current template and its `auto_escape` mode, `none`, `html`, `json`
+ *   or the name of a custom mode.
+ * * `state` - The render state for looking up variables with
+ *   `mj_filter_state_lookup`, valid during the call.
+ * * `user_data` - The pointer supplied when the filter was registered.
+ * * `result` - Where the callback reports its JSON encoded result or an error.
+ *
+ * The callback may be invoked from multiple threads at once.
+ */
+typedef void (*StateFilterCallback)(const char *args_json, uintptr_t args_json_len, const char *state_json,
+                                    uintptr_t state_json_len, const FilterState *state, void *user_data,
+                                    CallbackResult *result);
+
+/**
+ * A host callback receiving rendered output.
+ *
+ * # Arguments
+ * * `chunk` - The next piece of output, valid for `chunk_len` bytes during the
+ *   call and not null terminated.
+ * * `user_data` - The pointer supplied with the render call.
+ *
+ * # Returns
+ * * `0` to continue rendering, any other value aborts the render.
+ */
+typedef int (*WriteCallback)(const char *chunk, uintptr_t chunk_len, void *user_data);
+
+/**
+ * A host callback receiving the result of one row of a batch.
+ *
+ * # Arguments
+ * * `index` - The position of the row in the batch.
+ * * `ok` - Whether the row rendered, `text` is its error message otherwise.
+ * * `text` - The output or error message of the row, valid for `text_len`
+ *   bytes during the call and not null terminated.
+ * * `user_data` - The pointer supplied with the render call.
+ *
+ * # Returns
+ * * `0` to continue with the next row, any other value aborts the batch.
+ */
+typedef int (*RowCallback)(uintptr_t index, bool ok, const char *text, uintptr_t text_len,
+                           void *user_data);
+
 typedef enum ResultCString_Tag {
 	Ok,
 	Err,
@@ -34,34 +289,1768 @@ struct ResultCString {
 	};
 };
 
+/**
+ * A length-carrying result, the output may contain null bytes.
+ *
+ * `ptr` points to `len` bytes which are not null terminated.  On success
+ * they hold the output, on failure the UTF-8 error message.  Must be
+ * released with `free_result_bytes`.
+ */
+typedef struct ResultBytes {
+	bool is_ok;
+	uint8_t *ptr;
+	uintptr_t len;
+} ResultBytes;
+
 /**
  * Renders a Minijinja template from a file or a string with context variables provided as JSON.
  *
  * # Arguments
  * * `template_source` - Either the filename of the template or the template content as a string.
- * * `from_file` - If true, treat `template_source` as a filename, otherwise as template content.
  * * `json_context` - JSON string containing context variables.
+ * * `template_path` - Directory (or file) to load templates from; when set `template_source` is a template name.
+ * * `autoescape` - Whether HTML autoescaping is enabled.  Templates loaded from
+ *   `template_path` are escaped by their own name instead, for HTML if it ends
+ *   in `.html`, `.htm` or `.xml` and for JSON strings if it ends in `.json`.
+ * * `undefined_behavior` - One of `strict`, `semistrict`, `chainable` or `lenient`.
+ * * `autoescape_on` - File extensions for which autoescaping is applied.
+ * * `block_start`, `block_end` - Block delimiters, null or empty keeps `{%` and `%}`.
+ * * `variable_start`, `variable_end` - Variable delimiters, null or empty keeps `{{` and `}}`.
+ * * `comment_start`, `comment_end` - Comment delimiters, null or empty keeps `{#` and `#}`.
+ * * `line_statement_prefix` - Enables line statements (e.g. `#`), null or empty disables them.
+ *   Enabling this changes how lines starting with the prefix are parsed.
+ * * `line_comment_prefix` - Enables line comments (e.g. `##`), null or empty disables them.
+ * * `trim_blocks` - Removes the first newline after a block tag.
+ * * `lstrip_blocks` - Strips whitespace from the start of a line up to a block tag.
+ * * `keep_trailing_newline` - Keeps the trailing newline at the end of the template.
+ * * `fuel` - Maximum number of instructions a render may execute, `0` or negative is unlimited.
+ *   A render that runs out fails with `MJ_ERROR_OUT_OF_FUEL`.
+ * * `recursion_limit` - Maximum recursion depth, `0` keeps the MiniJinja default of 500.
+ *   Values above 500 are capped.
+ * * `null_as_undefined` - Whether JSON nulls in the context become undefined instead of none.
+ * * `pycompat` - Enables Python methods such as `dict.items()` and `str.upper()`.
+ * * `float_precision` - Number of decimal places printed for floats, negative keeps the
+ *   shortest representation.
+ * * `float_trailing_zeros` - Whether printed floats keep trailing zeros such as in `1.0`.
+ * * `autoescape_mode` - `html`, `json`, `none` or `auto` (by template extension), null or
+ *   empty derives the mode from `autoescape` and `autoescape_on`.
+ * * `now` - ISO 8601 timestamp or Unix epoch seconds returned by `now()`, null or empty
+ *   uses the system clock.
+ * * `random_seed` - Seed of `uuid()`, `random()` and `randrange()`, negative seeds every
+ *   render randomly.
+ * * `allow_env` - Whether `env()` may read the environment variables of the process.
+ * * `context_format` - `json`, `yaml` or `msgpack`, the format of `json_context`.  Null or
+ *   empty is JSON.  A MessagePack context is read as raw bytes, binary values in it become
+ *   bytes like blobs.
+ * * `debug` - Whether error messages show the failing line of the template with the values
+ *   of the variables in scope.
  *
  * # Returns
- * * `Ok(String)` containing the rendered template, or `Err(minijinja::Error)` if rendering fails.
+ * * `Ok` containing the rendered template, or `Err` with the error message if rendering fails.
+ *
+ * The positional options are frozen and shared by the other positional render
+ * functions, options added later are only available through `render_template_with_config`.
+ *
+ * # Safety
+ * All pointers must be valid for the given lengths and `autoescape_on` must point to
+ * `autoescape_on_count` C strings.
+ * Null pointers are reported as errors instead of being dereferenced.
  */
 struct ResultCString render_template(const char *template_source, uintptr_t template_source_len,
                                      const char *json_context, uintptr_t json_context_len, const char *template_path,
-                                     bool autoscape, const char *undefined_behavior, const char **autoescape_on,
-                                     size_t autoescape_on_count);
+                                     bool autoescape, const char *undefined_behavior, const char *const *autoescape_on,
+                                     uintptr_t autoescape_on_count, const char *block_start, const char *block_end,
+                                     const char *variable_start, const char *variable_end, const char *comment_start,
+                                     const char *comment_end, const char *line_statement_prefix,
+                                     const char *line_comment_prefix, bool trim_blocks, bool lstrip_blocks,
+                                     bool keep_trailing_newline, int64_t fuel, uintptr_t recursion_limit,
+                                     bool null_as_undefined, bool pycompat,
+                                     int32_t float_precision, bool float_trailing_zeros, const char *autoescape_mode,
+                                     const char *now, int64_t random_seed,
+                                     bool allow_env, const char *context_format, bool debug);
 
 /**
- * Frees the memory allocated for a ResultCString.
+ * Renders a Minijinja template with its options given as a JSON object.
+ *
+ * This behaves like `render_template`, but instead of one parameter per
+ * option the options are read from `config_json`, so new options do not
+ * change the signature.  The keys are named like the options of the SQL
+ * functions, e.g. `{"autoescape": false, "undefined_behavior": "strict",
+ * "variable_delimiters": ["[[", "]]"], "fuel": 1000, "debug": true}`.
+ * Missing or null keys keep their defaults.  Unknown keys, e.g. a misspelt `fuel`,
+ * are rejected with `MJ_ERROR_INVALID_ARGUMENT` unless the config sets
+ * `"ignore_unknown_keys": true`.
+ *
+ * The config can also set `"template_compression": "gzip"` to pass the
+ * template source gzip compressed, it is decompressed before parsing.  A source
+ * that decompresses to more than 64 MiB is rejected with `MJ_ERROR_INVALID_ARGUMENT`.
+ *
+ * Options added after this function are only available in the config:
+ * * `timeout_ms` - The wall-clock limit of the render in milliseconds, 0 means unlimited.
+ *   A render that exceeds it fails with `MJ_ERROR_TIMEOUT`.  With both limits the one
+ *   that stops the render first is reported.  The render is stopped at its next output or
+ *   iteration over `range` or the context, which includes filters such as `map` that
+ *   iterate it, set `fuel` to also bound recursive macros that print nothing.  Renders with
+ *   a timeout run on at most 64 worker threads, which stay busy with timed out renders until
+ *   they stop, a render finding none free fails with `MJ_ERROR_INVALID_OPERATION`.
+ * * `undefined_placeholder` - What undefined values print as under the `lenient` and
+ *   `chainable` undefined behaviors, e.g. `N/A`.  It is printed without escaping, null or
+ *   empty prints nothing.
+ * * `root_variable` - The variable a context that is not an object, such as an array or a
+ *   scalar, is bound to, e.g. `value`.  If it is null or empty such a context is rejected
+ *   with `MJ_ERROR_INVALID_CONTEXT`, a null context renders without variables.
+ * * `strict_context` - Whether a context object that repeats a key is rejected with
+ *   `MJ_ERROR_INVALID_CONTEXT` naming the key, instead of keeping its last value.
+ * * `big_integers_as_strings` - Whether context integers beyond the safe range of JavaScript
+ *   numbers, `2^53 - 1`, become strings of their digits, so IDs keep their exact digits when
+ *   they are serialized, e.g. with `tojson`.
  *
  * # Arguments
- * * `result` - The ResultCString to free
+ * * `template_source` - The template content, compressed as given by `template_compression`.
+ * * `json_context` - The context in the format given by `context_format`, JSON by default.
+ * * `config_json` - A JSON object with the render options.
+ *
+ * # Returns
+ * * `Ok` containing the rendered template, or `Err` with the error message if the config
+ *   is not a JSON object, holds an invalid value for a known key, the template source
+ *   cannot be decompressed, or rendering fails.
  *
  * # Safety
- * This function is unsafe because it takes ownership of raw pointers.
- * The caller must ensure that:
- * - The ResultCString was created by this library
- * - The ResultCString is not used after calling this function
- * - This function is called exactly once for each ResultCString
+ * All pointers must be valid for the given lengths.
  */
-void free_result_cstring(struct ResultCString result);
-}
\ No newline at end of file
+struct ResultCString render_template_with_config(const char *template_source,
+                                                 uintptr_t template_source_len,
+                                                 const char *json_context,
+                                                 uintptr_t json_context_len,
+                                                 const char *config_json,
+                                                 uintptr_t config_json_len);
+
+/**
+ * Renders a Minijinja template like `render_template_with_config` and returns
+ * the output with its length, like `render_template_bytes`.
+ *
+ * # Returns
+ * * A `ResultBytes` holding the output, or the error message if rendering
+ *   fails.  It must be released with `free_result_bytes`.
+ *
+ * # Safety
+ * All pointers must be valid for the given lengths.
+ */
+struct ResultBytes render_template_bytes_with_config(const char *template_source,
+                                                     uintptr_t template_source_len,
+                                                     const char *json_context,
+                                                     uintptr_t json_context_len,
+                                                     const char *config_json,
+                                                     uintptr_t config_json_len);
+
+/**
+ * Renders a Minijinja template like `render_template_with_config` and returns
+ * the output as JSON, like `render_template_json`.
+ *
+ * # Returns
+ * * `Ok` containing the JSON document, or `Err` with the error message if rendering fails.
+ *
+ * # Safety
+ * All pointers must be valid for the given lengths.
+ */
+struct ResultCString render_template_json_with_config(const char *template_source,
+                                                      uintptr_t template_source_len,
+                                                      const char *json_context,
+                                                      uintptr_t json_context_len,
+                                                      const char *config_json,
+                                                      uintptr_t config_json_len);
+
+/**
+ * Renders a template file.
+ *
+ * The directory of the file becomes the template directory and the file is
+ * loaded by its own name, so `{% extends %}`, `{% include %}` and
+ * `{% import %}` resolve relative to it.  The template is rendered with the
+ * default options of `render_template`.
+ *
+ * # Arguments
+ * * `path` - The path of the template file.
+ * * `json_context` - JSON string containing context variables.
+ *
+ * # Returns
+ * * `Ok` containing the rendered template, or `Err` with the error message if
+ *   the file does not exist or rendering fails.
+ *
+ * # Safety
+ * All pointers must be valid for the given lengths.
+ */
+struct ResultCString render_template_file(const char *path, uintptr_t path_len,
+                                          const char *json_context, uintptr_t json_context_len);
+
+/**
+ * Renders a Minijinja template like `render_template` and returns the output with its length.
+ *
+ * Unlike `render_template` the output may contain null bytes and callers do
+ * not need to measure it.  The arguments are the same as for `render_template`.
+ *
+ * # Returns
+ * * A `ResultBytes` holding the output, or the error message if rendering
+ *   fails.  It must be released with `free_result_bytes`.
+ *
+ * # Safety
+ * All pointers must be valid for the given lengths and `autoescape_on` must point to
+ * `autoescape_on_count` C strings.
+ */
+struct ResultBytes render_template_bytes(const char *template_source, uintptr_t template_source_len,
+                                         const char *json_context, uintptr_t json_context_len,
+                                         const char *template_path, bool autoescape, const char *undefined_behavior,
+                                         const char *const *autoescape_on, uintptr_t autoescape_on_count,
+                                         const char *block_start, const char *block_end,
+                                         const char *variable_start, const char *variable_end,
+                                         const char *comment_start, const char *comment_end,
+                                         const char *line_statement_prefix, const char *line_comment_prefix,
+                                         bool trim_blocks, bool lstrip_blocks, bool keep_trailing_newline,
+                                         int64_t fuel, uintptr_t recursion_limit, bool null_as_undefined, bool pycompat,
+                                         int32_t float_precision, bool float_trailing_zeros,
+                                         const char *autoescape_mode, const char *now, int64_t random_seed,
+                                         bool allow_env, const char *context_format, bool debug);
+
+/**
+ * Renders a Minijinja template and reports errors as JSON.
+ *
+ * This behaves like `render_template`, but the error is a JSON object with
+ * the fields `code`, `kind`, `message`, `detail`, `template_name`, `line`,
+ * `column`, `debug_info`, `partial_output` and `undefined` so callers do not
+ * have to parse the error text.  `debug_info` holds the source snippet of the error, for
+ * render errors only with `debug` enabled.  `partial_output` is the output
+ * written before the error, which shows how far a failed render got, and
+ * `null` if the render did not start, e.g. for errors in the arguments.
+ * `code` is one of
+ * the `MJ_ERROR_*` constants.  `kind` is the
+ * name of the MiniJinja error kind (e.g. `SyntaxError`), or `InvalidOptions`
+ * and `InvalidContext` for errors in the arguments, or `Timeout` for a render
+ * that exceeded `timeout_ms`.  Unknown fields are `null`, lines and columns
+ * start at 1.
+ *
+ * For an `UndefinedError` caused by an attribute, item or variable access,
+ * `undefined` describes it: the failing `expression`, e.g. `order.customer.name`,
+ * the `path` of the value the access was made on, e.g. `order.customer`, and
+ * the `name` of the attribute, index or key it lacks, e.g. `name`.  An
+ * undefined variable has a `null` path.  For paths starting at a variable set
+ * in the template, such as a loop variable, only the expression is known.
+ *
+ * # Returns
+ * * `Ok` containing the rendered template, or `Err` with the JSON error object.
+ *
+ * # Safety
+ * All pointers must be valid for the given lengths and `autoescape_on` must point to
+ * `autoescape_on_count` C strings.
+ */
+struct ResultCString render_template_ex(const char *template_source, uintptr_t template_source_len,
+                                        const char *json_context, uintptr_t json_context_len,
+                                        const char *template_path, bool autoescape, const char *undefined_behavior,
+                                        const char *const *autoescape_on, uintptr_t autoescape_on_count,
+                                        const char *block_start, const char *block_end, const char *variable_start,
+                                        const char *variable_end, const char *comment_start,
+                                        const char *comment_end, const char *line_statement_prefix,
+                                        const char *line_comment_prefix, bool trim_blocks, bool lstrip_blocks,
+                                        bool keep_trailing_newline, int64_t fuel, uintptr_t recursion_limit,
+                                        bool null_as_undefined, bool pycompat,
+                                        int32_t float_precision, bool float_trailing_zeros,
+                                        const char *autoescape_mode, const char *now, int64_t random_seed,
+                                        bool allow_env, const char *context_format, bool debug);
+
+/**
+ * Renders a Minijinja template like `render_template_with_config` and reports
+ * errors as JSON, like `render_template_ex`.
+ *
+ * # Returns
+ * * `Ok` containing the rendered template, or `Err` with the JSON error object.
+ *
+ * # Safety
+ * All pointers must be valid for the given lengths.
+ */
+struct ResultCString render_template_ex_with_config(const char *template_source,
+                                                    uintptr_t template_source_len,
+                                                    const char *json_context,
+                                                    uintptr_t json_context_len,
+                                                    const char *config_json,
+                                                    uintptr_t config_json_len);
+
+/**
+ * Renders a Minijinja template and reports the variables that were undefined.
+ *
+ * The variables referenced by the template are checked against the context,
+ * which also catches typos such as `{{ custmer }}` under lenient undefined
+ * behavior where they would otherwise render as empty strings.  Variables
+ * only referenced by included or imported templates are not reported.  The
+ * arguments are the same as for `render_template`.
+ *
+ * # Returns
+ * * `Ok` containing a JSON object `{"output": ..., "undefined": [...],
+ *   "output_bytes": ...}` with the rendered template, the sorted names of the
+ *   undefined variables and the length of the output in bytes, or `Err` with
+ *   the error message if rendering fails.  If `fuel` is positive the object
+ *   also has `fuel_consumed`, the number of instructions the render executed,
+ *   to help find expensive templates.
+ *
+ * # Safety
+ * All pointers must be valid for the given lengths and `autoescape_on` must point to
+ * `autoescape_on_count` C strings.
+ */
+struct ResultCString render_template_debug(const char *template_source, uintptr_t template_source_len,
+                                           const char *json_context, uintptr_t json_context_len,
+                                           const char *template_path, bool autoescape, const char *undefined_behavior,
+                                           const char *const *autoescape_on, uintptr_t autoescape_on_count,
+                                           const char *block_start, const char *block_end,
+                                           const char *variable_start, const char *variable_end,
+                                           const char *comment_start, const char *comment_end,
+                                           const char *line_statement_prefix, const char *line_comment_prefix,
+                                           bool trim_blocks, bool lstrip_blocks, bool keep_trailing_newline,
+                                           int64_t fuel, uintptr_t recursion_limit, bool null_as_undefined, bool pycompat,
+                                           int32_t float_precision, bool float_trailing_zeros,
+                                           const char *autoescape_mode, const char *now, int64_t random_seed,
+                                           bool allow_env, const char *context_format, bool debug);
+
+/**
+ * Renders a Minijinja template like `render_template_with_config` and reports
+ * the undefined variables, like `render_template_debug`.
+ *
+ * # Returns
+ * * `Ok` containing the JSON report of `render_template_debug`, or `Err` with
+ *   the error message if rendering fails.
+ *
+ * # Safety
+ * All pointers must be valid for the given lengths.
+ */
+struct ResultCString render_template_debug_with_config(const char *template_source,
+                                                       uintptr_t template_source_len,
+                                                       const char *json_context,
+                                                       uintptr_t json_context_len,
+                                                       const char *config_json,
+                                                       uintptr_t config_json_len);
+
+/**
+ * Renders a Minijinja template and returns the result as JSON.
+ *
+ * A template consisting of a single `{{ ... }}` expression returns the value
+ * of the expression serialized as JSON, so `{{ items | length }}` produces a
+ * number and `{{ user }}` an object.  Any other template is rendered and the
+ * output is returned as a JSON string.  The arguments are the same as for
+ * `render_template`.
+ *
+ * # Returns
+ * * `Ok` containing the JSON document, or `Err` with the error message if rendering fails.
+ *
+ * # Safety
+ * All pointers must be valid for the given lengths and `autoescape_on` must point to
+ * `autoescape_on_count` C strings.
+ */
+struct ResultCString render_template_json(const char *template_source, uintptr_t template_source_len,
+                                          const char *json_context, uintptr_t json_context_len,
+                                          const char *template_path, bool autoescape, const char *undefined_behavior,
+                                          const char *const *autoescape_on, uintptr_t autoescape_on_count,
+                                          const char *block_start, const char *block_end,
+                                          const char *variable_start, const char *variable_end,
+                                          const char *comment_start, const char *comment_end,
+                                          const char *line_statement_prefix, const char *line_comment_prefix,
+                                          bool trim_blocks, bool lstrip_blocks, bool keep_trailing_newline,
+                                          int64_t fuel, uintptr_t recursion_limit, bool null_as_undefined, bool pycompat,
+                                          int32_t float_precision, bool float_trailing_zeros,
+                                          const char *autoescape_mode, const char *now, int64_t random_seed,
+                                          bool allow_env, const char *context_format, bool debug);
+
+/**
+ * Renders a Minijinja template with several JSON contexts merged into one.
+ *
+ * The contexts are deep-merged from left to right: objects are merged key by
+ * key and any other value is replaced by the later document.  All other
+ * arguments behave as in `render_template`, except that MessagePack contexts
+ * cannot be merged.
+ *
+ * # Arguments
+ * * `json_contexts` - An array of `json_context_count` JSON documents.
+ * * `json_context_lens` - The byte lengths of the documents in `json_contexts`.
+ * * `json_context_count` - The number of documents, zero renders with an
+ *   empty context.
+ *
+ * # Safety
+ * `json_contexts` and `json_context_lens` must point to `json_context_count`
+ * elements, with every document valid for its length.  The remaining
+ * pointers follow the requirements of `render_template`.
+ */
+struct ResultCString render_template_multi(const char *template_source, uintptr_t template_source_len,
+                                           const char *const *json_contexts, const uintptr_t *json_context_lens,
+                                           uintptr_t json_context_count, const char *template_path, bool autoescape,
+                                           const char *undefined_behavior, const char *const *autoescape_on,
+                                           uintptr_t autoescape_on_count, const char *block_start,
+                                           const char *block_end, const char *variable_start,
+                                           const char *variable_end, const char *comment_start,
+                                           const char *comment_end, const char *line_statement_prefix,
+                                           const char *line_comment_prefix, bool trim_blocks, bool lstrip_blocks,
+                                           bool keep_trailing_newline, int64_t fuel, uintptr_t recursion_limit,
+                                           bool null_as_undefined, bool pycompat,
+                                           int32_t float_precision, bool float_trailing_zeros,
+                                           const char *autoescape_mode, const char *now, int64_t random_seed,
+                                           bool allow_env, const char *context_format, bool debug);
+
+/**
+ * Renders one template against each context of a JSON array.
+ *
+ * The template is compiled once and rendered with the default options of
+ * `render_template` for every element of `json_contexts`.
+ *
+ * # Arguments
+ * * `template_source` - The template content as a string.
+ * * `json_contexts` - A JSON array of contexts.
+ *
+ * # Returns
+ * * `Ok` containing a JSON array with one `{"ok": output}` or `{"err": message}`
+ *   object per context, or `Err` if the template does not compile or
+ *   `json_contexts` is not a JSON array.
+ *
+ * # Safety
+ * All pointers must be valid for the given lengths.
+ */
+struct ResultCString render_template_batch(const char *template_source, uintptr_t template_source_len,
+                                           const char *json_contexts, uintptr_t json_contexts_len);
+
+/**
+ * Renders one template against each context of a JSON array like
+ * `render_template_batch`, with the options of `render_template_with_config`.
+ *
+ * # Returns
+ * * `Ok` containing the JSON array of results of `render_template_batch`, or
+ *   `Err` if the config is invalid, the template does not compile or
+ *   `json_contexts` is not a JSON array.
+ *
+ * # Safety
+ * All pointers must be valid for the given lengths.
+ */
+struct ResultCString render_template_batch_with_config(const char *template_source,
+                                                       uintptr_t template_source_len,
+                                                       const char *json_contexts,
+                                                       uintptr_t json_contexts_len,
+                                                       const char *config_json,
+                                                       uintptr_t config_json_len);
+
+/**
+ * Renders one template against each row of a JSON array with shared globals.
+ *
+ * Like `render_template_batch`, but the keys of `json_globals` are available
+ * to every row, so invariant values are passed once instead of being repeated
+ * in each row.  A row overrides a global by having the same key, which
+ * replaces the whole value; nested objects are not merged.
+ *
+ * # Arguments
+ * * `template_source` - The template content as a string.
+ * * `json_globals` - A JSON object of variables shared by all rows.
+ * * `json_rows` - A JSON array of row contexts.
+ *
+ * # Returns
+ * * `Ok` containing a JSON array with one `{"ok": output}` or `{"err": message}`
+ *   object per row, or `Err` if the template does not compile, `json_globals`
+ *   is not a JSON object or `json_rows` is not a JSON array.
+ *
+ * # Safety
+ * All pointers must be valid for the given lengths.
+ */
+struct ResultCString render_template_batch_ctx(const char *template_source,
+                                               uintptr_t template_source_len,
+                                               const char *json_globals, uintptr_t json_globals_len,
+                                               const char *json_rows, uintptr_t json_rows_len);
+
+/**
+ * Renders one template against each row of a JSON array with shared globals
+ * like `render_template_batch_ctx`, with the options of
+ * `render_template_with_config`.
+ *
+ * # Returns
+ * * `Ok` containing the JSON array of results of `render_template_batch`, or
+ *   `Err` if the config is invalid, the template does not compile,
+ *   `json_globals` is not a JSON object or `json_rows` is not a JSON array.
+ *
+ * # Safety
+ * All pointers must be valid for the given lengths.
+ */
+struct ResultCString render_template_batch_ctx_with_config(const char *template_source,
+                                                           uintptr_t template_source_len,
+                                                           const char *json_globals,
+                                                           uintptr_t json_globals_len,
+                                                           const char *json_rows,
+                                                           uintptr_t json_rows_len,
+                                                           const char *config_json,
+                                                           uintptr_t config_json_len);
+
+/**
+ * Renders one template against each context of a JSON array into Arrow.
+ *
+ * Like `render_template_batch`, but the results are an [Arrow IPC stream]
+ * holding one record batch with a row per context and two nullable `Utf8`
+ * columns: `output` holds the rendered template and is null for contexts that
+ * failed, `error` holds the error message of those and is null otherwise.
+ * The buffers of the batch are 8 byte aligned and little endian.
+ *
+ * [Arrow IPC stream]: https://arrow.apache.org/docs/format/Columnar.html#ipc-streaming-format
+ *
+ * # Arguments
+ * * `template_source` - The template content as a string.
+ * * `json_contexts` - A JSON array of contexts.
+ *
+ * # Returns
+ * * `Ok` containing the Arrow IPC stream, or `Err` with the error message if
+ *   the template does not compile, `json_contexts` is not a JSON array or a
+ *   column exceeds the 2 GiB an Arrow string array holds.
+ *
+ * # Safety
+ * All pointers must be valid for the given lengths.
+ */
+struct ResultBytes render_template_batch_arrow(const char *template_source,
+                                               uintptr_t template_source_len,
+                                               const char *json_contexts,
+                                               uintptr_t json_contexts_len);
+
+/**
+ * Renders one template against each context of a JSON array into Arrow like
+ * `render_template_batch_arrow`, with the options of
+ * `render_template_with_config`.
+ *
+ * # Returns
+ * * `Ok` containing the Arrow IPC stream of `render_template_batch_arrow`, or
+ *   `Err` with the error message if the config is invalid or the batch fails
+ *   like `render_template_batch_arrow`.
+ *
+ * # Safety
+ * All pointers must be valid for the given lengths.
+ */
+struct ResultBytes render_template_batch_arrow_with_config(const char *template_source,
+                                                           uintptr_t template_source_len,
+                                                           const char *json_contexts,
+                                                           uintptr_t json_contexts_len,
+                                                           const char *config_json,
+                                                           uintptr_t config_json_len);
+
+/**
+ * Renders one template against each context of a JSON array, passing each
+ * result to a callback.
+ *
+ * Like `render_template_batch`, but `row_callback` receives the output or
+ * error message of every context in order as soon as it is rendered, so the
+ * results are never collected.  The template is compiled once.
+ *
+ * # Arguments
+ * * `template_source` - The template content as a string.
+ * * `json_contexts` - A JSON array of contexts.
+ * * `row_callback` - Receives the result of each context, returning nonzero
+ *   aborts the batch.
+ * * `user_data` - Passed to every call of `row_callback`.
+ *
+ * # Returns
+ * * `Ok` with an empty string once every context was passed to the callback,
+ *   or `Err` with the error message if the template does not compile,
+ *   `json_contexts` is not a JSON array or the callback aborted the batch.
+ *   An abort is reported with `MJ_ERROR_IO`.
+ *
+ * # Safety
+ * All pointers must be valid for the given lengths.
+ */
+struct ResultCString render_template_batch_stream(const char *template_source,
+                                                  uintptr_t template_source_len,
+                                                  const char *json_contexts,
+                                                  uintptr_t json_contexts_len,
+                                                  RowCallback row_callback, void *user_data);
+
+/**
+ * Renders one template against each context of a JSON array, passing each
+ * result to a callback like `render_template_batch_stream`, with the options
+ * of `render_template_with_config`.
+ *
+ * # Returns
+ * * `Ok` with an empty string once every context was passed to the callback,
+ *   or `Err` with the error message if the config is invalid or the batch
+ *   fails like `render_template_batch_stream`.
+ *
+ * # Safety
+ * All pointers must be valid for the given lengths.
+ */
+struct ResultCString render_template_batch_stream_with_config(const char *template_source,
+                                                              uintptr_t template_source_len,
+                                                              const char *json_contexts,
+                                                              uintptr_t json_contexts_len,
+                                                              const char *config_json,
+                                                              uintptr_t config_json_len,
+                                                              RowCallback row_callback,
+                                                              void *user_data);
+
+/**
+ * Renders a Minijinja template and streams the output to a callback.
+ *
+ * The output is passed to `write_callback` in chunks as it is produced instead
+ * of being collected into one string.  The template is rendered with the
+ * default options of `render_template`.
+ *
+ * # Arguments
+ * * `template_source` - The template content as a string.
+ * * `json_context` - JSON string containing context variables.
+ * * `write_callback` - Receives the output, returning nonzero aborts the render.
+ * * `user_data` - Passed to every call of `write_callback`.
+ *
+ * # Returns
+ * * `Ok` with an empty string once all output was written, or `Err` with the
+ *   error message if rendering fails or the callback aborted it.  Output
+ *   written before an error is not retracted.
+ *
+ * # Safety
+ * All pointers must be valid for the given lengths.
+ */
+struct ResultCString render_template_stream(const char *template_source, uintptr_t template_source_len,
+                                            const char *json_context, uintptr_t json_context_len,
+                                            WriteCallback write_callback, void *user_data);
+
+/**
+ * Renders a Minijinja template and streams the output to a callback like
+ * `render_template_stream`, with the options of `render_template_with_config`.
+ *
+ * The callback runs on the calling thread, so `timeout_ms` is only checked
+ * while rendering, see `timeout_ms` of `render_template_with_config`.  Time
+ * spent in the callback counts towards it.
+ *
+ * # Returns
+ * * `Ok` with an empty string once all output was written, or `Err` with the
+ *   error message if the config is invalid, rendering fails or the callback
+ *   aborted it.
+ *
+ * # Safety
+ * All pointers must be valid for the given lengths.
+ */
+struct ResultCString render_template_stream_with_config(const char *template_source,
+                                                        uintptr_t template_source_len,
+                                                        const char *json_context,
+                                                        uintptr_t json_context_len,
+                                                        const char *config_json,
+                                                        uintptr_t config_json_len,
+                                                        WriteCallback write_callback,
+                                                        void *user_data);
+
+/**
+ * Renders a Minijinja template, resolving variables through a callback.
+ *
+ * Instead of a context document the template looks its variables up on
+ * demand, so only the variables it actually uses are produced by the host.
+ * This suits wide rows of which a template only uses a few columns.  The
+ * template is rendered with the default options of `render_template`.
+ *
+ * # Arguments
+ * * `template_source` - The template content as a string.
+ * * `resolver_callback` - Called with the name of every variable the template
+ *   reads that is not set by the template itself, including globals such as
+ *   `range`.  Leaving the result unset makes the variable undefined, and
+ *   globals then resolve as usual.  The callback is invoked on the calling
+ *   thread before this function returns, at most once per name.
+ * * `user_data` - Passed to every call of `resolver_callback`.
+ *
+ * # Returns
+ * * The rendered output, or the error message if rendering fails or the
+ *   callback reported an error or an invalid value for any variable.
+ *
+ * # Safety
+ * `template_source` must be valid for `template_source_len` bytes.
+ */
+struct ResultCString render_template_dynamic(const char *template_source,
+                                             uintptr_t template_source_len,
+                                             ResolverCallback resolver_callback, void *user_data);
+
+/**
+ * Renders a Minijinja template into a caller provided buffer.
+ *
+ * This avoids allocating the output, so a caller can reuse one scratch
+ * buffer for many renders.  The template is rendered with the default
+ * options of `render_template` and goes through the same template cache.
+ *
+ * The output, or the message of an error, is written to `out_buf` without a
+ * NUL terminator and its full length is stored in `out_len`.  If it is longer
+ * than `out_cap` only the first `out_cap` bytes are written.  Output that
+ * does not fit returns `MJ_ERROR_BUFFER_TOO_SMALL`, the caller can then grow
+ * the buffer to `out_len` bytes and render again.  A truncated error message
+ * keeps the code of the error, it is only recognizable by `out_len`
+ * exceeding `out_cap`.  Passing a null `out_buf` with an `out_cap` of 0 only
+ * measures the output.
+ *
+ * # Arguments
+ * * `template_source` - The template content as a string.
+ * * `json_context` - JSON string containing context variables.
+ * * `out_buf` - The buffer receiving the output.
+ * * `out_cap` - The size of `out_buf` in bytes.
+ * * `out_len` - Receives the length of the output or error message.
+ *
+ * # Returns
+ * * `MJ_OK` if the output was written, `MJ_ERROR_BUFFER_TOO_SMALL` if it did
+ *   not fit, or the `MJ_ERROR_*` code of the error whose message was
+ *   written, even if the message did not fit.  A null `out_len` returns `MJ_ERROR_INVALID_ARGUMENT` without
+ *   writing anything.
+ *
+ * # Safety
+ * `template_source` and `json_context` must be valid for the given lengths,
+ * `out_buf` must be valid for writing `out_cap` bytes and `out_len` must be
+ * valid for writing or null.
+ */
+int32_t render_template_into(const char *template_source, uintptr_t template_source_len,
+                             const char *json_context, uintptr_t json_context_len, uint8_t *out_buf,
+                             uintptr_t out_cap, uintptr_t *out_len);
+
+/**
+ * Renders a Minijinja template into a caller provided buffer like
+ * `render_template_into`, with the options of `render_template_with_config`.
+ *
+ * An invalid config is reported like any other error, its message is written
+ * to `out_buf` and `MJ_ERROR_INVALID_ARGUMENT` is returned.  The render runs
+ * on the calling thread, so `timeout_ms` is only checked while rendering.
+ *
+ * # Returns
+ * * The code of `render_template_into`.
+ *
+ * # Safety
+ * `template_source`, `json_context` and `config_json` must be valid for the
+ * given lengths, `out_buf` must be valid for writing `out_cap` bytes and
+ * `out_len` must be valid for writing or null.
+ */
+int32_t render_template_into_with_config(const char *template_source,
+                                         uintptr_t template_source_len,
+                                         const char *json_context,
+                                         uintptr_t json_context_len,
+                                         const char *config_json,
+                                         uintptr_t config_json_len,
+                                         uint8_t *out_buf,
+                                         uintptr_t out_cap,
+                                         uintptr_t *out_len);
+
+/**
+ * Returns the error code of the last call on the current thread.
+ *
+ * Every call returning a `ResultCString` records its outcome, so this is
+ * `MJ_OK` after a successful call and one of the `MJ_ERROR_*` codes after a
+ * failed one.
+ */
+int32_t mj_last_error_code(void);
+
+/**
+ * Checks that a template compiles without rendering it.
+ *
+ * The template is parsed with the default syntax and no template logic is
+ * executed, so no context is needed.
+ *
+ * # Arguments
+ * * `template_source` - The template content as a string.
+ * * `strict` - Whether the filters, tests and functions the template calls are
+ *   also checked against those of `render_template`, which otherwise fail only
+ *   when a render reaches them, e.g. a misspelled `{{ x | uppr }}`.
+ *
+ * # Returns
+ * * `Ok` with an empty string if the template is valid, or `Err` with the
+ *   syntax error and the line and column where it occurred.  The message
+ *   goes on with the failing source line and a caret under the column and,
+ *   for errors such as `unexpected end of input`, a `hint:` line naming
+ *   what is missing, e.g. the `{% endfor %}` of an open loop.  In strict
+ *   mode a template calling unknown names fails with
+ *   `MJ_ERROR_UNKNOWN_CALLABLE` and a line per name, such as
+ *   `Unknown filter 'uppr' at line 1, column 8`.
+ *
+ * # Safety
+ * `template_source` must be valid for `template_source_len` bytes.
+ */
+struct ResultCString mj_validate_template(const char *template_source,
+                                          uintptr_t template_source_len, bool strict);
+
+/**
+ * Lists the variables a template expects from its context.
+ *
+ * The template is parsed with the default syntax and analysed without being
+ * rendered.  Variables the template assigns itself and globals such as
+ * `range` or `now` are not reported, nested lookups such as `user.name`
+ * report the root variable `user`.
+ *
+ * # Arguments
+ * * `template_source` - The template content as a string.
+ *
+ * # Returns
+ * * `Ok` containing a sorted JSON array of variable names, or `Err` with the
+ *   syntax error if the template does not compile.
+ *
+ * # Safety
+ * `template_source` must be valid for `template_source_len` bytes.
+ */
+struct ResultCString mj_template_variables(const char *template_source, uintptr_t template_source_len);
+
+/**
+ * Lists the variables a template expects that are missing from a context.
+ *
+ * This is a pre-flight check before rendering: the template is analysed
+ * like `mj_template_variables` and the variables that are not keys of the
+ * context are returned, so a caller can report all of them at once instead
+ * of failing at the first one in strict mode.  Nested lookups such as
+ * `user.name` report the root variable `user`, and globals such as `range`
+ * or `now` are never missing.  A key holding `null` counts as present.
+ *
+ * # Arguments
+ * * `template_source` - The template content as a string.
+ * * `json_context` - JSON string containing context variables.
+ *
+ * # Returns
+ * * `Ok` containing a sorted JSON array of the missing variable names, or
+ *   `Err` if the template does not compile or the context is invalid.
+ *
+ * # Safety
+ * All pointers must be valid for the given lengths.
+ */
+struct ResultCString mj_missing_variables(const char *template_source,
+                                          uintptr_t template_source_len, const char *json_context,
+                                          uintptr_t json_context_len);
+
+/**
+ * Lists the variables a template expects that are missing from a context
+ * like `mj_missing_variables`, with the options of
+ * `render_template_with_config`.
+ *
+ * The template is parsed with the configured syntax and, with a
+ * `template_path`, loaded by name, and the context is read like the context
+ * of a render.
+ *
+ * # Returns
+ * * `Ok` containing a sorted JSON array of the missing variable names, or
+ *   `Err` if the config is invalid, the template does not compile or the
+ *   context is invalid.
+ *
+ * # Safety
+ * All pointers must be valid for the given lengths.
+ */
+struct ResultCString mj_missing_variables_with_config(const char *template_source,
+                                                      uintptr_t template_source_len,
+                                                      const char *json_context,
+                                                      uintptr_t json_context_len,
+                                                      const char *config_json,
+                                                      uintptr_t config_json_len);
+
+/**
+ * Evaluates a single expression against a context and returns its value.
+ *
+ * The expression is written without delimiters, e.g. `amount * 1.2` or
+ * `items | length`, and is evaluated with the default options of
+ * `render_template`.
+ *
+ * # Arguments
+ * * `expression` - The expression source.
+ * * `json_context` - JSON string containing context variables.
+ *
+ * # Returns
+ * * `Ok` containing the value of the expression as JSON, or `Err` with the
+ *   error message if the expression does not compile or fails to evaluate.
+ *
+ * # Safety
+ * All pointers must be valid for the given lengths.
+ */
+struct ResultCString mj_eval_expr(const char *expression, uintptr_t expression_len, const char *json_context,
+                                  uintptr_t json_context_len);
+
+/**
+ * Evaluates a single expression against a context like `mj_eval_expr`, with
+ * the options of `render_template_with_config`.
+ *
+ * The context is read like the context of a render and `timeout_ms` and
+ * `fuel` bound the evaluation.  `template_path` and `template_compression`
+ * do not apply to expressions.
+ *
+ * # Returns
+ * * `Ok` containing the value of the expression as JSON, or `Err` with the
+ *   error message if the config is invalid or the expression does not
+ *   compile or fails to evaluate.
+ *
+ * # Safety
+ * All pointers must be valid for the given lengths.
+ */
+struct ResultCString mj_eval_expr_with_config(const char *expression,
+                                              uintptr_t expression_len,
+                                              const char *json_context,
+                                              uintptr_t json_context_len,
+                                              const char *config_json,
+                                              uintptr_t config_json_len);
+
+/**
+ * Returns the MiniJinja version and the version of this binding.
+ *
+ * The string looks like `minijinja 2.14.0, duckdb_minijinja_binding 0.1.0`
+ * and is meant for bug reports and compatibility checks.
+ *
+ * # Returns
+ * * A static NUL terminated string that must not be freed.
+ */
+const char *mj_version(void);
+
+/**
+ * Frees the memory allocated for a ResultCString.
+ *
+ * # Arguments
+ * * `result` - The ResultCString to free
+ *
+ * # Safety
+ * This function is unsafe because it takes ownership of raw pointers.
+ * The caller must ensure that:
+ * - The ResultCString was created by this library
+ * - The ResultCString is not used after calling this function
+ * - This function is called exactly once for each ResultCString
+ */
+void free_result_cstring(struct ResultCString result);
+
+/**
+ * Creates a new environment handle.
+ *
+ * # Returns
+ * * A pointer to a newly allocated handle which must be freed with `mj_env_destroy`.
+ */
+EnvHandle *mj_env_create(void);
+
+/**
+ * Creates an independent copy of an environment handle.
+ *
+ * The copy starts out with the templates, filters, tests, globals and
+ * settings of `handle` at the time of the call.  Changes made to either
+ * handle afterwards do not affect the other, so a configured base handle can
+ * be specialized per request, e.g. with request scoped globals.  Compiled
+ * templates are shared rather than copied, which keeps cloning cheap.
+ * Callbacks registered on `handle` are registered on the copy as well and
+ * must stay callable for its lifetime too.
+ *
+ * # Returns
+ * * A pointer to a newly allocated handle which must be freed with
+ *   `mj_env_destroy`, or null if `handle` is null.
+ *
+ * # Safety
+ * `handle` must be a valid handle or null.
+ */
+EnvHandle *mj_env_clone(const EnvHandle *handle);
+
+/**
+ * Creates a new environment handle without any filters, tests or functions.
+ *
+ * This is the starting point of a restricted dialect: only the filters,
+ * tests and functions added with `mj_env_add_filter`, `mj_env_add_test` and
+ * `mj_env_add_function` are available, using any other fails with an unknown
+ * filter, test or function error.  Templates, control structures and
+ * operators work as usual.
+ *
+ * # Returns
+ * * A pointer to a newly allocated handle which must be freed with `mj_env_destroy`.
+ */
+EnvHandle *mj_env_create_empty(void);
+
+/**
+ * Destroys an environment handle created by `mj_env_create`.
+ *
+ * # Safety
+ * The handle must have been created by `mj_env_create` and must not be used
+ * after this call. Passing a null pointer is a no-op.
+ */
+void mj_env_destroy(EnvHandle *handle);
+
+/**
+ * Renders a template string with the environment of the handle.
+ *
+ * # Arguments
+ * * `handle` - The environment handle.
+ * * `template_source` - The template content as a string.
+ * * `json_context` - JSON string containing context variables.
+ *
+ * # Safety
+ * `handle` must be a valid handle and all string pointers must be valid for
+ * the given lengths.
+ */
+struct ResultCString mj_env_render(const EnvHandle *handle, const char *template_source,
+                                   uintptr_t template_source_len, const char *json_context,
+                                   uintptr_t json_context_len);
+
+/**
+ * Renders a template string with a context built with `mj_ctx_new`.
+ *
+ * Like `mj_env_render`, but the context is only read, so it may be rendered
+ * again afterwards.
+ *
+ * # Safety
+ * `handle` must be a valid handle, `template_source` must be valid for
+ * `template_source_len` bytes and `ctx` must be a valid context.
+ */
+struct ResultCString mj_env_render_ctx(const EnvHandle *handle, const char *template_source,
+                                       uintptr_t template_source_len, const ContextHandle *ctx);
+
+/**
+ * Compiles a template and registers it with the handle under `name`.
+ *
+ * Registered templates are found by name from `{% include %}`, `{% import %}`,
+ * `{% from %}` and `{% extends %}` in any template of the handle, without a
+ * loader or files on disk.  They may be registered in any order since names are
+ * only resolved when rendering, and take precedence over templates of a loader
+ * set with `mj_env_set_loader`.  Registering a template under an existing name
+ * replaces the prior template.
+ *
+ * # Returns
+ * * `Ok` with an empty string, or `Err` with the syntax error if the template
+ *   fails to compile.
+ *
+ * # Safety
+ * `handle` must be a valid handle and all string pointers must be valid for
+ * the given lengths.
+ */
+struct ResultCString mj_env_add_template(EnvHandle *handle, const char *name, uintptr_t name_len,
+                                         const char *template_source, uintptr_t template_source_len);
+
+/**
+ * Checks that a template compiles in an environment and wraps its source
+ * into a bundle that can be registered with `mj_env_add_bundle`.
+ *
+ * The template is checked with the syntax and whitespace options of the
+ * handle, so a build step can reject invalid templates ahead of time.  A
+ * bundle is not a compiled form of the template: MiniJinja does not expose
+ * its compiled instructions, so the bundle holds the template source in a
+ * versioned layout and registering it compiles the template again.
+ * Registering bundles is therefore not faster than registering the sources
+ * with `mj_env_add_template`.
+ *
+ * # Returns
+ * * `ResultBytes` holding the bundle, or the compile error if the template is
+ *   invalid.  The result must be freed with `free_result_bytes`.
+ *
+ * # Safety
+ * `handle` must be a valid handle and `template_source` must be valid for
+ * `template_source_len` bytes.
+ */
+struct ResultBytes mj_env_bundle_template(const EnvHandle *handle, const char *template_source,
+                                          uintptr_t template_source_len);
+
+/**
+ * Registers a template from a bundle created by `mj_env_bundle_template`.
+ *
+ * This behaves like `mj_env_add_template` with the source the bundle was
+ * created from, which is compiled again.
+ *
+ * # Returns
+ * * `Ok` with an empty string, or `Err` if the bytes are not a template
+ *   source bundle or the template fails to compile.
+ *
+ * # Safety
+ * `handle` must be a valid handle, `name` must be valid for `name_len` bytes
+ * and `bytes` must be valid for `bytes_len` bytes.
+ */
+struct ResultCString mj_env_add_bundle(EnvHandle *handle, const char *name, uintptr_t name_len,
+                                       const uint8_t *bytes, uintptr_t bytes_len);
+
+/**
+ * Registers a batch of templates given as parallel name and source arrays.
+ *
+ * The batch is all or nothing: every template is compiled before any is
+ * registered, and if one fails the handle is left unchanged.  Templates of
+ * the batch may refer to each other, e.g. with `{% extends %}`, as references
+ * are resolved when rendering.  A name that appears more than once keeps
+ * the last source.
+ *
+ * # Returns
+ * * `Ok` with an empty string, or `Err` for the first template that is not
+ *   valid UTF-8 or fails to compile, naming it in the message.
+ *
+ * # Safety
+ * `handle` must be a valid handle, `names`, `name_lens`, `sources` and
+ * `source_lens` must each point to `count` elements and every name and source
+ * must be valid for its length.
+ */
+struct ResultCString mj_env_add_templates(EnvHandle *handle, const char *const *names,
+                                          const uintptr_t *name_lens, const char *const *sources,
+                                          const uintptr_t *source_lens, uintptr_t count);
+
+/**
+ * Renders a template previously registered with `mj_env_add_template`.
+ *
+ * # Arguments
+ * * `handle` - The environment handle.
+ * * `name` - The name the template was registered under.
+ * * `json_context` - JSON string containing context variables.
+ *
+ * # Safety
+ * `handle` must be a valid handle and all string pointers must be valid for
+ * the given lengths.
+ */
+struct ResultCString mj_env_render_named(const EnvHandle *handle, const char *name, uintptr_t name_len,
+                                         const char *json_context, uintptr_t json_context_len);
+
+/**
+ * Renders a single block of a template registered with `mj_env_add_template`.
+ *
+ * The template is evaluated with the context first, so top-level `{% set %}`
+ * and imports are visible to the block, and with `{% extends %}` the block
+ * as overridden by the template is rendered.  This renders e.g. only the
+ * `subject` block of an email template whose body is the rest.
+ *
+ * # Arguments
+ * * `handle` - The environment handle.
+ * * `name` - The name the template was registered under.
+ * * `block` - The name of the block to render.
+ * * `json_context` - JSON string containing context variables.
+ *
+ * # Returns
+ * * `Ok` containing the rendered block, or `Err` with `MJ_ERROR_UNKNOWN_CALLABLE`
+ *   if the template has no such block.
+ *
+ * # Safety
+ * `handle` must be a valid handle and all string pointers must be valid for
+ * the given lengths.
+ */
+struct ResultCString mj_env_render_block(const EnvHandle *handle, const char *name,
+                                         uintptr_t name_len, const char *block, uintptr_t block_len,
+                                         const char *json_context, uintptr_t json_context_len);
+
+/**
+ * Renders every block of a template registered with `mj_env_add_template`.
+ *
+ * The template is evaluated once and each block is rendered from the same
+ * state, so an email template with a `subject` and a `body` block yields
+ * `{"subject": "...", "body": "..."}` in one call.  Nothing outside the
+ * blocks is rendered.
+ *
+ * Only the blocks the template itself defines are returned, nested blocks
+ * included.  With `{% extends %}` these are its overrides, rendered as the
+ * override with `{{ super() }}` reaching the parent; blocks only the parent
+ * defines are not returned but can be rendered with `mj_env_render_block`.
+ *
+ * # Arguments
+ * * `handle` - The environment handle.
+ * * `name` - The name the template was registered under.
+ * * `json_context` - JSON string containing context variables.
+ *
+ * # Returns
+ * * `Ok` containing a JSON object mapping block names to their output, or
+ *   `Err` if evaluating the template or rendering a block fails.
+ *
+ * # Safety
+ * `handle` must be a valid handle and all string pointers must be valid for
+ * the given lengths.
+ */
+struct ResultCString mj_env_render_blocks(const EnvHandle *handle, const char *name,
+                                          uintptr_t name_len, const char *json_context,
+                                          uintptr_t json_context_len);
+
+/**
+ * Removes a registered template from the handle.
+ *
+ * # Returns
+ * * `true` if a template with that name was registered, `false` otherwise.
+ *
+ * # Safety
+ * `handle` must be a valid handle or null and `name` must be valid for
+ * `name_len` bytes.
+ */
+bool mj_env_remove_template(EnvHandle *handle, const char *name, uintptr_t name_len);
+
+/**
+ * Removes all registered templates from the handle.
+ *
+ * # Safety
+ * `handle` must be a valid handle or null.
+ */
+void mj_env_clear_templates(EnvHandle *handle);
+
+/**
+ * Checks whether a template is registered with the handle.
+ *
+ * # Safety
+ * `handle` must be a valid handle or null and `name` must be valid for
+ * `name_len` bytes.
+ */
+bool mj_env_template_exists(const EnvHandle *handle, const char *name, uintptr_t name_len);
+
+/**
+ * Lists the names of all templates registered with the handle.
+ *
+ * # Returns
+ * * `Ok` containing a JSON array of template names (`[]` if none are registered).
+ *
+ * # Safety
+ * `handle` must be a valid handle.
+ */
+struct ResultCString mj_env_list_templates(const EnvHandle *handle);
+
+/**
+ * Sets the maximum recursion depth for renders with the handle.
+ *
+ * `0` restores the MiniJinja default of 500, values above that are capped.
+ *
+ * # Safety
+ * `handle` must be a valid handle or null.
+ */
+void mj_env_set_recursion_limit(EnvHandle *handle, uintptr_t recursion_limit);
+
+/**
+ * Enables or disables Python compatible methods for renders with the handle.
+ *
+ * When enabled, templates can call the Python methods Jinja2 templates
+ * commonly use, such as `dict.items()`, `str.upper()` or `list.count()`.
+ *
+ * # Safety
+ * `handle` must be a valid handle or null.
+ */
+void mj_env_set_pycompat(EnvHandle *handle, bool enabled);
+
+/**
+ * Sets how values are escaped in renders with the handle.
+ *
+ * `mode` is one of `html`, `json` (for use inside JSON string literals),
+ * `none` or `auto`, which picks the escaping from the template name.  Handles
+ * start out in a mode like `auto` that does not escape `.json` templates.
+ *
+ * The escaping of a template is fixed when it is compiled, so the mode
+ * applies to templates added or loaded afterwards and to `mj_env_render`.
+ *
+ * # Returns
+ * * `Ok` with an empty string, or `Err` if the mode is not known.
+ *
+ * # Safety
+ * `handle` must be a valid handle and `mode` must be valid for `mode_len` bytes.
+ */
+struct ResultCString mj_env_set_autoescape_mode(EnvHandle *handle, const char *mode, uintptr_t mode_len);
+
+/**
+ * Sets a host callback that escapes values in renders with the handle.
+ *
+ * Whenever auto escaping is active, whatever the mode, the callback receives
+ * the string form of each printed value and its result is written instead,
+ * replacing the built-in escaping.  Values marked safe, e.g. with the `safe`
+ * filter, are printed unchanged without calling it.  Which templates escape
+ * is still decided by `mj_env_set_autoescape_mode` and `{% autoescape %}`.
+ * Passing a null callback restores the built-in escaping.
+ *
+ * # Safety
+ * `handle` must be a valid handle or null and `callback` must stay callable
+ * with `user_data` for the lifetime of the handle.
+ */
+void mj_env_set_escape_callback(EnvHandle *handle, EscapeCallback callback, void *user_data);
+
+/**
+ * Returns the auto escape mode the handle picks for a template name.
+ *
+ * This asks the mode set with `mj_env_set_autoescape_mode` the way compiling
+ * a template of that name would, e.g. to check which templates an `auto`
+ * mode escapes.  A `{% autoescape %}` block in the template still overrides
+ * it and an escape callback only changes how values are escaped, not
+ * whether.
+ *
+ * # Returns
+ * * `Ok` with `html`, `json`, `none` or the name of a custom mode.
+ *
+ * # Safety
+ * `handle` must be a valid handle and `name` must be valid for `name_len`
+ * bytes.
+ */
+struct ResultCString mj_env_autoescape_for(const EnvHandle *handle, const char *name,
+                                           uintptr_t name_len);
+
+/**
+ * Sets the template syntax of the handle.
+ *
+ * `config_json` is a JSON object with the `block_delimiters`,
+ * `variable_delimiters` and `comment_delimiters` as `[start, end]` pairs and
+ * the `line_statement_prefix` and `line_comment_prefix`, named like the
+ * options of the SQL functions.  Missing keys keep the MiniJinja default, so
+ * `{}` restores the default syntax.  A render config is accepted as well, its
+ * other options are ignored, but unknown keys are rejected.  The syntax is
+ * validated once here instead of on every render.
+ *
+ * Like the autoescape mode, the syntax applies to templates added or loaded
+ * afterwards and to `mj_env_render`.
+ *
+ * # Returns
+ * * `Ok` with an empty string, or `Err` describing why the config is
+ *   invalid, e.g. empty delimiters or two that start alike.  The syntax is
+ *   only changed on success.
+ *
+ * # Safety
+ * `handle` must be a valid handle and `config_json` must be valid for
+ * `config_json_len` bytes.
+ */
+struct ResultCString mj_env_set_syntax(EnvHandle *handle, const char *config_json,
+                                       uintptr_t config_json_len);
+
+/**
+ * Returns the template syntax of the handle.
+ *
+ * # Returns
+ * * `Ok` with a JSON object in the format taken by `mj_env_set_syntax`,
+ *   holding the effective delimiters and the line prefixes, which are `null`
+ *   when disabled.
+ *
+ * # Safety
+ * `handle` must be a valid handle.
+ */
+struct ResultCString mj_env_get_syntax(const EnvHandle *handle);
+
+/**
+ * Freezes the time `now()` returns in renders with the handle.
+ *
+ * `now` is an ISO 8601 timestamp or Unix epoch seconds, timestamps without
+ * an offset are taken as UTC.  Passing null or an empty string makes `now()`
+ * read the system clock again.
+ *
+ * # Returns
+ * * `Ok` with an empty string, or `Err` if the timestamp is invalid.
+ *
+ * # Safety
+ * `handle` must be a valid handle and `now` must be null or valid for
+ * `now_len` bytes.
+ */
+struct ResultCString mj_env_set_now(EnvHandle *handle, const char *now, uintptr_t now_len);
+
+/**
+ * Seeds `uuid()`, `random()` and `randrange()` in renders with the handle.
+ *
+ * Every render starts its sequence from the seed, so rendering a template
+ * with the same context always produces the same values.  A negative seed
+ * makes every render pick a random seed again.  The generator is not
+ * suitable for secrets.
+ *
+ * # Safety
+ * `handle` must be a valid handle or null.
+ */
+void mj_env_set_random_seed(EnvHandle *handle, int64_t seed);
+
+/**
+ * Enables or disables debug information in errors of renders with the handle.
+ *
+ * With debug information error messages show the failing line of the
+ * template and the values of the variables in scope.  It is disabled by
+ * default, as error messages may be shown where the template source must
+ * not be.
+ *
+ * # Safety
+ * `handle` must be a valid handle or null.
+ */
+void mj_env_set_debug(EnvHandle *handle, bool enabled);
+
+/**
+ * Allows or forbids `env()` to read environment variables in renders with
+ * the handle.
+ *
+ * Access is disabled by default, `env()` then fails with an error.
+ *
+ * # Safety
+ * `handle` must be a valid handle or null.
+ */
+void mj_env_set_allow_env(EnvHandle *handle, bool allowed);
+
+/**
+ * Sets a host callback answering method calls MiniJinja does not know.
+ *
+ * This lets values passed in through globals, functions or the context
+ * respond to methods such as `{{ account.balance_in('EUR') }}`.  The callback
+ * receives the value as JSON together with the method name and arguments.
+ * Leaving the result unset reports an unknown method.
+ *
+ * Methods MiniJinja implements itself never reach the callback.  If Python
+ * compatible methods are enabled with `mj_env_set_pycompat`, the callback is
+ * asked first and only the methods it does not answer fall back to them.
+ * Passing a null callback removes it.
+ *
+ * # Safety
+ * `handle` must be a valid handle or null and `callback` must stay callable
+ * with `user_data` for the lifetime of the handle.
+ */
+void mj_env_set_unknown_method_callback(EnvHandle *handle, MethodCallback callback, void *user_data);
+
+/**
+ * Reports a successful JSON encoded result from a callback.
+ *
+ * # Safety
+ * `result` must be the pointer passed to the callback and `json` must be
+ * valid for `json_len` bytes.
+ */
+void mj_callback_set_ok(CallbackResult *result, const char *json, uintptr_t json_len);
+
+/**
+ * Reports an error from a callback.
+ *
+ * # Safety
+ * `result` must be the pointer passed to the callback and `message` must be
+ * valid for `message_len` bytes.
+ */
+void mj_callback_set_err(CallbackResult *result, const char *message, uintptr_t message_len);
+
+/**
+ * Looks up a variable visible at the point where a filter was called.
+ *
+ * This sees the context of the render, globals and variables set in the
+ * template, e.g. by `{% set %}` or a loop.
+ *
+ * # Returns
+ * * `Ok` with the JSON encoded value, or `Err` if the variable is undefined
+ *   or cannot be serialized.
+ *
+ * # Safety
+ * `state` must be the pointer passed to the callback and `name` must be
+ * valid for `name_len` bytes.
+ */
+struct ResultCString mj_filter_state_lookup(const FilterState *state, const char *name,
+                                            uintptr_t name_len);
+
+/**
+ * Registers a filter implemented by a host callback.
+ *
+ * The callback receives a JSON array holding the filtered value followed by
+ * the filter arguments and reports a JSON encoded result.  Errors reported by
+ * the callback fail the render.
+ *
+ * # Returns
+ * * `Ok` with an empty string, or `Err` if the callback is null.
+ *
+ * # Safety
+ * `handle` must be a valid handle, `name` must be valid for `name_len` bytes
+ * and `callback` must stay callable with `user_data` for the lifetime of the handle.
+ */
+struct ResultCString mj_env_add_filter(EnvHandle *handle, const char *name, uintptr_t name_len,
+                                       JsonCallback callback, void *user_data);
+
+/**
+ * Registers a filter implemented by a host callback that sees the render state.
+ *
+ * This works like `mj_env_add_filter`, but the callback also receives the
+ * name of the current template, the auto escape mode and a state for looking
+ * up variables with `mj_filter_state_lookup`.  Describing the state costs an
+ * extra JSON document per call and every lookup serializes the variable, so
+ * prefer `mj_env_add_filter` for filters that only need their arguments.
+ *
+ * # Returns
+ * * `Ok` with an empty string, or `Err` if the callback is null.
+ *
+ * # Safety
+ * `handle` must be a valid handle, `name` must be valid for `name_len` bytes
+ * and `callback` must stay callable with `user_data` for the lifetime of the handle.
+ */
+struct ResultCString mj_env_add_state_filter(EnvHandle *handle, const char *name,
+                                             uintptr_t name_len, StateFilterCallback callback,
+                                             void *user_data);
+
+/**
+ * Registers a global function implemented by a host callback.
+ *
+ * The callback receives the function arguments as a JSON array and reports a
+ * JSON encoded result.  Errors reported by the callback fail the render.
+ *
+ * # Returns
+ * * `Ok` with an empty string, or `Err` if the callback is null.
+ *
+ * # Safety
+ * `handle` must be a valid handle, `name` must be valid for `name_len` bytes
+ * and `callback` must stay callable with `user_data` for the lifetime of the handle.
+ */
+struct ResultCString mj_env_add_function(EnvHandle *handle, const char *name, uintptr_t name_len,
+                                         JsonCallback callback, void *user_data);
+
+/**
+ * Registers a test implemented by a host callback.
+ *
+ * The callback receives a JSON array holding the tested value followed by
+ * the test arguments and must report `true` or `false`.  Any other result
+ * fails the render.
+ *
+ * # Returns
+ * * `Ok` with an empty string, or `Err` if the callback is null.
+ *
+ * # Safety
+ * `handle` must be a valid handle, `name` must be valid for `name_len` bytes
+ * and `callback` must stay callable with `user_data` for the lifetime of the handle.
+ */
+struct ResultCString mj_env_add_test(EnvHandle *handle, const char *name, uintptr_t name_len,
+                                     JsonCallback callback, void *user_data);
+
+/**
+ * Removes a filter, built-in or added with `mj_env_add_filter`.
+ *
+ * Templates using the filter afterwards fail with an unknown filter error.
+ * Removing a filter that does not exist does nothing.
+ *
+ * # Safety
+ * `handle` must be a valid handle or null and `name` must be valid for
+ * `name_len` bytes.
+ */
+void mj_env_remove_filter(EnvHandle *handle, const char *name, uintptr_t name_len);
+
+/**
+ * Removes a global function such as `range`, see `mj_env_remove_filter`.
+ *
+ * Functions share their namespace with globals, so this also removes a
+ * global added with `mj_env_add_global`.
+ *
+ * # Safety
+ * `handle` must be a valid handle or null and `name` must be valid for
+ * `name_len` bytes.
+ */
+void mj_env_remove_function(EnvHandle *handle, const char *name, uintptr_t name_len);
+
+/**
+ * Adds a global variable available to every render with the handle.
+ *
+ * Variables of the same name in the render context shadow the global.
+ * Adding a global under an existing name replaces it.
+ *
+ * # Returns
+ * * `Ok` with an empty string, or `Err` if the value is not valid JSON.
+ *
+ * # Safety
+ * `handle` must be a valid handle and all string pointers must be valid for
+ * the given lengths.
+ */
+struct ResultCString mj_env_add_global(EnvHandle *handle, const char *name, uintptr_t name_len,
+                                       const char *json_value, uintptr_t json_value_len);
+
+/**
+ * Sets a host callback used to load templates that are not registered.
+ *
+ * The loader is consulted by `mj_env_render_named` as well as by
+ * `{% include %}`, `{% import %}` and `{% extends %}`.  Loaded templates are
+ * cached in the handle until they are removed or cleared.  Passing a null
+ * callback removes the loader.
+ *
+ * # Safety
+ * `handle` must be a valid handle or null and `callback` must stay callable
+ * with `user_data` for the lifetime of the handle.
+ */
+void mj_env_set_loader(EnvHandle *handle, LoaderCallback callback, void *user_data);
+
+/**
+ * Sets a loader that layers in-memory overrides over a template directory.
+ *
+ * A template is looked up in the overrides first and only loaded from
+ * `base_path` if no override has its name, so `{% extends "base.html" %}`
+ * picks an override of `base.html` when there is one and the file otherwise.
+ * This replaces a loader set with `mj_env_set_loader`, and templates
+ * registered with `mj_env_add_template` still take precedence over both.
+ * Loaded templates are cached in the handle until they are removed or
+ * cleared.  A name that appears more than once keeps the last source.
+ *
+ * # Returns
+ * * `Ok` with an empty string, or `Err` if an argument is null or not valid
+ *   UTF-8.  The loader is only changed on success.
+ *
+ * # Safety
+ * `handle` must be a valid handle, `names`, `name_lens`, `sources` and
+ * `source_lens` must each point to `count` elements and every name, source
+ * and `base_path` must be valid for its length.
+ */
+struct ResultCString mj_env_set_overlay_loader(EnvHandle *handle, const char *const *names,
+                                               const uintptr_t *name_lens,
+                                               const char *const *sources,
+                                               const uintptr_t *source_lens, uintptr_t count,
+                                               const char *base_path, uintptr_t base_path_len);
+
+/**
+ * Removes all compiled templates from the cache used by `render_template`.
+ */
+void mj_clear_template_cache(void);
+
+/**
+ * Sets how many compiled templates the cache used by `render_template` keeps.
+ *
+ * The least recently used templates are evicted once the limit is reached,
+ * `0` disables the cache.  The default is 64.
+ */
+void mj_set_template_cache_size(uintptr_t max_entries);
+
+/**
+ * Compiles a template once so it can be rendered for many contexts.
+ *
+ * # Arguments
+ * * `template_source` - The template content as a string.
+ * * `json_invariant` - A JSON object of the variables shared by every render,
+ *   passed once instead of being repeated in each row.  A row overrides an
+ *   invariant variable by having the same key, which replaces the whole
+ *   value.  Null means no invariant variables.
+ * * `config_json` - The options as a JSON object, see
+ *   `render_template_with_config`.  Null renders with the default options of
+ *   `render_template`.
+ * * `prepared_out` - Receives the prepared template, which must be freed with
+ *   `mj_prepared_free`, or null if preparing it fails.
+ *
+ * # Returns
+ * * `Ok` with an empty string once the template is prepared, or `Err` with
+ *   the error message if the config or the invariant variables are invalid or
+ *   the template does not compile.
+ *
+ * # Safety
+ * All pointers must be null or valid for the given lengths and `prepared_out`
+ * must be valid for writing.
+ */
+struct ResultCString mj_prepare(const char *template_source,
+                                uintptr_t template_source_len,
+                                const char *json_invariant,
+                                uintptr_t json_invariant_len,
+                                const char *config_json,
+                                uintptr_t config_json_len,
+                                PreparedTemplate **prepared_out);
+
+/**
+ * Renders a prepared template with one context.
+ *
+ * # Arguments
+ * * `prepared` - The template returned by `mj_prepare`.
+ * * `json_row` - JSON string containing the context variables of this render.
+ *
+ * # Safety
+ * `prepared` must be a valid prepared template and `json_row` must be valid
+ * for `json_row_len` bytes.
+ */
+struct ResultCString mj_prepared_render(const PreparedTemplate *prepared, const char *json_row,
+                                        uintptr_t json_row_len);
+
+/**
+ * Renders a prepared template with a context built with `mj_ctx_new`.
+ *
+ * The context is only read, so it may be rendered again afterwards.
+ *
+ * # Safety
+ * `prepared` must be a valid prepared template and `ctx` a valid context.
+ */
+struct ResultCString mj_prepared_render_ctx(const PreparedTemplate *prepared,
+                                            const ContextHandle *ctx);
+
+/**
+ * Frees a prepared template created by `mj_prepare`.
+ *
+ * # Safety
+ * `prepared` must have been created by `mj_prepare` and must not be used
+ * after this call. Passing a null pointer is a no-op.
+ */
+void mj_prepared_free(PreparedTemplate *prepared);
+
+/**
+ * Creates an empty struct context.
+ *
+ * # Returns
+ * * A pointer to the context which must be freed with `mj_ctx_free`, unless
+ *   it is passed to `mj_ctx_set_struct`.
+ */
+ContextHandle *mj_ctx_new(void);
+
+/**
+ * Creates an empty list context.
+ *
+ * Values set on a list are appended in order, their keys are ignored and may
+ * be null.  A list is usually nested into a struct with `mj_ctx_set_list`,
+ * rendering it directly makes its items unreachable by name.
+ *
+ * # Returns
+ * * A pointer to the context which must be freed with `mj_ctx_free`, unless
+ *   it is passed to `mj_ctx_set_list`.
+ */
+ContextHandle *mj_ctx_new_list(void);
+
+/**
+ * Frees a context created by `mj_ctx_new` or `mj_ctx_new_list`.
+ *
+ * # Safety
+ * `ctx` must have been created by `mj_ctx_new` or `mj_ctx_new_list` and must
+ * not be used after this call. Passing a null pointer is a no-op.
+ */
+void mj_ctx_free(ContextHandle *ctx);
+
+/**
+ * Sets `key` to none, like a SQL NULL.
+ *
+ * All `mj_ctx_set_*` functions replace an existing field of a struct and
+ * append to a list.
+ *
+ * # Returns
+ * * `true` on success, `false` if `ctx` is null or `key` is null or not valid
+ *   UTF-8 for a struct.  `mj_last_error_code` then reports
+ *   `MJ_ERROR_INVALID_ARGUMENT`.
+ *
+ * # Safety
+ * `ctx` must be a valid context or null and `key` must be valid for
+ * `key_len` bytes.
+ */
+bool mj_ctx_set_null(ContextHandle *ctx, const char *key, uintptr_t key_len);
+
+/**
+ * Sets `key` to a boolean, see `mj_ctx_set_null`.
+ *
+ * # Safety
+ * `ctx` must be a valid context or null and `key` must be valid for
+ * `key_len` bytes.
+ */
+bool mj_ctx_set_bool(ContextHandle *ctx, const char *key, uintptr_t key_len, bool value);
+
+/**
+ * Sets `key` to an integer, see `mj_ctx_set_null`.
+ *
+ * # Safety
+ * `ctx` must be a valid context or null and `key` must be valid for
+ * `key_len` bytes.
+ */
+bool mj_ctx_set_int(ContextHandle *ctx, const char *key, uintptr_t key_len, int64_t value);
+
+/**
+ * Sets `key` to a float, see `mj_ctx_set_null`.
+ *
+ * # Safety
+ * `ctx` must be a valid context or null and `key` must be valid for
+ * `key_len` bytes.
+ */
+bool mj_ctx_set_float(ContextHandle *ctx, const char *key, uintptr_t key_len, double value);
+
+/**
+ * Sets `key` to a string, see `mj_ctx_set_null`.
+ *
+ * The string is copied and must be valid UTF-8, otherwise `false` is
+ * returned.
+ *
+ * # Safety
+ * `ctx` must be a valid context or null, `key` must be valid for `key_len`
+ * bytes and `value` for `value_len` bytes.
+ */
+bool mj_ctx_set_string(ContextHandle *ctx, const char *key, uintptr_t key_len, const char *value,
+                       uintptr_t value_len);
+
+/**
+ * Sets `key` to bytes, like a blob, see `mj_ctx_set_null`.
+ *
+ * The bytes are copied.
+ *
+ * # Safety
+ * `ctx` must be a valid context or null, `key` must be valid for `key_len`
+ * bytes and `value` for `value_len` bytes.
+ */
+bool mj_ctx_set_bytes(ContextHandle *ctx, const char *key, uintptr_t key_len, const uint8_t *value,
+                      uintptr_t value_len);
+
+/**
+ * Sets `key` to a list created with `mj_ctx_new_list`, see `mj_ctx_set_null`.
+ *
+ * Ownership of `child` passes to this call, it must not be used or freed
+ * afterwards, even if the call fails.
+ *
+ * # Returns
+ * * `true` on success, `false` if `child` is not a list or the arguments are
+ *   invalid as for `mj_ctx_set_null`.
+ *
+ * # Safety
+ * `ctx` must be a valid context or null, `key` must be valid for `key_len`
+ * bytes and `child` must be a valid context other than `ctx` or null.
+ */
+bool mj_ctx_set_list(ContextHandle *ctx, const char *key, uintptr_t key_len, ContextHandle *child);
+
+/**
+ * Sets `key` to a struct created with `mj_ctx_new`, see `mj_ctx_set_list`.
+ *
+ * # Safety
+ * `ctx` must be a valid context or null, `key` must be valid for `key_len`
+ * bytes and `child` must be a valid context other than `ctx` or null.
+ */
+bool mj_ctx_set_struct(ContextHandle *ctx, const char *key, uintptr_t key_len,
+                       ContextHandle *child);
+
+/**
+ * Frees the memory allocated for a ResultBytes.
+ *
+ * # Safety
+ * The ResultBytes must have been created by this library and must not be
+ * used after calling this function, which must be called exactly once for it.
+ */
+void free_result_bytes(struct ResultBytes result);
+
+}
@@ -18,7 +18,59 @@ struct MinijinjaRenderBindData : public FunctionData {
 	bool autoescape = true;
 	vector<string> autoescape_on;
 	string undefined_behavior;
+	// Custom syntax delimiters, empty strings keep the MiniJinja defaults.
+	string block_start;
+	string block_end;
+	string variable_start;
+	string variable_end;
+	string comment_start;
+	string comment_end;
+	// Line statement/comment prefixes, empty strings leave them disabled.
+	string line_statement_prefix;
+	string line_comment_prefix;
+	// Whitespace control, the defaults match MiniJinja.
+	bool trim_blocks = false;
+	bool lstrip_blocks = false;
+	bool keep_trailing_newline = false;
+	// Instruction limit for a single render, 0 means unlimited.
+	int64_t fuel = 0;
+	// Maximum recursion depth, 0 keeps the MiniJinja default.
+	idx_t recursion_limit = 0;
+	// Whether JSON nulls in the context are undefined rather than none.
+	bool null_as_undefined = false;
+	// Whether Python methods such as dict.items() are available.
+	bool pycompat = false;
+	// Decimal places printed for floats, negative keeps the shortest representation.
+	int32_t float_precision = -1;
+	// Whether printed floats keep trailing zeros such as in 1.0.
+	bool float_trailing_zeros = true;
+	// Explicit autoescape mode, empty derives it from autoescape and autoescape_on.
+	string autoescape_mode;
+	// Fixed time returned by now(), empty uses the system clock.
+	string now;
+	// Seed of uuid(), random() and randrange(), negative seeds every render randomly.
+	int64_t random_seed = -1;
+	// Whether env() may read the environment variables of the process.
+	bool allow_env = false;
+	// Format of the context, empty is JSON.
+	string context_format;
+	// Whether errors show the failing line of the template.
+	bool debug = false;
+	// Wall-clock limit of a single render in milliseconds, 0 means unlimited.
+	uint64_t timeout_ms = 0;
+	// What undefined values print as in lenient and chainable mode, empty prints nothing.
+	string undefined_placeholder;
+	// Variable a context that is not an object is bound to, empty rejects such contexts.
+	string root_variable;
+	// Whether a context object that repeats a key is rejected.
+	bool strict_context = false;
+	// Whether context integers beyond 2^53 - 1 become strings.
+	bool big_integers_as_strings = false;
+	// Whether the result is returned as JSON, see render_template_json.
+	bool json_output = false;
 	int optional_args = 0;
+	// The options above as the config of render_template_with_config.
+	string config_json;
 
 	MinijinjaRenderBindData(string template_path_p, bool autoescape_p, vector<string> autoescape_on_p,
 	                        string undefined_behavior_p, int optional_args_p)
@@ -32,17 +84,138 @@ struct MinijinjaRenderBindData : public FunctionData {
 };
 
 unique_ptr<FunctionData> MinijinjaRenderBindData::Copy() const {
-	return make_uniq<MinijinjaRenderBindData>(template_path, autoescape, autoescape_on, undefined_behavior,
-	                                          optional_args);
+	return make_uniq<MinijinjaRenderBindData>(*this);
 }
 
 bool MinijinjaRenderBindData::Equals(const FunctionData &other_p) const {
 	auto &other = (const MinijinjaRenderBindData &)other_p;
 	return template_path == other.template_path && autoescape == other.autoescape &&
 	       autoescape_on == other.autoescape_on && undefined_behavior == other.undefined_behavior &&
+	       block_start == other.block_start && block_end == other.block_end &&
+	       variable_start == other.variable_start && variable_end == other.variable_end &&
+	       comment_start == other.comment_start && comment_end == other.comment_end &&
+	       line_statement_prefix == other.line_statement_prefix &&
+	       line_comment_prefix == other.line_comment_prefix && trim_blocks == other.trim_blocks &&
+	       lstrip_blocks == other.lstrip_blocks && keep_trailing_newline == other.keep_trailing_newline &&
+	       fuel == other.fuel && recursion_limit == other.recursion_limit &&
+	       null_as_undefined == other.null_as_undefined && pycompat == other.pycompat &&
+	       float_precision == other.float_precision && float_trailing_zeros == other.float_trailing_zeros &&
+	       autoescape_mode == other.autoescape_mode && now == other.now && random_seed == other.random_seed &&
+	       allow_env == other.allow_env && context_format == other.context_format &&
+	       debug == other.debug && timeout_ms == other.timeout_ms &&
+	       undefined_placeholder == other.undefined_placeholder && root_variable == other.root_variable &&
+	       strict_context == other.strict_context && big_integers_as_strings == other.big_integers_as_strings &&
+	       json_output == other.json_output &&
 	       optional_args == other.optional_args;
 }
 
+// Quotes a string as a JSON string literal.
+static string JsonQuote(const string &value) {
+	static const char *hex_digits = "0123456789abcdef";
+	string result = "\"";
+	for (const unsigned char c : value) {
+		if (c == '"' || c == '\\') {
+			result += '\\';
+			result += static_cast<char>(c);
+		} else if (c < 0x20) {
+			result += "\\u00";
+			result += hex_digits[c >> 4];
+			result += hex_digits[c & 0xf];
+		} else {
+			result += static_cast<char>(c);
+		}
+	}
+	return result + "\"";
+}
+
+// Builds the config of render_template_with_config, leaving out options that keep their default.
+static string RenderConfigJson(const MinijinjaRenderBindData &bind_data) {
+	vector<string> fields;
+	auto add = [&](const string &key, const string &json) {
+		fields.push_back(JsonQuote(key) + ": " + json);
+	};
+	auto add_string = [&](const string &key, const string &value) {
+		if (!value.empty()) {
+			add(key, JsonQuote(value));
+		}
+	};
+	auto add_bool = [&](const string &key, bool value) {
+		add(key, value ? "true" : "false");
+	};
+	auto add_delimiters = [&](const string &key, const string &start, const string &end) {
+		if (!start.empty()) {
+			add(key, "[" + JsonQuote(start) + ", " + JsonQuote(end) + "]");
+		}
+	};
+
+	add_string("template_path", bind_data.template_path);
+	add_bool("autoescape", bind_data.autoescape);
+	add_string("undefined_behavior",
+	           bind_data.undefined_behavior == "semi_strict" ? "semistrict" : bind_data.undefined_behavior);
+	if (!bind_data.autoescape_on.empty()) {
+		vector<string> extensions;
+		for (const auto &extension : bind_data.autoescape_on) {
+			extensions.push_back(JsonQuote(extension));
+		}
+		add("autoescape_extensions", "[" + StringUtil::Join(extensions, ", ") + "]");
+	}
+	add_delimiters("block_delimiters", bind_data.block_start, bind_data.block_end);
+	add_delimiters("variable_delimiters", bind_data.variable_start, bind_data.variable_end);
+	add_delimiters("comment_delimiters", bind_data.comment_start, bind_data.comment_end);
+	add_string("line_statement_prefix", bind_data.line_statement_prefix);
+	add_string("line_comment_prefix", bind_data.line_comment_prefix);
+	add_bool("trim_blocks", bind_data.trim_blocks);
+	add_bool("lstrip_blocks", bind_data.lstrip_blocks);
+	add_bool("keep_trailing_newline", bind_data.keep_trailing_newline);
+	if (bind_data.fuel > 0) {
+		add("fuel", std::to_string(bind_data.fuel));
+	}
+	if (bind_data.recursion_limit > 0) {
+		add("recursion_limit", std::to_string(bind_data.recursion_limit));
+	}
+	add_bool("null_as_undefined", bind_data.null_as_undefined);
+	add_bool("pycompat", bind_data.pycompat);
+	if (bind_data.float_precision >= 0) {
+		add("float_precision", std::to_string(bind_data.float_precision));
+	}
+	add_bool("float_trailing_zeros", bind_data.float_trailing_zeros);
+	add_string("autoescape_mode", bind_data.autoescape_mode);
+	add_string("now", bind_data.now);
+	if (bind_data.random_seed >= 0) {
+		add("random_seed", std::to_string(bind_data.random_seed));
+	}
+	add_bool("allow_env", bind_data.allow_env);
+	add_string("context_format", bind_data.context_format);
+	add_bool("debug", bind_data.debug);
+	if (bind_data.timeout_ms > 0) {
+		add("timeout_ms", std::to_string(bind_data.timeout_ms));
+	}
+	add_string("undefined_placeholder", bind_data.undefined_placeholder);
+	add_string("root_variable", bind_data.root_variable);
+	add_bool("strict_context", bind_data.strict_context);
+	add_bool("big_integers_as_strings", bind_data.big_integers_as_strings);
+	return "{" + StringUtil::Join(fields, ", ") + "}";
+}
+
+// Reads a delimiter pair passed as a two element VARCHAR list.
+static void BindDelimiters(ClientContext &context, const Expression &arg, const string &alias, string &start,
+                           string &end) {
+	if (arg.return_type.id() != LogicalTypeId::LIST ||
+	    ListType::GetChildType(arg.return_type).id() != LogicalTypeId::VARCHAR) {
+		throw BinderException(StringUtil::Format("minijinja_render: '%s' argument must be a list of two strings", alias));
+	}
+	const auto list_children = ListValue::GetChildren(ExpressionExecutor::EvaluateScalar(context, arg));
+	if (list_children.size() != 2 || list_children[0].IsNull() || list_children[1].IsNull()) {
+		throw BinderException(
+		    StringUtil::Format("minijinja_render: '%s' argument must contain a start and an end delimiter", alias));
+	}
+	start = list_children[0].GetValue<string>();
+	end = list_children[1].GetValue<string>();
+	if (start.empty() || end.empty()) {
+		throw BinderException(StringUtil::Format("minijinja_render: '%s' delimiters must not be empty", alias));
+	}
+}
+
 unique_ptr<FunctionData> MinijinjaRenderBind(ClientContext &context, ScalarFunction &bound_function,
                                              vector<unique_ptr<Expression>> &arguments) {
 	if (arguments.empty()) {
@@ -54,6 +227,30 @@ unique_ptr<FunctionData> MinijinjaRenderBind(ClientContext &context, ScalarFunct
 	string undefined_behavior = "lenient";
 	bool autoescape = true;
 	vector<string> autoescape_on;
+	string block_start, block_end;
+	string variable_start, variable_end;
+	string comment_start, comment_end;
+	string line_statement_prefix, line_comment_prefix;
+	bool trim_blocks = false;
+	bool lstrip_blocks = false;
+	bool keep_trailing_newline = false;
+	int64_t fuel = 0;
+	idx_t recursion_limit = 0;
+	bool null_as_undefined = false;
+	bool pycompat = false;
+	int32_t float_precision = -1;
+	bool float_trailing_zeros = true;
+	string autoescape_mode;
+	string now;
+	int64_t random_seed = -1;
+	bool allow_env = false;
+	string context_format;
+	bool debug = false;
+	uint64_t timeout_ms = 0;
+	string undefined_placeholder;
+	string root_variable;
+	bool strict_context = false;
+	bool big_integers_as_strings = false;
 	int optional_args = 0;
 
 	idx_t start_idx = bound_function.name == "minijinja_render" ? 1 : 2;
@@ -101,6 +298,50 @@ unique_ptr<FunctionData> MinijinjaRenderBind(ClientContext &context, ScalarFunct
 				                       undefined_behavior));
 			}
 
+		} else if (alias == "autoescape_mode") {
+			optional_args++;
+			if (arg->return_type.id() != LogicalTypeId::VARCHAR) {
+				throw BinderException("minijinja_render: 'autoescape_mode' argument must be a VARCHAR");
+			}
+			autoescape_mode = StringValue::Get(ExpressionExecutor::EvaluateScalar(context, *arg));
+
+			std::vector<string> valid_values = {"html", "json", "none", "auto"};
+
+			if (find(valid_values.begin(), valid_values.end(), autoescape_mode) == valid_values.end()) {
+				throw BinderException(
+				    StringUtil::Format("minijinja_render: 'autoescape_mode' argument has invalid value '%s' must be "
+				                       "one of: html, json, none, auto",
+				                       autoescape_mode));
+			}
+		} else if (alias == "context_format") {
+			optional_args++;
+			if (arg->return_type.id() != LogicalTypeId::VARCHAR) {
+				throw BinderException("minijinja_render: 'context_format' argument must be a VARCHAR");
+			}
+			context_format = StringValue::Get(ExpressionExecutor::EvaluateScalar(context, *arg));
+			if (context_format != "json" && context_format != "yaml") {
+				throw BinderException(StringUtil::Format(
+				    "minijinja_render: 'context_format' argument has invalid value '%s' must be one of: json, yaml",
+				    context_format));
+			}
+		} else if (alias == "now") {
+			optional_args++;
+			if (arg->return_type.id() != LogicalTypeId::VARCHAR) {
+				throw BinderException("minijinja_render: 'now' argument must be a VARCHAR");
+			}
+			now = StringValue::Get(ExpressionExecutor::EvaluateScalar(context, *arg));
+		} else if (alias == "undefined_placeholder") {
+			optional_args++;
+			if (arg->return_type.id() != LogicalTypeId::VARCHAR) {
+				throw BinderException("minijinja_render: 'undefined_placeholder' argument must be a VARCHAR");
+			}
+			undefined_placeholder = StringValue::Get(ExpressionExecutor::EvaluateScalar(context, *arg));
+		} else if (alias == "root_variable") {
+			optional_args++;
+			if (arg->return_type.id() != LogicalTypeId::VARCHAR) {
+				throw BinderException("minijinja_render: 'root_variable' argument must be a VARCHAR");
+			}
+			root_variable = StringValue::Get(ExpressionExecutor::EvaluateScalar(context, *arg));
 		} else if (alias == "autoescape_extensions") {
 			optional_args++;
 
@@ -121,13 +362,174 @@ unique_ptr<FunctionData> MinijinjaRenderBind(ClientContext &context, ScalarFunct
 
 				autoescape_on.push_back(list_item.GetValue<string>());
 			}
+		} else if (alias == "block_delimiters") {
+			optional_args++;
+			BindDelimiters(context, *arg, alias, block_start, block_end);
+		} else if (alias == "variable_delimiters") {
+			optional_args++;
+			BindDelimiters(context, *arg, alias, variable_start, variable_end);
+		} else if (alias == "comment_delimiters") {
+			optional_args++;
+			BindDelimiters(context, *arg, alias, comment_start, comment_end);
+		} else if (alias == "trim_blocks" || alias == "lstrip_blocks" || alias == "keep_trailing_newline" ||
+		           alias == "null_as_undefined" || alias == "pycompat" || alias == "float_trailing_zeros" ||
+		           alias == "allow_env" || alias == "debug" || alias == "strict_context" ||
+		           alias == "big_integers_as_strings") {
+			optional_args++;
+			if (arg->return_type.id() != LogicalTypeId::BOOLEAN) {
+				throw BinderException(StringUtil::Format("minijinja_render: '%s' argument must be a BOOLEAN", alias));
+			}
+			auto value = BooleanValue::Get(ExpressionExecutor::EvaluateScalar(context, *arg));
+			if (alias == "trim_blocks") {
+				trim_blocks = value;
+			} else if (alias == "lstrip_blocks") {
+				lstrip_blocks = value;
+			} else if (alias == "null_as_undefined") {
+				null_as_undefined = value;
+			} else if (alias == "pycompat") {
+				pycompat = value;
+			} else if (alias == "float_trailing_zeros") {
+				float_trailing_zeros = value;
+			} else if (alias == "allow_env") {
+				if (value && !DBConfig::GetConfig(context).options.enable_external_access) {
+					throw PermissionException(
+					    "minijinja_render: 'allow_env' is not allowed when external access is disabled");
+				}
+				allow_env = value;
+			} else if (alias == "debug") {
+				debug = value;
+			} else if (alias == "strict_context") {
+				strict_context = value;
+			} else if (alias == "big_integers_as_strings") {
+				big_integers_as_strings = value;
+			} else {
+				keep_trailing_newline = value;
+			}
+		} else if (alias == "fuel") {
+			optional_args++;
+			if (!arg->return_type.IsIntegral()) {
+				throw BinderException("minijinja_render: 'fuel' argument must be an integer");
+			}
+			fuel = ExpressionExecutor::EvaluateScalar(context, *arg).GetValue<int64_t>();
+		} else if (alias == "recursion_limit") {
+			optional_args++;
+			if (!arg->return_type.IsIntegral()) {
+				throw BinderException("minijinja_render: 'recursion_limit' argument must be an integer");
+			}
+			auto limit = ExpressionExecutor::EvaluateScalar(context, *arg).GetValue<int64_t>();
+			if (limit < 0) {
+				throw BinderException("minijinja_render: 'recursion_limit' argument must not be negative");
+			}
+			recursion_limit = static_cast<idx_t>(limit);
+		} else if (alias == "random_seed") {
+			optional_args++;
+			if (!arg->return_type.IsIntegral()) {
+				throw BinderException("minijinja_render: 'random_seed' argument must be an integer");
+			}
+			random_seed = ExpressionExecutor::EvaluateScalar(context, *arg).GetValue<int64_t>();
+			if (random_seed < 0) {
+				throw BinderException("minijinja_render: 'random_seed' argument must not be negative");
+			}
+		} else if (alias == "timeout_ms") {
+			optional_args++;
+			if (!arg->return_type.IsIntegral()) {
+				throw BinderException("minijinja_render: 'timeout_ms' argument must be an integer");
+			}
+			auto timeout = ExpressionExecutor::EvaluateScalar(context, *arg).GetValue<int64_t>();
+			if (timeout < 0) {
+				throw BinderException("minijinja_render: 'timeout_ms' argument must not be negative");
+			}
+			timeout_ms = static_cast<uint64_t>(timeout);
+		} else if (alias == "float_precision") {
+			optional_args++;
+			if (!arg->return_type.IsIntegral()) {
+				throw BinderException("minijinja_render: 'float_precision' argument must be an integer");
+			}
+			float_precision = ExpressionExecutor::EvaluateScalar(context, *arg).GetValue<int32_t>();
+			if (float_precision < 0) {
+				throw BinderException("minijinja_render: 'float_precision' argument must not be negative");
+			}
+		} else if (alias == "line_statement_prefix" || alias == "line_comment_prefix") {
+			optional_args++;
+			if (arg->return_type.id() != LogicalTypeId::VARCHAR) {
+				throw BinderException(StringUtil::Format("minijinja_render: '%s' argument must be a VARCHAR", alias));
+			}
+			auto prefix = StringValue::Get(ExpressionExecutor::EvaluateScalar(context, *arg));
+			if (alias == "line_statement_prefix") {
+				line_statement_prefix = prefix;
+			} else {
+				line_comment_prefix = prefix;
+			}
 		} else {
 			throw BinderException(StringUtil::Format("minijinja_render: Unknown argument '%s'", alias));
 		}
 	}
 
-	return make_uniq<MinijinjaRenderBindData>(template_path, autoescape, autoescape_on, undefined_behavior,
-	                                          optional_args);
+	auto result = make_uniq<MinijinjaRenderBindData>(template_path, autoescape, autoescape_on, undefined_behavior,
+	                                                 optional_args);
+	result->block_start = block_start;
+	result->block_end = block_end;
+	result->variable_start = variable_start;
+	result->variable_end = variable_end;
+	result->comment_start = comment_start;
+	result->comment_end = comment_end;
+	result->line_statement_prefix = line_statement_prefix;
+	result->line_comment_prefix = line_comment_prefix;
+	result->trim_blocks = trim_blocks;
+	result->lstrip_blocks = lstrip_blocks;
+	result->keep_trailing_newline = keep_trailing_newline;
+	result->fuel = fuel;
+	result->recursion_limit = recursion_limit;
+	result->null_as_undefined = null_as_undefined;
+	result->pycompat = pycompat;
+	result->float_precision = float_precision;
+	result->float_trailing_zeros = float_trailing_zeros;
+	result->autoescape_mode = autoescape_mode;
+	result->now = now;
+	result->random_seed = random_seed;
+	result->allow_env = allow_env;
+	result->context_format = context_format;
+	result->debug = debug;
+	result->timeout_ms = timeout_ms;
+	result->undefined_placeholder = undefined_placeholder;
+	result->root_variable = root_variable;
+	result->strict_context = strict_context;
+	result->big_integers_as_strings = big_integers_as_strings;
+	result->json_output = bound_function.name == "minijinja_render_json";
+	result->config_json = RenderConfigJson(*result);
+	return std::move(result);
+}
+
+// Renders a single template with the options captured at bind time.
+static string_t MinijinjaRenderOne(const MinijinjaRenderBindData &bind_data, Vector &result, string_t expression,
+                                   const char *context_json, idx_t context_json_len) {
+	const auto &config = bind_data.config_json;
+	if (bind_data.json_output) {
+		ResultCString eval_result =
+		    render_template_json_with_config(expression.GetData(), expression.GetSize(), context_json,
+		                                     context_json_len, config.c_str(), config.size());
+		if (eval_result.tag == ResultCString::Tag::Err) {
+			string err_str = string(eval_result.err._0);
+			free_result_cstring(eval_result);
+			throw InvalidInputException("Error rendering template: " + err_str);
+		}
+		auto vector_result = StringVector::AddString(result, eval_result.ok._0);
+		free_result_cstring(eval_result);
+		return vector_result;
+	}
+
+	// The output carries its length, so embedded null bytes are preserved.
+	ResultBytes eval_result = render_template_bytes_with_config(
+	    expression.GetData(), expression.GetSize(), context_json, context_json_len, config.c_str(), config.size());
+	if (!eval_result.is_ok) {
+		string err_str = string(reinterpret_cast<const char *>(eval_result.ptr), eval_result.len);
+		free_result_bytes(eval_result);
+		throw InvalidInputException("Error rendering template: " + err_str);
+	}
+	auto vector_result =
+	    StringVector::AddString(result, reinterpret_cast<const char *>(eval_result.ptr), eval_result.len);
+	free_result_bytes(eval_result);
+	return vector_result;
 }
 
 inline void MinijinjaRenderFunc(DataChunk &args, ExpressionState &state, Vector &result) {
@@ -140,53 +542,48 @@ inline void MinijinjaRenderFunc(DataChunk &args, ExpressionState &state, Vector
 	UnifiedVectorFormat expression_vector_unified;
 	expression_vector.ToUnifiedFormat(count, expression_vector_unified);
 
-	std::vector<const char *> autoescape_on_ptrs;
-	for (const auto &s : bind_data.autoescape_on) {
-		autoescape_on_ptrs.push_back(s.c_str());
-	}
-
 	if (args.ColumnCount() - bind_data.optional_args == 2) {
 		// There can be a context column.
 		auto &context_json_vector = args.data[1];
 		BinaryExecutor::Execute<string_t, string_t, string_t>(
 		    expression_vector, context_json_vector, result, args.size(),
 		    [&](string_t expression, string_t context_json) {
-			    ResultCString eval_result = render_template(
-			        expression.GetData(), expression.GetSize(), context_json.GetData(), context_json.GetSize(),
-			        bind_data.template_path.c_str(), bind_data.autoescape, bind_data.undefined_behavior.c_str(),
-			        autoescape_on_ptrs.data(), static_cast<int32_t>(autoescape_on_ptrs.size()));
-			    if (eval_result.tag == ResultCString::Tag::Err) {
-				    string err_str = string(eval_result.err._0);
-				    free_result_cstring(eval_result);
-				    throw InvalidInputException("Error rendering template: " + err_str);
-			    } else {
-				    auto vector_result = StringVector::AddString(result, eval_result.ok._0);
-				    free_result_cstring(eval_result);
-				    return vector_result;
-			    }
+			    return MinijinjaRenderOne(bind_data, result, expression, context_json.GetData(),
+			                              context_json.GetSize());
 		    });
 	} else if (args.ColumnCount() - bind_data.optional_args == 1) {
 		// No context column.
 		UnaryExecutor::Execute<string_t, string_t>(expression_vector, result, args.size(), [&](string_t expression) {
-			ResultCString eval_result =
-			    render_template(expression.GetData(), expression.GetSize(), "{}", 2, bind_data.template_path.c_str(),
-			                    bind_data.autoescape, bind_data.undefined_behavior.c_str(), autoescape_on_ptrs.data(),
-			                    static_cast<int32_t>(autoescape_on_ptrs.size()));
-			if (eval_result.tag == ResultCString::Tag::Err) {
-				string err_str = string(eval_result.err._0);
-				free_result_cstring(eval_result);
-				throw InvalidInputException("Error rendering template: " + err_str);
-			} else {
-				auto vector_result = StringVector::AddString(result, eval_result.ok._0);
-				free_result_cstring(eval_result);
-				return vector_result;
-			}
+			return MinijinjaRenderOne(bind_data, result, expression, "{}", 2);
 		});
 	} else {
 		throw InvalidInputException("Invalid number of arguments to minijinja_render");
 	}
 }
 
+// Lists the variables a template references that are missing from its context, as a JSON array.
+static void MinijinjaMissingVariablesFunc(DataChunk &args, ExpressionState &state, Vector &result) {
+	BinaryExecutor::Execute<string_t, string_t, string_t>(
+	    args.data[0], args.data[1], result, args.size(), [&](string_t template_str, string_t context_json) {
+		    auto missing = mj_missing_variables(template_str.GetData(), template_str.GetSize(),
+		                                        context_json.GetData(), context_json.GetSize());
+		    if (missing.tag == ResultCString::Tag::Err) {
+			    std::string err_str(missing.err._0);
+			    free_result_cstring(missing);
+			    throw InvalidInputException("Error analysing template: " + err_str);
+		    }
+		    auto vector_result = StringVector::AddString(result, missing.ok._0);
+		    free_result_cstring(missing);
+		    return vector_result;
+	    });
+}
+
+// Returns the MiniJinja version and the version of the binding, for bug reports.
+static void MinijinjaVersionFunc(DataChunk &args, ExpressionState &state, Vector &result) {
+	result.SetVectorType(VectorType::CONSTANT_VECTOR);
+	ConstantVector::GetData<string_t>(result)[0] = StringVector::AddString(result, mj_version());
+}
+
 // Extension initalization.
 static void LoadInternal(ExtensionLoader &loader) {
 	// Register minijinja_render (template only, no context)
@@ -223,6 +620,14 @@ static void LoadInternal(ExtensionLoader &loader) {
 		render_with_context.stability = FunctionStability::VOLATILE;
 		render_with_ctx.AddFunction(render_with_context);
 
+		// A VARCHAR context is passed through unparsed, so it may also hold YAML.
+		auto render_with_text_context =
+		    ScalarFunction({LogicalType::VARCHAR, LogicalType::VARCHAR}, LogicalType::VARCHAR, MinijinjaRenderFunc,
+		                   MinijinjaRenderBind, nullptr, nullptr, nullptr, LogicalType(LogicalTypeId::ANY));
+		render_with_text_context.null_handling = FunctionNullHandling::SPECIAL_HANDLING;
+		render_with_text_context.stability = FunctionStability::VOLATILE;
+		render_with_ctx.AddFunction(render_with_text_context);
+
 		CreateScalarFunctionInfo info(render_with_ctx);
 
 		FunctionDescription desc_with_context;
@@ -235,6 +640,72 @@ static void LoadInternal(ExtensionLoader &loader) {
 		loader.RegisterFunction(info);
 	}
 
+	// Register minijinja_render_json (template + JSON context, JSON result)
+	{
+		ScalarFunctionSet render_json("minijinja_render_json");
+
+		auto render_json_with_context =
+		    ScalarFunction({LogicalType::VARCHAR, LogicalType::JSON()}, LogicalType::JSON(), MinijinjaRenderFunc,
+		                   MinijinjaRenderBind, nullptr, nullptr, nullptr, LogicalType(LogicalTypeId::ANY));
+		render_json_with_context.null_handling = FunctionNullHandling::SPECIAL_HANDLING;
+		render_json_with_context.stability = FunctionStability::VOLATILE;
+		render_json.AddFunction(render_json_with_context);
+
+		auto render_json_with_text_context =
+		    ScalarFunction({LogicalType::VARCHAR, LogicalType::VARCHAR}, LogicalType::JSON(), MinijinjaRenderFunc,
+		                   MinijinjaRenderBind, nullptr, nullptr, nullptr, LogicalType(LogicalTypeId::ANY));
+		render_json_with_text_context.null_handling = FunctionNullHandling::SPECIAL_HANDLING;
+		render_json_with_text_context.stability = FunctionStability::VOLATILE;
+		render_json.AddFunction(render_json_with_text_context);
+
+		CreateScalarFunctionInfo info(render_json);
+
+		FunctionDescription desc_json;
+		desc_json.description = "Render a Jinja2-style template with a JSON context and return the result as JSON, "
+		                        "keeping the type of a template that is a single expression";
+		desc_json.parameter_types = {LogicalType::VARCHAR, LogicalType::JSON()};
+		desc_json.parameter_names = {"template", "context"};
+		desc_json.examples = {"minijinja_render_json('{{ items | length }}', '{\"items\": [1, 2, 3]}')"};
+		info.descriptions.push_back(desc_json);
+
+		loader.RegisterFunction(info);
+	}
+
+	// Register minijinja_missing_variables (template + JSON context, JSON result)
+	{
+		ScalarFunctionSet missing_variables("minijinja_missing_variables");
+		missing_variables.AddFunction(ScalarFunction({LogicalType::VARCHAR, LogicalType::JSON()}, LogicalType::JSON(),
+		                                             MinijinjaMissingVariablesFunc));
+		missing_variables.AddFunction(ScalarFunction({LogicalType::VARCHAR, LogicalType::VARCHAR}, LogicalType::JSON(),
+		                                             MinijinjaMissingVariablesFunc));
+
+		CreateScalarFunctionInfo info(missing_variables);
+
+		FunctionDescription desc_missing;
+		desc_missing.description =
+		    "List the variables a template references that are missing from a JSON context, as a JSON array";
+		desc_missing.parameter_types = {LogicalType::VARCHAR, LogicalType::JSON()};
+		desc_missing.parameter_names = {"template", "context"};
+		desc_missing.examples = {
+		    "minijinja_missing_variables('{{ customer_id }} {{ region }} {{ name }}', '{\"name\": \"Ann\"}')"};
+		info.descriptions.push_back(desc_missing);
+
+		loader.RegisterFunction(info);
+	}
+
+	// Register minijinja_version (no arguments)
+	{
+		ScalarFunction version("minijinja_version", {}, LogicalType::VARCHAR, MinijinjaVersionFunc);
+		CreateScalarFunctionInfo info(version);
+
+		FunctionDescription desc_version;
+		desc_version.description = "Return the MiniJinja version the extension was built with";
+		desc_version.examples = {"minijinja_version()"};
+		info.descriptions.push_back(desc_version);
+
+		loader.RegisterFunction(info);
+	}
+
 	QueryFarmSendTelemetry(loader, "minijinja", MINIJINJA_EXTENSION_VERSION);
 }
 